@@ -2,4 +2,28 @@ pub mod player;
 pub mod network;
 pub mod auth;
 pub mod hash;
-pub mod client;
\ No newline at end of file
+pub mod client;
+pub mod block;
+pub mod world;
+pub mod movement;
+pub mod tracker;
+pub mod tablist;
+pub mod entity;
+pub mod dimension;
+pub mod portal;
+pub mod digging;
+pub mod placement;
+pub mod inventory;
+pub mod item;
+pub mod container;
+pub mod recipe;
+pub mod health;
+pub mod gamerule;
+pub mod spawn;
+pub mod command;
+pub mod userlist;
+pub mod rcon;
+pub mod permission;
+pub mod text;
+pub mod chat;
+pub mod playerdata;
\ No newline at end of file
@@ -14,7 +14,7 @@ const REFRESH_URL: &str = "https://authserver.mojang.com/refresh";
 pub struct Profile {}
 
 
-pub async fn verify_join(username: &str, server_id: String, shared_secret: &[u8], public_key: &[u8]) -> Result<(String, UUID4)> {
+pub async fn verify_join(username: &str, server_id: String, shared_secret: &[u8], public_key: &[u8]) -> Result<(String, UUID4, Vec<ProfileProperty>)> {
     let client = reqwest::Client::new();
     let response = client.get(
         (HAS_JOINED_SERVER_URL.to_owned()
@@ -28,7 +28,7 @@ pub async fn verify_join(username: &str, server_id: String, shared_secret: &[u8]
     if let Ok(response) = response {
         if let Ok(text) = response.text().await {
             if let Ok(join) = serde_json::from_str::<ServerJoinResponse>(&text) {
-                return Ok((join.name, join.id));
+                return Ok((join.name, join.id, join.properties));
             }
             return Err(anyhow!("Bad response!"));
         }
@@ -107,9 +107,19 @@ struct JoinRequest {
     serverId: String,
 }
 
+/// Signed property attached to a profile, such as the player's skin textures.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
 /// Response to a request checking if a plyer is authenticated.
 #[derive(Serialize, Deserialize, Clone)]
 struct ServerJoinResponse {
     id: UUID4,
     name: String,
+    #[serde(default)]
+    properties: Vec<ProfileProperty>,
 }
@@ -0,0 +1,136 @@
+/// A block type known to the server, covering the contiguous range of protocol state ids
+/// the block occupies in 1.16.3.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    /// Namespaced identifier, e.g. `minecraft:stone`.
+    pub name: &'static str,
+    /// First state id belonging to this block.
+    pub min_state: u32,
+    /// Last state id belonging to this block.
+    pub max_state: u32,
+    /// State placed when no other properties are known.
+    pub default_state: u32,
+    /// Time factor used to compute break time, negative if the block is unbreakable.
+    pub hardness: f32,
+    /// Whether entities collide with the block.
+    pub solid: bool,
+}
+
+impl Block {
+    const fn new(name: &'static str, min_state: u32, max_state: u32, default_state: u32, hardness: f32, solid: bool) -> Self {
+        Self {
+            name,
+            min_state,
+            max_state,
+            default_state,
+            hardness,
+            solid,
+        }
+    }
+
+    pub fn contains_state(&self, state: u32) -> bool {
+        state >= self.min_state && state <= self.max_state
+    }
+}
+
+pub const AIR: u32 = 0;
+pub const STONE: u32 = 1;
+pub const GRASS_BLOCK: u32 = 9;
+pub const DIRT: u32 = 10;
+pub const BEDROCK: u32 = 33;
+pub const OBSIDIAN: u32 = 1434;
+pub const FIRE: u32 = 1471;
+/// Nether portal with its plane along the x axis.
+pub const NETHER_PORTAL_X: u32 = 4014;
+/// Nether portal with its plane along the z axis.
+pub const NETHER_PORTAL_Z: u32 = 4015;
+pub const END_PORTAL: u32 = 5138;
+pub const END_STONE: u32 = 5147;
+pub const END_GATEWAY: u32 = 9224;
+
+/// Blocks currently understood by Endstone, ordered by state id.
+pub const BLOCKS: &[Block] = &[
+    Block::new("minecraft:air", 0, 0, 0, 0.0, false),
+    Block::new("minecraft:stone", 1, 1, 1, 1.5, true),
+    Block::new("minecraft:granite", 2, 2, 2, 1.5, true),
+    Block::new("minecraft:polished_granite", 3, 3, 3, 1.5, true),
+    Block::new("minecraft:diorite", 4, 4, 4, 1.5, true),
+    Block::new("minecraft:polished_diorite", 5, 5, 5, 1.5, true),
+    Block::new("minecraft:andesite", 6, 6, 6, 1.5, true),
+    Block::new("minecraft:polished_andesite", 7, 7, 7, 1.5, true),
+    Block::new("minecraft:grass_block", 8, 9, 9, 0.6, true),
+    Block::new("minecraft:dirt", 10, 10, 10, 0.5, true),
+    Block::new("minecraft:coarse_dirt", 11, 11, 11, 0.5, true),
+    Block::new("minecraft:podzol", 12, 13, 13, 0.5, true),
+    Block::new("minecraft:cobblestone", 14, 14, 14, 2.0, true),
+    Block::new("minecraft:oak_planks", 15, 15, 15, 2.0, true),
+    Block::new("minecraft:spruce_planks", 16, 16, 16, 2.0, true),
+    Block::new("minecraft:birch_planks", 17, 17, 17, 2.0, true),
+    Block::new("minecraft:jungle_planks", 18, 18, 18, 2.0, true),
+    Block::new("minecraft:acacia_planks", 19, 19, 19, 2.0, true),
+    Block::new("minecraft:dark_oak_planks", 20, 20, 20, 2.0, true),
+    Block::new("minecraft:bedrock", 33, 33, 33, -1.0, true),
+    Block::new("minecraft:water", 34, 49, 34, 100.0, false),
+    Block::new("minecraft:lava", 50, 65, 50, 100.0, false),
+    Block::new("minecraft:sand", 66, 66, 66, 0.5, true),
+    Block::new("minecraft:red_sand", 67, 67, 67, 0.5, true),
+    Block::new("minecraft:gravel", 68, 68, 68, 0.6, true),
+    Block::new("minecraft:gold_ore", 69, 69, 69, 3.0, true),
+    Block::new("minecraft:iron_ore", 70, 70, 70, 3.0, true),
+    Block::new("minecraft:coal_ore", 71, 71, 71, 3.0, true),
+    Block::new("minecraft:nether_gold_ore", 72, 72, 72, 3.0, true),
+    Block::new("minecraft:oak_log", 73, 75, 74, 2.0, true),
+    Block::new("minecraft:spruce_log", 76, 78, 77, 2.0, true),
+    Block::new("minecraft:birch_log", 79, 81, 80, 2.0, true),
+    Block::new("minecraft:jungle_log", 82, 84, 83, 2.0, true),
+    Block::new("minecraft:acacia_log", 85, 87, 86, 2.0, true),
+    Block::new("minecraft:dark_oak_log", 88, 90, 89, 2.0, true),
+    Block::new("minecraft:white_bed", 1049, 1064, 1052, 0.2, false),
+    Block::new("minecraft:orange_bed", 1065, 1080, 1068, 0.2, false),
+    Block::new("minecraft:magenta_bed", 1081, 1096, 1084, 0.2, false),
+    Block::new("minecraft:light_blue_bed", 1097, 1112, 1100, 0.2, false),
+    Block::new("minecraft:yellow_bed", 1113, 1128, 1116, 0.2, false),
+    Block::new("minecraft:lime_bed", 1129, 1144, 1132, 0.2, false),
+    Block::new("minecraft:pink_bed", 1145, 1160, 1148, 0.2, false),
+    Block::new("minecraft:gray_bed", 1161, 1176, 1164, 0.2, false),
+    Block::new("minecraft:light_gray_bed", 1177, 1192, 1180, 0.2, false),
+    Block::new("minecraft:cyan_bed", 1193, 1208, 1196, 0.2, false),
+    Block::new("minecraft:purple_bed", 1209, 1224, 1212, 0.2, false),
+    Block::new("minecraft:blue_bed", 1225, 1240, 1228, 0.2, false),
+    Block::new("minecraft:brown_bed", 1241, 1256, 1244, 0.2, false),
+    Block::new("minecraft:green_bed", 1257, 1272, 1260, 0.2, false),
+    Block::new("minecraft:red_bed", 1273, 1288, 1276, 0.2, false),
+    Block::new("minecraft:black_bed", 1289, 1304, 1292, 0.2, false),
+    Block::new("minecraft:obsidian", 1434, 1434, 1434, 50.0, true),
+    Block::new("minecraft:fire", 1440, 1951, 1471, 0.0, false),
+    Block::new("minecraft:oak_stairs", 1954, 2033, 1965, 2.0, true),
+    Block::new("minecraft:chest", 2034, 2057, 2035, 2.5, true),
+    Block::new("minecraft:crafting_table", 3356, 3356, 3356, 2.5, true),
+    Block::new("minecraft:furnace", 3373, 3380, 3374, 3.5, true),
+    Block::new("minecraft:nether_portal", 4014, 4015, 4014, -1.0, false),
+    Block::new("minecraft:end_portal", 5138, 5138, 5138, -1.0, false),
+    Block::new("minecraft:end_portal_frame", 5139, 5146, 5143, -1.0, true),
+    Block::new("minecraft:end_stone", 5147, 5147, 5147, 3.0, true),
+    Block::new("minecraft:end_gateway", 9224, 9224, 9224, -1.0, false),
+    Block::new("minecraft:barrel", 14791, 14802, 14792, 2.5, true),
+    Block::new("minecraft:respawn_anchor", 15829, 15833, 15829, 50.0, true),
+];
+
+pub fn get_block(state: u32) -> Option<&'static Block> {
+    BLOCKS.iter().find(|block| block.contains_state(state))
+}
+
+pub fn get_block_by_name(name: &str) -> Option<&'static Block> {
+    let name = if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{}", name)
+    };
+
+    BLOCKS.iter().find(|block| block.name == name)
+}
+
+/// Unknown states are treated as solid so that unmapped blocks still collide.
+pub fn is_solid(state: u32) -> bool {
+    get_block(state).map(|block| block.solid).unwrap_or(true)
+}
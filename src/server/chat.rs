@@ -0,0 +1,229 @@
+use std::time::Instant;
+use mcproto_rs::uuid::UUID4;
+use mcproto_rs::v1_16_3::{ChatPosition, ClientChatMode};
+use crate::server::network::Server;
+use crate::server::player::Position;
+
+/// Longest chat message or command a client may send, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 256;
+/// Invalid messages a player may send before being kicked.
+pub const MAX_INVALID_MESSAGES: u32 = 3;
+/// Messages a player may send in a burst before being kicked for spamming.
+const SPAM_BURST: f64 = 10.0;
+/// Messages per second a player may keep sending, like vanilla's one per 20 ticks.
+const SPAM_RATE: f64 = 1.0;
+/// Channel players start in, which can not be removed.
+pub const GLOBAL_CHANNEL: &str = "global";
+/// Lets players talk in and read the staff channel.
+pub const STAFF_CHANNEL_PERMISSION: &str = "endstone.chat.channel.staff";
+/// How far messages in the local channel carry, in blocks.
+const LOCAL_CHAT_RADIUS: f64 = 100.0;
+
+/// Why a chat message was rejected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidMessage {
+    TooLong,
+    /// Nothing was left after removing illegal characters.
+    Empty,
+}
+
+/// Checks a chat message, returning it with illegal characters removed: the section sign, which
+/// would let players use formatting codes, and control characters.
+pub fn validate_message(message: &str) -> Result<String, InvalidMessage> {
+    if message.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(InvalidMessage::TooLong);
+    }
+    let cleaned = message.chars().filter(|c| *c != '§' && !c.is_control()).collect::<String>();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() {
+        return Err(InvalidMessage::Empty);
+    }
+    Ok(cleaned.to_string())
+}
+
+/// Whether a client with the given chat visibility setting wants messages sent to a position.
+pub fn is_visible(mode: &ClientChatMode, position: &ChatPosition) -> bool {
+    match (mode, position) {
+        (_, ChatPosition::Hotbar) => true,
+        (ClientChatMode::Enabled, _) => true,
+        (ClientChatMode::CommandsOnly, ChatPosition::SystemMessage) => true,
+        _ => false,
+    }
+}
+
+/// Per-player chat bookkeeping: a token bucket limiting how fast they may chat, and how many
+/// invalid messages they have sent.
+#[derive(Clone, Debug)]
+pub struct ChatState {
+    tokens: f64,
+    last_refill: Instant,
+    pub invalid_messages: u32,
+}
+
+impl ChatState {
+    pub fn new() -> Self {
+        Self { tokens: SPAM_BURST, last_refill: Instant::now(), invalid_messages: 0 }
+    }
+
+    /// Spends a token for a message, returning false if the player has run out and is spamming.
+    pub fn take_token(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * SPAM_RATE).min(SPAM_BURST);
+        self.last_refill = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    /// Counts an invalid message, returning whether the player has now sent too many.
+    pub fn record_invalid(&mut self) -> bool {
+        self.invalid_messages += 1;
+        self.invalid_messages >= MAX_INVALID_MESSAGES
+    }
+}
+
+impl Default for ChatState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A player as seen by channel filters, copied so that no client needs to be locked while the
+/// filters run.
+#[derive(Clone, Debug)]
+pub struct ChatMember {
+    pub name: String,
+    pub uuid: UUID4,
+    pub position: Position,
+}
+
+/// Decides who may talk in a channel and who reads it. Plugins implement this to add channels of
+/// their own, see `Server::channels_mut`.
+pub trait ChannelFilter: Send + Sync {
+    /// Whether a player may switch to the channel.
+    fn can_join(&self, _server: &Server, _member: &ChatMember) -> bool {
+        true
+    }
+
+    /// Whether a message reaches a player. `sender` is `None` for messages from the console.
+    fn receives(&self, server: &Server, sender: Option<&ChatMember>, receiver: &ChatMember) -> bool;
+}
+
+/// Everyone reads everything.
+pub struct GlobalFilter;
+
+impl ChannelFilter for GlobalFilter {
+    fn receives(&self, _server: &Server, _sender: Option<&ChatMember>, _receiver: &ChatMember) -> bool {
+        true
+    }
+}
+
+/// Only players with a permission may talk and read.
+pub struct PermissionFilter {
+    pub permission: String,
+}
+
+impl ChannelFilter for PermissionFilter {
+    fn can_join(&self, server: &Server, member: &ChatMember) -> bool {
+        server.has_permission(&member.name, Some(member.uuid), &self.permission)
+    }
+
+    fn receives(&self, server: &Server, _sender: Option<&ChatMember>, receiver: &ChatMember) -> bool {
+        self.can_join(server, receiver)
+    }
+}
+
+/// Messages reach players in the sender's world within a distance of them.
+pub struct RadiusFilter {
+    pub radius: f64,
+}
+
+impl ChannelFilter for RadiusFilter {
+    fn receives(&self, _server: &Server, sender: Option<&ChatMember>, receiver: &ChatMember) -> bool {
+        let sender = match sender {
+            Some(sender) => &sender.position,
+            None => return true,
+        };
+        let receiver = &receiver.position;
+        let (dx, dy, dz) = (sender.x - receiver.x, sender.y - receiver.y, sender.z - receiver.z);
+        sender.world == receiver.world && dx * dx + dy * dy + dz * dz <= self.radius * self.radius
+    }
+}
+
+/// A named chat channel players can switch to with `/channel`.
+pub struct Channel {
+    pub name: String,
+    /// Rendered with `{name}` and `{message}` instead of the server's chat template when set.
+    pub template: Option<String>,
+    pub filter: Box<dyn ChannelFilter>,
+}
+
+impl Channel {
+    pub fn new(name: &str, filter: Box<dyn ChannelFilter>) -> Self {
+        Self { name: name.to_lowercase(), template: None, filter }
+    }
+
+    pub fn with_template(mut self, template: &str) -> Self {
+        self.template = Some(template.to_string());
+        self
+    }
+}
+
+/// The chat channels of a server, in the order they were registered.
+pub struct Channels {
+    channels: Vec<Channel>,
+}
+
+impl Channels {
+    /// The global channel, a staff channel for players with `endstone.chat.channel.staff` and a
+    /// local channel.
+    pub fn new() -> Self {
+        let mut channels = Self { channels: vec![] };
+        channels.register(Channel::new(GLOBAL_CHANNEL, Box::new(GlobalFilter)));
+        channels.register(Channel::new("staff", Box::new(PermissionFilter { permission: STAFF_CHANNEL_PERMISSION.to_string() }))
+            .with_template("&b[Staff] &r<{name}> {message}"));
+        channels.register(Channel::new("local", Box::new(RadiusFilter { radius: LOCAL_CHAT_RADIUS }))
+            .with_template("&7[Local] &r<{name}> {message}"));
+        channels
+    }
+
+    /// Adds a channel, replacing any with the same name.
+    pub fn register(&mut self, channel: Channel) {
+        match self.channels.iter_mut().find(|existing| existing.name == channel.name) {
+            Some(existing) => *existing = channel,
+            None => self.channels.push(channel),
+        }
+    }
+
+    /// Removes a channel. Players in it fall back to the global channel, which itself can not be
+    /// removed.
+    pub fn remove(&mut self, name: &str) -> Option<Channel> {
+        if name.eq_ignore_ascii_case(GLOBAL_CHANNEL) {
+            return None;
+        }
+        let index = self.channels.iter().position(|channel| channel.name.eq_ignore_ascii_case(name))?;
+        Some(self.channels.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Channel> {
+        self.channels.iter().find(|channel| channel.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The channel with the given name, or the global channel if it no longer exists.
+    pub fn get_or_global(&self, name: &str) -> &Channel {
+        self.get(name).or_else(|| self.get(GLOBAL_CHANNEL)).unwrap()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Channel> {
+        self.channels.iter()
+    }
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,38 +1,72 @@
 use std::sync::Arc;
 use mcproto_rs::{v1_16_3 as proto, v1_16_3::Packet753 as Packet, v1_16_3::RawPacket753 as RawPacket};
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 use anyhow::{anyhow, Result};
-use craftio_rs::{CraftAsyncReader, CraftAsyncWriter, CraftConnection, CraftIo, CraftSyncReader, CraftSyncWriter, CraftTcpConnection, CraftTokioConnection};
+use craftio_rs::{CraftAsyncReader, CraftAsyncWriter, CraftConnection, CraftIo, CraftReader, CraftSyncReader, CraftSyncWriter, CraftTcpConnection, CraftTokioConnection, CraftWriter};
 use mcproto_rs::protocol::{PacketDirection, State};
 use mcproto_rs::v1_16_3::HandshakeNextState;
 use tokio::io::BufReader;
 use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 
+/// Handle to a client connection. The reading and writing halves are locked separately so that
+/// packets can be sent to a client while another task is waiting on its next packet.
+#[derive(Clone)]
 pub struct Client {
-    connection: Arc<Mutex<CraftTokioConnection>>,
+    reader: Arc<Mutex<CraftReader<BufReader<OwnedReadHalf>>>>,
+    writer: Arc<Mutex<CraftWriter<OwnedWriteHalf>>>,
+    /// Set once the server gives up on the client, see `close`.
+    closed: Arc<watch::Sender<bool>>,
 }
 
 impl Client {
     pub fn from_tcp_stream(connection: TcpStream) -> Self {
         let split = connection.into_split();
+        let (reader, writer) = CraftTokioConnection::from_async((BufReader::new(split.0), split.1), PacketDirection::ServerBound).into_split();
         Self {
-            connection: Arc::new(Mutex::new(CraftTokioConnection::from_async((BufReader::new(split.0), split.1), PacketDirection::ServerBound))),
+            reader: Arc::new(Mutex::new(reader)),
+            writer: Arc::new(Mutex::new(writer)),
+            closed: Arc::new(watch::channel(false).0),
         }
     }
 
+    /// Stops serving the client: reads, including one already waiting, return no packet and
+    /// writes are dropped. The socket is released once the tasks serving the client end.
+    pub fn close(&self) {
+        let _ = self.closed.send(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
     pub async fn write_packet(&mut self, packet: Packet) -> Result<()> {
+        if self.is_closed() {
+            return Ok(());
+        }
         println!("Server -> Client: {:?}", packet);
-        self.connection.lock().await.write_packet_async(packet).await;
+        self.writer.lock().await.write_packet_async(packet).await;
         Ok(())
     }
 
     pub async fn read_next_packet(&mut self) -> Result<Option<Packet>> {
-        if let Some(raw) = self.connection.clone().lock().await.read_raw_packet_async::<RawPacket>().await? {
-            println!("Client -> Server: {:?}", &raw);
-            Ok(Some(mcproto_rs::protocol::RawPacket::deserialize(&raw)?))
-            // Ok(Some(raw))
-        } else {
-            Ok(None)
+        let mut closed = self.closed.subscribe();
+        if *closed.borrow() {
+            return Ok(None);
+        }
+        let reader = self.reader.clone();
+        let mut reader = reader.lock().await;
+        tokio::select! {
+            raw = reader.read_raw_packet_async::<RawPacket>() => {
+                if let Some(raw) = raw? {
+                    println!("Client -> Server: {:?}", &raw);
+                    Ok(Some(mcproto_rs::protocol::RawPacket::deserialize(&raw)?))
+                    // Ok(Some(raw))
+                } else {
+                    Ok(None)
+                }
+            }
+            _ = closed.changed() => Ok(None),
         }
     }
 
@@ -59,7 +93,8 @@ impl Client {
     }
 
     pub async fn set_state(&mut self, state: State) {
-        self.connection.lock().await.set_state(state);
+        self.reader.lock().await.set_state(state);
+        self.writer.lock().await.set_state(state);
         /*
         self.reader.lock().await.set_state(state.clone());
         self.writer.lock().await.set_state(state);
@@ -67,17 +102,17 @@ impl Client {
     }
 
     pub async fn set_compression_threshold(&mut self, threshold: i32) {
-        self.connection.lock().await.set_compression_threshold(Some(threshold));
-        self.connection.lock().await.set_compression_threshold(Some(threshold));
+        self.reader.lock().await.set_compression_threshold(Some(threshold));
+        self.writer.lock().await.set_compression_threshold(Some(threshold));
     }
 
     pub async fn enable_encryption(&mut self, key: &[u8], iv: &[u8]) -> Result<()> {
-        let reader = self.connection.lock().await.enable_encryption(key, iv);
+        let reader = self.reader.lock().await.enable_encryption(key, iv);
 
         if let Err(error) = reader {
             Err(anyhow!("Encryption Error {:?}", error))
         } else {
-            self.connection.lock().await.enable_encryption(key, iv);
+            self.writer.lock().await.enable_encryption(key, iv);
             Ok(())
         }
     }
@@ -0,0 +1,944 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Error, Result};
+use rand::Rng;
+use mcproto_rs::types::{CountedArray, VarInt};
+use mcproto_rs::v1_16_3::{CommandArgumentNodeSpec, CommandLiteralNodeSpec, CommandNode as NodeSpec, CommandNodeSpec, CommandParserSpec, DoubleParserProps, EntityParserFlags, GameMode, IntegerParserProps, Packet753 as Packet, PlayDeclareCommandsSpec, StringParserMode, SuggestionsTypeSpec};
+use crate::server::item::{self, Item};
+use crate::server::player::Position;
+
+/// How many characters of input are shown before the cursor in syntax errors.
+const ERROR_CONTEXT: usize = 10;
+
+/// A cursor over command input, mirroring Brigadier's `StringReader`.
+#[derive(Clone)]
+pub struct StringReader<'a> {
+    input: &'a str,
+    pub cursor: usize,
+}
+
+impl<'a> StringReader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, cursor: 0 }
+    }
+
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.cursor..]
+    }
+
+    pub fn can_read(&self) -> bool {
+        self.cursor < self.input.len()
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.remaining().chars().next()
+    }
+
+    pub fn skip(&mut self) {
+        if let Some(c) = self.peek() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    /// Reads up to the next space.
+    pub fn read_word(&mut self) -> &'a str {
+        let remaining = self.remaining();
+        let word = &remaining[..remaining.find(' ').unwrap_or(remaining.len())];
+        self.cursor += word.len();
+        word
+    }
+
+    /// Reads a word, or a string in double or single quotes with `\` escapes.
+    pub fn read_string(&mut self) -> Result<String> {
+        let quote = match self.peek() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Ok(self.read_word().to_string()),
+        };
+        let start = self.cursor;
+        self.skip();
+        let mut result = String::new();
+        let mut escaped = false;
+        while let Some(c) = self.peek() {
+            self.skip();
+            if escaped {
+                if c != quote && c != '\\' {
+                    self.cursor -= c.len_utf8();
+                    return Err(self.error(&format!("Invalid escape sequence '{}' in quoted string", c)));
+                }
+                result.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return Ok(result);
+            } else {
+                result.push(c);
+            }
+        }
+        self.cursor = start;
+        Err(self.error("Unclosed quoted string"))
+    }
+
+    pub fn read_int(&mut self) -> Result<i32> {
+        let start = self.cursor;
+        let number = self.read_number();
+        number.parse().map_err(|_| {
+            self.cursor = start;
+            self.error(&format!("Invalid integer '{}'", number))
+        })
+    }
+
+    pub fn read_double(&mut self) -> Result<f64> {
+        let start = self.cursor;
+        let number = self.read_number();
+        number.parse().map_err(|_| {
+            self.cursor = start;
+            self.error(&format!("Invalid double '{}'", number))
+        })
+    }
+
+    fn read_number(&mut self) -> &'a str {
+        let remaining = self.remaining();
+        let length = remaining
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(remaining.len());
+        // `1..5` is a range, so the number stops at the first of two dots.
+        let length = remaining[..length].find("..").unwrap_or(length);
+        self.cursor += length;
+        &remaining[..length]
+    }
+
+    /// A syntax error pointing at the cursor, formatted like the vanilla client shows them.
+    pub fn error(&self, message: &str) -> Error {
+        let before = &self.input[..self.cursor];
+        let context = match before.char_indices().rev().nth(ERROR_CONTEXT - 1) {
+            Some((index, _)) if index > 0 => format!("...{}", &before[index..]),
+            _ => before.to_string(),
+        };
+        anyhow!("{} at position {}: {}<--[HERE]", message, self.cursor, context)
+    }
+}
+
+/// One component of a position argument.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coordinate {
+    pub value: f64,
+    /// Given with `~`, relative to the sender.
+    pub relative: bool,
+}
+
+/// A position argument, either in world coordinates or in `^` coordinates local to where the
+/// sender is looking.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coordinates {
+    World([Coordinate; 3]),
+    /// Left, up and forwards.
+    Local([f64; 3]),
+}
+
+impl Coordinates {
+    fn parse(reader: &mut StringReader, integer: bool) -> Result<Self> {
+        let start = reader.cursor;
+        if reader.peek() == Some('^') {
+            let mut values = [0.0; 3];
+            for (i, value) in values.iter_mut().enumerate() {
+                if i > 0 {
+                    expect_space(reader, start)?;
+                }
+                if reader.peek() != Some('^') {
+                    return Err(reader.error("Cannot mix world & local coordinates (everything must either use ^ or not)"));
+                }
+                reader.skip();
+                *value = read_offset(reader)?;
+            }
+            return Ok(Coordinates::Local(values));
+        }
+
+        let mut coordinates = [Coordinate { value: 0.0, relative: false }; 3];
+        for (i, coordinate) in coordinates.iter_mut().enumerate() {
+            if i > 0 {
+                expect_space(reader, start)?;
+            }
+            match reader.peek() {
+                Some('^') => return Err(reader.error("Cannot mix world & local coordinates (everything must either use ^ or not)")),
+                Some('~') => {
+                    reader.skip();
+                    *coordinate = Coordinate { value: read_offset(reader)?, relative: true };
+                }
+                _ if integer => *coordinate = Coordinate { value: reader.read_int()? as f64, relative: false },
+                _ => {
+                    let word_start = reader.cursor;
+                    let mut value = reader.read_double()?;
+                    // Whole x and z coordinates point at the centre of the block, as in vanilla.
+                    if i != 1 && !reader.input[word_start..reader.cursor].contains('.') {
+                        value += 0.5;
+                    }
+                    *coordinate = Coordinate { value, relative: false };
+                }
+            }
+        }
+        Ok(Coordinates::World(coordinates))
+    }
+
+    /// The position these coordinates point at when run by someone at `origin`.
+    pub fn resolve(&self, origin: &Position) -> (f64, f64, f64) {
+        match self {
+            Coordinates::World([x, y, z]) => {
+                let component = |coordinate: &Coordinate, base: f64| if coordinate.relative { base + coordinate.value } else { coordinate.value };
+                (component(x, origin.x), component(y, origin.y), component(z, origin.z))
+            }
+            Coordinates::Local([left, up, forwards]) => {
+                let (yaw, pitch) = ((origin.yaw as f64 + 90.0).to_radians(), (-origin.pitch as f64).to_radians());
+                let pitch_up = (-origin.pitch as f64 + 90.0).to_radians();
+                let forward_axis = (yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos());
+                let up_axis = (yaw.cos() * pitch_up.cos(), pitch_up.sin(), yaw.sin() * pitch_up.cos());
+                // Left is the negated cross product of forwards and up.
+                let left_axis = (
+                    -(forward_axis.1 * up_axis.2 - forward_axis.2 * up_axis.1),
+                    -(forward_axis.2 * up_axis.0 - forward_axis.0 * up_axis.2),
+                    -(forward_axis.0 * up_axis.1 - forward_axis.1 * up_axis.0),
+                );
+                (
+                    origin.x + forward_axis.0 * forwards + up_axis.0 * up + left_axis.0 * left,
+                    origin.y + forward_axis.1 * forwards + up_axis.1 * up + left_axis.1 * left,
+                    origin.z + forward_axis.2 * forwards + up_axis.2 * up + left_axis.2 * left,
+                )
+            }
+        }
+    }
+
+    /// The block these coordinates point at when run by someone at `origin`.
+    pub fn resolve_block(&self, origin: &Position) -> (i32, i32, i32) {
+        let (x, y, z) = self.resolve(origin);
+        (x.floor() as i32, y.floor() as i32, z.floor() as i32)
+    }
+}
+
+fn expect_space(reader: &mut StringReader, start: usize) -> Result<()> {
+    if reader.peek() == Some(' ') {
+        reader.skip();
+        Ok(())
+    } else {
+        reader.cursor = start;
+        Err(reader.error("Incomplete (expected 3 coordinates)"))
+    }
+}
+
+/// The number after `~` or `^`, which may be left out.
+fn read_offset(reader: &mut StringReader) -> Result<f64> {
+    match reader.peek() {
+        None | Some(' ') => Ok(0.0),
+        _ => reader.read_double(),
+    }
+}
+
+/// An integer range such as `1..5`, `..5`, `1..` or `3`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntRange {
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+}
+
+impl IntRange {
+    fn parse(reader: &mut StringReader) -> Result<Self> {
+        let start = reader.cursor;
+        let min = if reader.remaining().starts_with("..") { None } else { Some(reader.read_int()?) };
+        let max = if reader.remaining().starts_with("..") {
+            reader.cursor += 2;
+            match reader.peek() {
+                None | Some(' ') => None,
+                _ => Some(reader.read_int()?),
+            }
+        } else {
+            min
+        };
+        if min.is_none() && max.is_none() {
+            reader.cursor = start;
+            return Err(reader.error("Expected value or range of values"));
+        }
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                reader.cursor = start;
+                return Err(reader.error("Min cannot be bigger than max"));
+            }
+        }
+        Ok(Self { min, max })
+    }
+
+    pub fn contains(&self, value: i32) -> bool {
+        self.min.map(|min| value >= min).unwrap_or(true) && self.max.map(|max| value <= max).unwrap_or(true)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SelectorTarget {
+    /// A player given by name.
+    Name(String),
+    /// `@s`
+    Sender,
+    /// `@p`
+    Nearest,
+    /// `@a`
+    AllPlayers,
+    /// `@r`
+    Random,
+    /// `@e`, which only finds players as long as there are no other entities.
+    AllEntities,
+}
+
+/// An entity argument, either a player name or a selector such as `@a[gamemode=creative]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntitySelector {
+    pub target: SelectorTarget,
+    pub name: Option<String>,
+    pub gamemode: Option<GameMode>,
+    pub limit: Option<usize>,
+}
+
+/// A player an `EntitySelector` can pick, along with whatever the caller needs to act on them.
+pub struct SelectorCandidate<T> {
+    pub name: String,
+    pub gamemode: GameMode,
+    pub position: Position,
+    pub value: T,
+}
+
+impl EntitySelector {
+    fn parse(reader: &mut StringReader, single: bool, players_only: bool) -> Result<Self> {
+        let start = reader.cursor;
+        if reader.peek() != Some('@') {
+            let name = reader.read_word();
+            if name.is_empty() || name.len() > 16 {
+                reader.cursor = start;
+                return Err(reader.error("Invalid name or UUID"));
+            }
+            return Ok(Self { target: SelectorTarget::Name(name.to_string()), name: None, gamemode: None, limit: None });
+        }
+
+        reader.skip();
+        let target = match reader.peek() {
+            Some('s') => SelectorTarget::Sender,
+            Some('p') => SelectorTarget::Nearest,
+            Some('a') => SelectorTarget::AllPlayers,
+            Some('r') => SelectorTarget::Random,
+            Some('e') => SelectorTarget::AllEntities,
+            _ => return Err(reader.error("Unknown selector type")),
+        };
+        reader.skip();
+        let mut selector = Self { target, name: None, gamemode: None, limit: None };
+        if reader.peek() == Some('[') {
+            reader.skip();
+            selector.parse_options(reader)?;
+        }
+
+        if single && selector.allows_many() {
+            reader.cursor = start;
+            return Err(reader.error("Only one entity is allowed, but the provided selector allows more than one"));
+        }
+        if players_only && selector.target == SelectorTarget::AllEntities {
+            reader.cursor = start;
+            return Err(reader.error("Only players may be affected by this command, but the provided selector includes entities"));
+        }
+        Ok(selector)
+    }
+
+    fn parse_options(&mut self, reader: &mut StringReader) -> Result<()> {
+        loop {
+            while reader.peek() == Some(' ') {
+                reader.skip();
+            }
+            if reader.peek() == Some(']') {
+                reader.skip();
+                return Ok(());
+            }
+            let option_start = reader.cursor;
+            let remaining = reader.remaining();
+            let key = &remaining[..remaining.find(|c: char| c == '=' || c == ']' || c == ' ').unwrap_or(remaining.len())];
+            reader.cursor += key.len();
+            if reader.peek() != Some('=') {
+                return Err(reader.error(&format!("Expected value for option '{}'", key)));
+            }
+            reader.skip();
+            let value_start = reader.cursor;
+            let remaining = reader.remaining();
+            let value = &remaining[..remaining.find(|c: char| c == ',' || c == ']').unwrap_or(remaining.len())];
+            reader.cursor += value.len();
+            let value = value.trim();
+            match key {
+                "name" => self.name = Some(value.to_string()),
+                "gamemode" => {
+                    let gamemode = crate::server::player::GAMEMODES.iter().find(|(name, _)| *name == value);
+                    match gamemode {
+                        Some((_, gamemode)) => self.gamemode = Some(gamemode.clone()),
+                        None => {
+                            reader.cursor = value_start;
+                            return Err(reader.error(&format!("Invalid or unknown game mode '{}'", value)));
+                        }
+                    }
+                }
+                "limit" => match value.parse::<usize>() {
+                    Ok(limit) if limit > 0 => self.limit = Some(limit),
+                    _ => {
+                        reader.cursor = value_start;
+                        return Err(reader.error("Limit must be at least 1"));
+                    }
+                },
+                _ => {
+                    reader.cursor = option_start;
+                    return Err(reader.error(&format!("Unknown option '{}'", key)));
+                }
+            }
+            match reader.peek() {
+                Some(',') => reader.skip(),
+                Some(']') => {}
+                _ => return Err(reader.error("Expected end of options")),
+            }
+        }
+    }
+
+    fn allows_many(&self) -> bool {
+        match self.target {
+            SelectorTarget::AllPlayers | SelectorTarget::AllEntities => self.limit.map(|limit| limit > 1).unwrap_or(true),
+            _ => false,
+        }
+    }
+
+    /// Picks the matching candidates. `sender` is the index of the candidate running the command,
+    /// if it is a player.
+    pub fn select<T>(&self, mut candidates: Vec<SelectorCandidate<T>>, sender: Option<usize>, origin: &Position) -> Vec<T> {
+        let mut selected: Vec<SelectorCandidate<T>> = match &self.target {
+            SelectorTarget::Name(name) => candidates.into_iter().filter(|candidate| &candidate.name == name).collect(),
+            SelectorTarget::Sender => match sender {
+                Some(index) if index < candidates.len() => vec![candidates.swap_remove(index)],
+                _ => vec![],
+            },
+            _ => candidates.into_iter().filter(|candidate| candidate.position.world == origin.world).collect(),
+        };
+        selected.retain(|candidate| {
+            self.name.as_ref().map(|name| &candidate.name == name).unwrap_or(true)
+                && self.gamemode.as_ref().map(|gamemode| &candidate.gamemode == gamemode).unwrap_or(true)
+        });
+
+        let distance = |position: &Position| {
+            let (dx, dy, dz) = (position.x - origin.x, position.y - origin.y, position.z - origin.z);
+            dx * dx + dy * dy + dz * dz
+        };
+        let limit = match self.target {
+            SelectorTarget::Nearest => {
+                selected.sort_by(|a, b| distance(&a.position).partial_cmp(&distance(&b.position)).unwrap_or(std::cmp::Ordering::Equal));
+                self.limit.unwrap_or(1)
+            }
+            SelectorTarget::Random => {
+                rand::thread_rng().shuffle(&mut selected);
+                self.limit.unwrap_or(1)
+            }
+            _ => self.limit.unwrap_or(usize::MAX),
+        };
+        selected.into_iter().take(limit).map(|candidate| candidate.value).collect()
+    }
+}
+
+/// The parsers an argument node can use, each matching one of the client's parsers so it can
+/// highlight and check the input itself.
+#[derive(Clone, Debug)]
+pub enum ArgumentType {
+    Bool,
+    Integer { min: Option<i32>, max: Option<i32> },
+    Double { min: Option<f64>, max: Option<f64> },
+    /// A single unquoted word.
+    Word,
+    /// A word or a quoted string.
+    String,
+    /// Everything up to the end of the input.
+    GreedyString,
+    Entity { single: bool, players_only: bool },
+    /// Players by name or selector, including players that are offline.
+    GameProfile,
+    /// An item id such as `minecraft:stone`.
+    ItemStack,
+    /// A duration in ticks, optionally given in days (`d`) or seconds (`s`).
+    Time,
+    BlockPos,
+    Vec3,
+    IntRange,
+}
+
+/// A parsed argument value.
+#[derive(Clone, Debug)]
+pub enum Argument {
+    Bool(bool),
+    Integer(i32),
+    Double(f64),
+    String(String),
+    Entity(EntitySelector),
+    Item(&'static Item),
+    BlockPos(Coordinates),
+    Vec3(Coordinates),
+    IntRange(IntRange),
+}
+
+impl ArgumentType {
+    pub fn integer(min: i32, max: i32) -> Self {
+        ArgumentType::Integer { min: Some(min), max: Some(max) }
+    }
+
+    pub fn players() -> Self {
+        ArgumentType::Entity { single: false, players_only: true }
+    }
+
+    pub fn player() -> Self {
+        ArgumentType::Entity { single: true, players_only: true }
+    }
+
+    fn parse(&self, reader: &mut StringReader) -> Result<Argument> {
+        let start = reader.cursor;
+        Ok(match self {
+            ArgumentType::Bool => match reader.read_word() {
+                "true" => Argument::Bool(true),
+                "false" => Argument::Bool(false),
+                word => {
+                    reader.cursor = start;
+                    return Err(reader.error(&format!("Invalid boolean, expected 'true' or 'false' but found '{}'", word)));
+                }
+            },
+            ArgumentType::Integer { min, max } => {
+                let value = reader.read_int()?;
+                check_bounds(reader, start, value, *min, *max, "Integer")?;
+                Argument::Integer(value)
+            }
+            ArgumentType::Double { min, max } => {
+                let value = reader.read_double()?;
+                check_bounds(reader, start, value, *min, *max, "Double")?;
+                Argument::Double(value)
+            }
+            ArgumentType::Word => Argument::String(reader.read_word().to_string()),
+            ArgumentType::String => Argument::String(reader.read_string()?),
+            ArgumentType::GreedyString => {
+                let rest = reader.remaining().to_string();
+                reader.cursor = reader.input.len();
+                Argument::String(rest)
+            }
+            ArgumentType::Entity { single, players_only } => Argument::Entity(EntitySelector::parse(reader, *single, *players_only)?),
+            ArgumentType::GameProfile => Argument::Entity(EntitySelector::parse(reader, false, true)?),
+            ArgumentType::ItemStack => {
+                let name = reader.read_word();
+                if name.contains('{') {
+                    reader.cursor = start;
+                    return Err(reader.error("Item NBT is not supported"));
+                }
+                match item::get_item_by_name(name) {
+                    Some(item) => Argument::Item(item),
+                    None => {
+                        reader.cursor = start;
+                        return Err(reader.error(&format!("Unknown item '{}'", name)));
+                    }
+                }
+            }
+            ArgumentType::Time => {
+                let value = reader.read_double()?;
+                let scale = match reader.peek() {
+                    None | Some(' ') => 1.0,
+                    Some('t') => 1.0,
+                    Some('s') => 20.0,
+                    Some('d') => 24000.0,
+                    Some(_) => return Err(reader.error("Invalid unit")),
+                };
+                if reader.peek() != Some(' ') {
+                    reader.skip();
+                }
+                let ticks = (value * scale).round();
+                if ticks < 0.0 {
+                    reader.cursor = start;
+                    return Err(reader.error("Tick count must be non-negative"));
+                }
+                Argument::Integer(ticks as i32)
+            }
+            ArgumentType::BlockPos => Argument::BlockPos(Coordinates::parse(reader, true)?),
+            ArgumentType::Vec3 => Argument::Vec3(Coordinates::parse(reader, false)?),
+            ArgumentType::IntRange => Argument::IntRange(IntRange::parse(reader)?),
+        })
+    }
+
+    fn parser(&self) -> CommandParserSpec {
+        match self {
+            ArgumentType::Bool => CommandParserSpec::Bool,
+            ArgumentType::Integer { min, max } => CommandParserSpec::Integer(IntegerParserProps { min: *min, max: *max }),
+            ArgumentType::Double { min, max } => CommandParserSpec::Double(DoubleParserProps { min: *min, max: *max }),
+            ArgumentType::Word => CommandParserSpec::StringParser(StringParserMode::SingleWord),
+            ArgumentType::String => CommandParserSpec::StringParser(StringParserMode::QuotablePharse),
+            ArgumentType::GreedyString => CommandParserSpec::StringParser(StringParserMode::GreedyPhrase),
+            ArgumentType::Entity { single, players_only } => {
+                let mut flags = EntityParserFlags::default();
+                flags.set_single_target(*single);
+                flags.set_players_only(*players_only);
+                CommandParserSpec::Entity(flags)
+            }
+            ArgumentType::GameProfile => CommandParserSpec::GameProfile,
+            ArgumentType::ItemStack => CommandParserSpec::ItemStack,
+            ArgumentType::Time => CommandParserSpec::Time,
+            ArgumentType::BlockPos => CommandParserSpec::BlockPosition,
+            ArgumentType::Vec3 => CommandParserSpec::Vec3,
+            ArgumentType::IntRange => CommandParserSpec::IntRange,
+        }
+    }
+}
+
+fn check_bounds<T: PartialOrd + std::fmt::Display>(reader: &mut StringReader, start: usize, value: T, min: Option<T>, max: Option<T>, kind: &str) -> Result<()> {
+    let error = match (min, max) {
+        (Some(min), _) if value < min => format!("{} must not be less than {}, found {}", kind, min, value),
+        (_, Some(max)) if value > max => format!("{} must not be more than {}, found {}", kind, max, value),
+        _ => return Ok(()),
+    };
+    reader.cursor = start;
+    Err(reader.error(&error))
+}
+
+/// Where the server finds suggestions for an argument when the client asks for them.
+#[derive(Clone, Debug)]
+pub enum Suggestions {
+    /// Names of online players.
+    Players,
+    Values(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+enum NodeKind {
+    Root,
+    Literal(String),
+    Argument {
+        name: String,
+        kind: ArgumentType,
+        suggestions: Option<Suggestions>,
+    },
+}
+
+struct CommandNode<A> {
+    kind: NodeKind,
+    children: Vec<usize>,
+    /// What to run when the input ends at this node.
+    action: Option<A>,
+    /// Permission needed to use this node and everything below it.
+    permission: Option<String>,
+}
+
+/// Builds a command tree to register with a `CommandDispatcher`.
+pub struct CommandBuilder<A> {
+    kind: NodeKind,
+    action: Option<A>,
+    permission: Option<String>,
+    children: Vec<CommandBuilder<A>>,
+}
+
+pub fn literal<A>(name: &str) -> CommandBuilder<A> {
+    CommandBuilder { kind: NodeKind::Literal(name.to_string()), action: None, permission: None, children: vec![] }
+}
+
+pub fn argument<A>(name: &str, kind: ArgumentType) -> CommandBuilder<A> {
+    CommandBuilder { kind: NodeKind::Argument { name: name.to_string(), kind, suggestions: None }, action: None, permission: None, children: vec![] }
+}
+
+impl<A> CommandBuilder<A> {
+    pub fn then(mut self, child: CommandBuilder<A>) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Makes the command runnable when the input ends here.
+    pub fn executes(mut self, action: A) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Hides this node and everything below it from sources without the permission.
+    pub fn requires(mut self, permission: &str) -> Self {
+        self.permission = Some(permission.to_string());
+        self
+    }
+
+    /// Has the client ask the server for suggestions for this argument.
+    pub fn suggests(mut self, source: Suggestions) -> Self {
+        if let NodeKind::Argument { suggestions, .. } = &mut self.kind {
+            *suggestions = Some(source);
+        }
+        self
+    }
+}
+
+/// A command that parsed successfully, ready to be run.
+pub struct CommandContext<A> {
+    pub action: A,
+    pub input: String,
+    arguments: HashMap<String, Argument>,
+}
+
+impl<A> CommandContext<A> {
+    pub fn get(&self, name: &str) -> Option<&Argument> {
+        self.arguments.get(name)
+    }
+
+    pub fn get_int(&self, name: &str) -> Result<i32> {
+        match self.get(name) {
+            Some(Argument::Integer(value)) => Ok(*value),
+            _ => Err(anyhow!("Missing integer argument {}", name)),
+        }
+    }
+
+    pub fn get_double(&self, name: &str) -> Result<f64> {
+        match self.get(name) {
+            Some(Argument::Double(value)) => Ok(*value),
+            _ => Err(anyhow!("Missing double argument {}", name)),
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Result<bool> {
+        match self.get(name) {
+            Some(Argument::Bool(value)) => Ok(*value),
+            _ => Err(anyhow!("Missing boolean argument {}", name)),
+        }
+    }
+
+    pub fn get_string(&self, name: &str) -> Result<&str> {
+        match self.get(name) {
+            Some(Argument::String(value)) => Ok(value),
+            _ => Err(anyhow!("Missing string argument {}", name)),
+        }
+    }
+
+    pub fn get_entity(&self, name: &str) -> Result<&EntitySelector> {
+        match self.get(name) {
+            Some(Argument::Entity(value)) => Ok(value),
+            _ => Err(anyhow!("Missing entity argument {}", name)),
+        }
+    }
+
+    pub fn get_item(&self, name: &str) -> Result<&'static Item> {
+        match self.get(name) {
+            Some(Argument::Item(value)) => Ok(value),
+            _ => Err(anyhow!("Missing item argument {}", name)),
+        }
+    }
+
+    pub fn get_position(&self, name: &str) -> Result<&Coordinates> {
+        match self.get(name) {
+            Some(Argument::BlockPos(value)) | Some(Argument::Vec3(value)) => Ok(value),
+            _ => Err(anyhow!("Missing position argument {}", name)),
+        }
+    }
+
+    pub fn get_int_range(&self, name: &str) -> Result<IntRange> {
+        match self.get(name) {
+            Some(Argument::IntRange(value)) => Ok(*value),
+            _ => Err(anyhow!("Missing range argument {}", name)),
+        }
+    }
+}
+
+/// A tree of commands modelled on Brigadier, which parses input into a `CommandContext` and
+/// describes itself to clients with Declare Commands. `A` identifies what a command does.
+pub struct CommandDispatcher<A> {
+    /// The root is always the first node.
+    nodes: Vec<CommandNode<A>>,
+}
+
+impl<A: Clone> CommandDispatcher<A> {
+    pub fn new() -> Self {
+        Self { nodes: vec![CommandNode { kind: NodeKind::Root, children: vec![], action: None, permission: None }] }
+    }
+
+    /// Adds a command, merging it with any already registered under the same literals.
+    pub fn register(&mut self, command: CommandBuilder<A>) {
+        self.add_child(0, command);
+    }
+
+    fn add_child(&mut self, parent: usize, builder: CommandBuilder<A>) {
+        let existing = self.nodes[parent].children.iter().copied().find(|&child| match (&self.nodes[child].kind, &builder.kind) {
+            (NodeKind::Literal(a), NodeKind::Literal(b)) => a == b,
+            (NodeKind::Argument { name: a, .. }, NodeKind::Argument { name: b, .. }) => a == b,
+            _ => false,
+        });
+        let index = match existing {
+            Some(index) => {
+                if builder.action.is_some() {
+                    self.nodes[index].action = builder.action;
+                }
+                if builder.permission.is_some() {
+                    self.nodes[index].permission = builder.permission;
+                }
+                index
+            }
+            None => {
+                self.nodes.push(CommandNode { kind: builder.kind, children: vec![], action: builder.action, permission: builder.permission });
+                let index = self.nodes.len() - 1;
+                // Literals are tried before arguments, so `/gamemode survival` is never read as a name.
+                let children = &self.nodes[parent].children;
+                let position = match self.nodes[index].kind {
+                    NodeKind::Literal(_) => children.iter().position(|&child| !matches!(self.nodes[child].kind, NodeKind::Literal(_))).unwrap_or(children.len()),
+                    _ => children.len(),
+                };
+                self.nodes[parent].children.insert(position, index);
+                index
+            }
+        };
+        for child in builder.children {
+            self.add_child(index, child);
+        }
+    }
+
+    fn can_use(&self, node: usize, allowed: &dyn Fn(&str) -> bool) -> bool {
+        self.nodes[node].permission.as_ref().map(|permission| allowed(permission)).unwrap_or(true)
+    }
+
+    /// Parses a command, without the leading `/`. Nodes whose permission `allowed` rejects are
+    /// treated as if they did not exist.
+    pub fn parse(&self, input: &str, allowed: &dyn Fn(&str) -> bool) -> Result<CommandContext<A>> {
+        let reader = StringReader::new(input);
+        let (action, arguments) = self.parse_children(0, &reader, HashMap::new(), allowed).map_err(|(_, error)| error)?;
+        Ok(CommandContext { action, input: input.to_string(), arguments })
+    }
+
+    /// Tries each child of a node in turn, returning the first complete parse or the error that
+    /// got furthest into the input.
+    fn parse_children(&self, node: usize, reader: &StringReader, arguments: HashMap<String, Argument>, allowed: &dyn Fn(&str) -> bool) -> std::result::Result<(A, HashMap<String, Argument>), (usize, Error)> {
+        let mut best: Option<(usize, Error)> = None;
+        let mut fail = |cursor: usize, error: Error| {
+            if best.as_ref().map(|(best_cursor, _)| cursor > *best_cursor).unwrap_or(true) {
+                best = Some((cursor, error));
+            }
+        };
+
+        for &child in &self.nodes[node].children {
+            if !self.can_use(child, allowed) {
+                continue;
+            }
+            let mut reader = reader.clone();
+            let mut arguments = arguments.clone();
+            match &self.nodes[child].kind {
+                NodeKind::Root => continue,
+                NodeKind::Literal(name) => {
+                    if reader.read_word() != name {
+                        continue;
+                    }
+                }
+                NodeKind::Argument { name, kind, .. } => match kind.parse(&mut reader) {
+                    Ok(value) => {
+                        arguments.insert(name.clone(), value);
+                    }
+                    Err(error) => {
+                        fail(reader.cursor, error);
+                        continue;
+                    }
+                },
+            }
+
+            match reader.peek() {
+                None => match &self.nodes[child].action {
+                    Some(action) => return Ok((action.clone(), arguments)),
+                    None => fail(reader.cursor, reader.error("Unknown or incomplete command")),
+                },
+                Some(' ') => {
+                    reader.skip();
+                    match self.parse_children(child, &reader, arguments, allowed) {
+                        Ok(result) => return Ok(result),
+                        Err((cursor, error)) => fail(cursor, error),
+                    }
+                }
+                Some(_) => fail(reader.cursor, reader.error("Expected whitespace to end one argument, but found trailing data")),
+            }
+        }
+
+        match best {
+            Some(best) => Err(best),
+            None if node == 0 => Err((reader.cursor, reader.error("Unknown or incomplete command"))),
+            None => Err((reader.cursor, reader.error("Incorrect argument for command"))),
+        }
+    }
+
+    /// Suggestions for the word being typed at the end of `input`, as the byte offset it starts at
+    /// and the candidates to replace it with. `players` fills arguments suggesting player names.
+    pub fn suggest(&self, input: &str, players: &[String], allowed: &dyn Fn(&str) -> bool) -> (usize, Vec<String>) {
+        let reader = StringReader::new(input);
+        self.suggest_children(0, &reader, players, allowed).unwrap_or((input.len(), vec![]))
+    }
+
+    fn suggest_children(&self, node: usize, reader: &StringReader, players: &[String], allowed: &dyn Fn(&str) -> bool) -> Option<(usize, Vec<String>)> {
+        let typed = reader.remaining();
+        let children = self.nodes[node].children.iter().copied().filter(|&child| self.can_use(child, allowed)).collect::<Vec<usize>>();
+        if !typed.contains(' ') {
+            let mut suggestions: Vec<String> = vec![];
+            for &child in &children {
+                match &self.nodes[child].kind {
+                    NodeKind::Literal(name) => suggestions.push(name.clone()),
+                    NodeKind::Argument { suggestions: Some(Suggestions::Players), .. } => suggestions.extend(players.iter().cloned()),
+                    NodeKind::Argument { suggestions: Some(Suggestions::Values(values)), .. } => suggestions.extend(values.iter().cloned()),
+                    _ => {}
+                }
+            }
+            suggestions.retain(|suggestion| suggestion.to_lowercase().starts_with(&typed.to_lowercase()));
+            suggestions.sort();
+            suggestions.dedup();
+            return Some((reader.cursor, suggestions));
+        }
+
+        for child in children {
+            let mut reader = reader.clone();
+            let parsed = match &self.nodes[child].kind {
+                NodeKind::Root => false,
+                NodeKind::Literal(name) => reader.read_word() == name,
+                NodeKind::Argument { kind, .. } => kind.parse(&mut reader).is_ok(),
+            };
+            if parsed && reader.peek() == Some(' ') {
+                reader.skip();
+                if let Some(result) = self.suggest_children(child, &reader, players, allowed) {
+                    if !result.1.is_empty() {
+                        return Some(result);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// The part of the tree a source may use, for clients to check and highlight commands as they
+    /// are typed.
+    pub fn declare_commands(&self, allowed: &dyn Fn(&str) -> bool) -> Packet {
+        // Usable nodes in tree order, so the root stays first, and where each ends up.
+        let mut visible = vec![0];
+        let mut index = 0;
+        while index < visible.len() {
+            for &child in &self.nodes[visible[index]].children {
+                if self.can_use(child, allowed) && !visible.contains(&child) {
+                    visible.push(child);
+                }
+            }
+            index += 1;
+        }
+        let remapped = visible.iter().enumerate().map(|(new, &old)| (old, new)).collect::<HashMap<usize, usize>>();
+
+        let nodes = visible.iter().map(|&node| &self.nodes[node]).map(|node| CommandNodeSpec {
+            children_indices: CountedArray::from(node.children.iter()
+                .filter_map(|child| remapped.get(child))
+                .map(|&child| VarInt::from(child as i32))
+                .collect::<Vec<VarInt>>()),
+            redirect_node: None,
+            is_executable: node.action.is_some(),
+            node: match &node.kind {
+                NodeKind::Root => NodeSpec::Root,
+                NodeKind::Literal(name) => NodeSpec::Literal(CommandLiteralNodeSpec { name: name.clone() }),
+                NodeKind::Argument { name, kind, suggestions } => NodeSpec::Argument(CommandArgumentNodeSpec {
+                    name: name.clone(),
+                    parser: kind.parser(),
+                    suggestions_types: suggestions.as_ref().map(|_| SuggestionsTypeSpec::AskServer),
+                }),
+            },
+        }).collect::<Vec<CommandNodeSpec>>();
+        Packet::PlayDeclareCommands(PlayDeclareCommandsSpec {
+            nodes: CountedArray::from(nodes),
+            root_index: VarInt::from(0),
+        })
+    }
+}
+
+impl<A: Clone> Default for CommandDispatcher<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,435 @@
+use std::ops::Range;
+use mcproto_rs::nbt::{NamedTag, Tag};
+use mcproto_rs::types::{Chat, ItemStack, Slot};
+use mcproto_rs::v1_16_3::WindowType;
+use crate::server::block::{self, Block};
+use crate::server::inventory::{self, WindowLayout};
+use crate::server::item;
+use crate::server::placement::Facing;
+use crate::server::recipe::{CookingKind, RecipeKind, RecipeManager};
+use crate::server::world::{BlockChange, World};
+
+/// Slots of a single chest or barrel.
+pub const CONTAINER_SIZE: usize = 27;
+/// Input, fuel and output slots of a furnace.
+pub const FURNACE_SIZE: usize = 3;
+pub const FURNACE_INPUT_SLOT: usize = 0;
+pub const FURNACE_FUEL_SLOT: usize = 1;
+pub const FURNACE_OUTPUT_SLOT: usize = 2;
+/// Output slot followed by the 3x3 crafting grid.
+pub const CRAFTING_TABLE_SIZE: usize = 10;
+pub const CRAFTING_TABLE_OUTPUT_SLOT: usize = 0;
+/// Main inventory and hotbar slots shown below every container.
+const PLAYER_SLOTS: usize = 36;
+/// Window ids cycle through 1 to this, 0 being the player's own inventory.
+const MAX_WINDOW_ID: u8 = 100;
+
+const CHEST_SINGLE: u32 = 0;
+const CHEST_LEFT: u32 = 1;
+const CHEST_RIGHT: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContainerKind {
+    Chest,
+    DoubleChest,
+    Barrel,
+    Furnace,
+    CraftingTable,
+}
+
+impl ContainerKind {
+    /// Number of container slots, not counting the player inventory shown below them.
+    pub fn size(&self) -> usize {
+        match self {
+            ContainerKind::Chest | ContainerKind::Barrel => CONTAINER_SIZE,
+            ContainerKind::DoubleChest => CONTAINER_SIZE * 2,
+            ContainerKind::Furnace => FURNACE_SIZE,
+            ContainerKind::CraftingTable => CRAFTING_TABLE_SIZE,
+        }
+    }
+
+    pub fn window_type(&self) -> WindowType {
+        match self {
+            ContainerKind::Chest | ContainerKind::Barrel => WindowType::GenericThreeRow,
+            ContainerKind::DoubleChest => WindowType::GenericSixRow,
+            ContainerKind::Furnace => WindowType::Furnace,
+            ContainerKind::CraftingTable => WindowType::CraftingTable,
+        }
+    }
+
+    pub fn title(&self) -> Chat {
+        Chat::from_text(match self {
+            ContainerKind::Chest => "Chest",
+            ContainerKind::DoubleChest => "Large Chest",
+            ContainerKind::Barrel => "Barrel",
+            ContainerKind::Furnace => "Furnace",
+            ContainerKind::CraftingTable => "Crafting",
+        })
+    }
+}
+
+/// Fuel and smelting progress of a furnace, in ticks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FurnaceState {
+    pub burn_time: i16,
+    pub burn_duration: i16,
+    pub cook_time: i16,
+    pub cook_duration: i16,
+}
+
+impl FurnaceState {
+    /// Window properties 0 to 3 as sent in `WindowProperty`.
+    pub fn properties(&self) -> [(i16, i16); 4] {
+        [
+            (0, self.burn_time),
+            (1, self.burn_duration),
+            (2, self.cook_time),
+            (3, self.cook_duration),
+        ]
+    }
+}
+
+/// Ticks an item keeps a furnace burning for, if it is fuel.
+pub fn fuel_ticks(item_id: i32) -> Option<i16> {
+    let name = item::get_item(item_id)?.name.trim_start_matches("minecraft:");
+    match name {
+        "coal" | "charcoal" => Some(1600),
+        "stick" => Some(100),
+        "crafting_table" | "chest" | "barrel" | "oak_stairs" | "bow" => Some(300),
+        _ if name.ends_with("_planks") || name.ends_with("_log") => Some(300),
+        _ if name.starts_with("wooden_") => Some(200),
+        _ => None,
+    }
+}
+
+/// Data kept alongside a block that its state cannot hold.
+#[derive(Clone, Debug)]
+pub struct BlockEntity {
+    pub id: &'static str,
+    pub items: Vec<Slot>,
+    pub furnace: Option<FurnaceState>,
+}
+
+impl BlockEntity {
+    /// The block entity a freshly placed block starts with, if it has one.
+    pub fn for_block(block: &Block) -> Option<Self> {
+        let (id, size, furnace) = match block.name {
+            "minecraft:chest" => ("minecraft:chest", CONTAINER_SIZE, None),
+            "minecraft:barrel" => ("minecraft:barrel", CONTAINER_SIZE, None),
+            "minecraft:furnace" => ("minecraft:furnace", FURNACE_SIZE, Some(FurnaceState::default())),
+            _ => return None,
+        };
+        Some(Self {
+            id,
+            items: vec![None; size],
+            furnace,
+        })
+    }
+
+    /// Advances a furnace by one tick, burning fuel and smelting its input. Returns whether its
+    /// slots changed.
+    pub fn tick_furnace(&mut self, recipes: &RecipeManager) -> bool {
+        let furnace = match &mut self.furnace {
+            Some(furnace) => furnace,
+            None => return false,
+        };
+        let items = &mut self.items;
+        let recipe = items[FURNACE_INPUT_SLOT].as_ref()
+            .and_then(|input| recipes.find_cooking(CookingKind::Smelting, input.item_id.0));
+        let (result, cooking_time) = match recipe {
+            Some(recipe) => match &recipe.kind {
+                RecipeKind::Cooking { cooking_time, .. } => (Some(recipe.result.clone()), *cooking_time as i16),
+                _ => (None, 0),
+            },
+            None => (None, 0),
+        };
+        let can_smelt = match (&result, &items[FURNACE_OUTPUT_SLOT]) {
+            (Some(_), None) => true,
+            (Some(result), Some(output)) => inventory::same_item(result, output)
+                && output.item_count as i32 + result.item_count as i32 <= inventory::max_stack_size(output) as i32,
+            (None, _) => false,
+        };
+
+        let mut changed = false;
+        if furnace.burn_time > 0 {
+            furnace.burn_time -= 1;
+        }
+        if furnace.burn_time == 0 && can_smelt {
+            let fuel = items[FURNACE_FUEL_SLOT].as_ref().and_then(|fuel| fuel_ticks(fuel.item_id.0));
+            if let (Some(ticks), Some(item)) = (fuel, &mut items[FURNACE_FUEL_SLOT]) {
+                furnace.burn_time = ticks;
+                furnace.burn_duration = ticks;
+                item.item_count -= 1;
+                if item.item_count <= 0 {
+                    items[FURNACE_FUEL_SLOT] = None;
+                }
+                changed = true;
+            }
+        }
+
+        if furnace.burn_time > 0 && can_smelt {
+            furnace.cook_duration = cooking_time;
+            furnace.cook_time += 1;
+            if furnace.cook_time >= cooking_time {
+                furnace.cook_time = 0;
+                let result = result.unwrap();
+                match &mut items[FURNACE_OUTPUT_SLOT] {
+                    Some(output) => output.item_count += result.item_count,
+                    output => *output = Some(result),
+                }
+                if let Some(input) = &mut items[FURNACE_INPUT_SLOT] {
+                    input.item_count -= 1;
+                    if input.item_count <= 0 {
+                        items[FURNACE_INPUT_SLOT] = None;
+                    }
+                }
+                changed = true;
+            }
+        } else if furnace.cook_time > 0 {
+            // Progress winds back down once the furnace goes out or the input is taken.
+            furnace.cook_time = (furnace.cook_time - 2).max(0);
+        }
+        changed
+    }
+
+    /// The tag sent to clients with chunk data. Contents are never sent this way, only through
+    /// open windows.
+    pub fn to_nbt(&self, x: i32, y: i32, z: i32) -> NamedTag {
+        Tag::Compound(vec![
+            Tag::String(self.id.to_string()).with_name("id"),
+            Tag::Int(x).with_name("x"),
+            Tag::Int(y).with_name("y"),
+            Tag::Int(z).with_name("z"),
+        ]).with_name("")
+    }
+}
+
+/// Whether the block at a position has a block entity still holding items.
+pub fn holds_items(world: &mut World, x: i32, y: i32, z: i32) -> bool {
+    world.get_block_entity(x, y, z)
+        .map(|entity| entity.items.iter().any(Option::is_some))
+        .unwrap_or(false)
+}
+
+/// The furnace state with the lit property set, keeping its facing.
+pub fn furnace_lit_state(state: u32, lit: bool) -> u32 {
+    let furnace = match block::get_block(state) {
+        Some(furnace) if furnace.name == "minecraft:furnace" => furnace,
+        _ => return state,
+    };
+    let facing = (state - furnace.min_state) / 2;
+    furnace.min_state + facing * 2 + if lit { 0 } else { 1 }
+}
+
+fn is_chest(state: u32) -> bool {
+    block::get_block(state).map(|block| block.name == "minecraft:chest").unwrap_or(false)
+}
+
+fn chest_facing(state: u32) -> Facing {
+    let chest = block::get_block(state).unwrap();
+    Facing::from_horizontal_index((state - chest.min_state) / 6)
+}
+
+fn chest_type(state: u32) -> u32 {
+    let chest = block::get_block(state).unwrap();
+    (state - chest.min_state) % 6 / 2
+}
+
+fn with_chest_type(state: u32, kind: u32) -> u32 {
+    state - chest_type(state) * 2 + kind * 2
+}
+
+fn offset(position: (i32, i32, i32), facing: Facing) -> (i32, i32, i32) {
+    let (dx, dy, dz) = facing.offset();
+    (position.0 + dx, position.1 + dy, position.2 + dz)
+}
+
+/// The other half of a double chest, if the chest at the position is one.
+fn chest_partner(world: &mut World, position: (i32, i32, i32)) -> Option<(i32, i32, i32)> {
+    let (x, y, z) = position;
+    let state = world.get_block(x, y, z);
+    let facing = chest_facing(state);
+    let direction = match chest_type(state) {
+        CHEST_LEFT => facing.clockwise(),
+        CHEST_RIGHT => facing.clockwise().opposite(),
+        _ => return None,
+    };
+    let partner = offset(position, direction);
+    let partner_state = world.get_block(partner.0, partner.1, partner.2);
+    if is_chest(partner_state) && chest_facing(partner_state) == facing && chest_type(partner_state) != CHEST_SINGLE {
+        Some(partner)
+    } else {
+        None
+    }
+}
+
+/// Joins a newly placed chest with a single chest facing the same way beside it, returning the
+/// resulting state changes.
+pub fn connect_chest(world: &mut World, x: i32, y: i32, z: i32) -> Vec<BlockChange> {
+    let state = world.get_block(x, y, z);
+    let facing = chest_facing(state);
+    for (direction, kind, partner_kind) in [
+        (facing.clockwise(), CHEST_LEFT, CHEST_RIGHT),
+        (facing.clockwise().opposite(), CHEST_RIGHT, CHEST_LEFT),
+    ] {
+        let (px, py, pz) = offset((x, y, z), direction);
+        let partner_state = world.get_block(px, py, pz);
+        if !is_chest(partner_state) || chest_facing(partner_state) != facing || chest_type(partner_state) != CHEST_SINGLE {
+            continue;
+        }
+        let changes = vec![
+            BlockChange { x, y, z, state: with_chest_type(state, kind) },
+            BlockChange { x: px, y: py, z: pz, state: with_chest_type(partner_state, partner_kind) },
+        ];
+        for change in &changes {
+            world.set_block(change.x, change.y, change.z, change.state);
+        }
+        return changes;
+    }
+    vec![]
+}
+
+/// Turns the other half of a broken double chest back into a single chest.
+pub fn disconnect_chest(world: &mut World, x: i32, y: i32, z: i32) -> Option<BlockChange> {
+    let partner = chest_partner(world, (x, y, z))?;
+    let state = world.get_block(partner.0, partner.1, partner.2);
+    let change = BlockChange { x: partner.0, y: partner.1, z: partner.2, state: with_chest_type(state, CHEST_SINGLE) };
+    world.set_block(change.x, change.y, change.z, change.state);
+    Some(change)
+}
+
+/// The container right-clicking a block opens, along with the block entities holding its
+/// contents in slot order.
+pub fn container_at(world: &mut World, x: i32, y: i32, z: i32) -> Option<(ContainerKind, Vec<(i32, i32, i32)>)> {
+    let state = world.get_block(x, y, z);
+    let block = block::get_block(state)?;
+    let (kind, positions) = match block.name {
+        "minecraft:chest" => match chest_partner(world, (x, y, z)) {
+            // The right half holds the first rows.
+            Some(partner) if chest_type(state) == CHEST_RIGHT => (ContainerKind::DoubleChest, vec![(x, y, z), partner]),
+            Some(partner) => (ContainerKind::DoubleChest, vec![partner, (x, y, z)]),
+            None => (ContainerKind::Chest, vec![(x, y, z)]),
+        },
+        "minecraft:barrel" => (ContainerKind::Barrel, vec![(x, y, z)]),
+        "minecraft:furnace" => (ContainerKind::Furnace, vec![(x, y, z)]),
+        "minecraft:crafting_table" => return Some((ContainerKind::CraftingTable, vec![])),
+        _ => return None,
+    };
+
+    for &(x, y, z) in &positions {
+        if world.get_block_entity(x, y, z).is_none() {
+            let block = block::get_block(world.get_block(x, y, z))?;
+            world.set_block_entity(x, y, z, BlockEntity::for_block(block)?);
+        }
+    }
+    Some((kind, positions))
+}
+
+/// Layout of a container window: the container's slots followed by the player's main inventory
+/// and hotbar.
+pub struct ContainerWindow {
+    pub kind: ContainerKind,
+}
+
+impl WindowLayout for ContainerWindow {
+    fn size(&self) -> usize {
+        self.kind.size() + PLAYER_SLOTS
+    }
+
+    fn can_place(&self, slot: usize, _item: &ItemStack) -> bool {
+        match self.kind {
+            ContainerKind::Furnace => slot != FURNACE_OUTPUT_SLOT,
+            ContainerKind::CraftingTable => slot != CRAFTING_TABLE_OUTPUT_SLOT,
+            _ => true,
+        }
+    }
+
+    fn shift_targets(&self, slot: usize) -> Vec<Range<usize>> {
+        let size = self.kind.size();
+        let hotbar = size + PLAYER_SLOTS - 9;
+        if slot < size {
+            return vec![size..size + PLAYER_SLOTS];
+        }
+        match self.kind {
+            ContainerKind::Furnace => vec![0..1],
+            ContainerKind::CraftingTable if slot < hotbar => vec![hotbar..size + PLAYER_SLOTS],
+            ContainerKind::CraftingTable => vec![size..hotbar],
+            _ => vec![0..size],
+        }
+    }
+
+    fn hotbar_slot(&self, index: usize) -> usize {
+        self.kind.size() + PLAYER_SLOTS - 9 + index
+    }
+
+    fn is_result(&self, slot: usize) -> bool {
+        self.kind == ContainerKind::CraftingTable && slot == CRAFTING_TABLE_OUTPUT_SLOT
+    }
+}
+
+/// A container window a client currently has open.
+#[derive(Clone, Debug)]
+pub struct OpenContainer {
+    pub id: u8,
+    pub kind: ContainerKind,
+    pub world: String,
+    /// Block entities holding the contents, in slot order.
+    pub positions: Vec<(i32, i32, i32)>,
+    /// Crafting grid of a crafting table, which only lives as long as the window.
+    pub crafting: Vec<Slot>,
+}
+
+impl OpenContainer {
+    pub fn new(id: u8, kind: ContainerKind, world: &str, positions: Vec<(i32, i32, i32)>) -> Self {
+        let crafting = if kind == ContainerKind::CraftingTable { vec![None; CRAFTING_TABLE_SIZE] } else { vec![] };
+        Self {
+            id,
+            kind,
+            world: world.to_string(),
+            positions,
+            crafting,
+        }
+    }
+
+    /// Whether the window shows the contents of the block at the position.
+    pub fn shows(&self, world: &str, position: (i32, i32, i32)) -> bool {
+        self.world == world && self.positions.contains(&position)
+    }
+
+    /// Current container slots, read from the world's block entities.
+    pub fn slots(&self, world: &mut World) -> Vec<Slot> {
+        if self.kind == ContainerKind::CraftingTable {
+            return self.crafting.clone();
+        }
+        let mut slots = Vec::with_capacity(self.kind.size());
+        for &(x, y, z) in &self.positions {
+            match world.get_block_entity(x, y, z) {
+                Some(entity) => slots.extend(entity.items.iter().cloned()),
+                None => slots.extend(vec![None; CONTAINER_SIZE]),
+            }
+        }
+        slots.resize(self.kind.size(), None);
+        slots
+    }
+
+    /// Writes container slots back to the world's block entities.
+    pub fn set_slots(&mut self, world: &mut World, slots: &[Slot]) {
+        if self.kind == ContainerKind::CraftingTable {
+            self.crafting = slots.to_vec();
+            return;
+        }
+        let mut start = 0;
+        for &(x, y, z) in &self.positions {
+            if let Some(entity) = world.get_block_entity_mut(x, y, z) {
+                let end = start + entity.items.len();
+                entity.items.clone_from_slice(&slots[start..end]);
+                start = end;
+            }
+        }
+    }
+}
+
+/// The window id to use after the previous one.
+pub fn next_window_id(previous: u8) -> u8 {
+    previous % MAX_WINDOW_ID + 1
+}
@@ -0,0 +1,156 @@
+use std::time::Instant;
+use crate::server::block::Block;
+
+/// Furthest a player's eyes may be from the centre of a block they dig or place against.
+pub const MAX_REACH: f64 = 6.0;
+/// Fraction of the expected break time a client has to have spent digging, to allow for latency.
+const DIG_TOLERANCE: f64 = 0.7;
+const TICK_MILLIS: f64 = 50.0;
+/// First and last item ids of the sword, shovel, pickaxe, axe and hoe of each tier.
+const FIRST_TOOL: i32 = 586;
+const LAST_TOOL: i32 = 615;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToolKind {
+    Sword,
+    Shovel,
+    Pickaxe,
+    Axe,
+    Hoe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToolTier {
+    Wood,
+    Stone,
+    Gold,
+    Iron,
+    Diamond,
+    Netherite,
+}
+
+impl ToolTier {
+    /// Mining speed multiplier when the tool is effective against a block.
+    pub fn speed(&self) -> f32 {
+        match self {
+            ToolTier::Wood => 2.0,
+            ToolTier::Stone => 4.0,
+            ToolTier::Gold => 12.0,
+            ToolTier::Iron => 6.0,
+            ToolTier::Diamond => 8.0,
+            ToolTier::Netherite => 9.0,
+        }
+    }
+
+    /// Harvest level, compared against the level a block requires to drop anything.
+    pub fn level(&self) -> i32 {
+        match self {
+            ToolTier::Wood | ToolTier::Gold => 0,
+            ToolTier::Stone => 1,
+            ToolTier::Iron => 2,
+            ToolTier::Diamond => 3,
+            ToolTier::Netherite => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tool {
+    pub kind: ToolKind,
+    pub tier: ToolTier,
+}
+
+/// Returns the tool an item id belongs to, if it is one.
+pub fn get_tool(item_id: i32) -> Option<Tool> {
+    if item_id < FIRST_TOOL || item_id > LAST_TOOL {
+        return None;
+    }
+    let offset = (item_id - FIRST_TOOL) as usize;
+    let tier = [ToolTier::Wood, ToolTier::Stone, ToolTier::Gold, ToolTier::Iron, ToolTier::Diamond, ToolTier::Netherite][offset / 5];
+    let kind = [ToolKind::Sword, ToolKind::Shovel, ToolKind::Pickaxe, ToolKind::Axe, ToolKind::Hoe][offset % 5];
+    Some(Tool { kind, tier })
+}
+
+/// The tool that speeds up breaking a block, and the harvest level needed for it to drop if the
+/// block cannot be harvested by hand.
+fn block_tool(block: &Block) -> Option<(ToolKind, Option<i32>)> {
+    let name = block.name.trim_start_matches("minecraft:");
+    match name {
+        "obsidian" | "respawn_anchor" => Some((ToolKind::Pickaxe, Some(3))),
+        "gold_ore" => Some((ToolKind::Pickaxe, Some(2))),
+        "iron_ore" => Some((ToolKind::Pickaxe, Some(1))),
+        "stone" | "granite" | "polished_granite" | "diorite" | "polished_diorite" | "andesite" | "polished_andesite"
+        | "cobblestone" | "coal_ore" | "nether_gold_ore" | "end_stone" | "furnace" => Some((ToolKind::Pickaxe, Some(0))),
+        "grass_block" | "dirt" | "coarse_dirt" | "podzol" | "sand" | "red_sand" | "gravel" => Some((ToolKind::Shovel, None)),
+        _ if name.ends_with("_planks") || name.ends_with("_log") || name.ends_with("_stairs") => Some((ToolKind::Axe, None)),
+        "chest" | "crafting_table" | "barrel" => Some((ToolKind::Axe, None)),
+        _ => None,
+    }
+}
+
+/// Whether breaking the block with the given tool drops it.
+pub fn can_harvest(block: &Block, tool: Option<&Tool>) -> bool {
+    match block_tool(block) {
+        Some((kind, Some(level))) => tool.map(|tool| tool.kind == kind && tool.tier.level() >= level).unwrap_or(false),
+        _ => true,
+    }
+}
+
+/// Ticks a survival player needs to break a block, or `None` if it cannot be broken. Zero means
+/// the block breaks as soon as digging starts.
+pub fn break_ticks(block: &Block, tool: Option<&Tool>, efficiency: i32, haste: i32, on_ground: bool) -> Option<u32> {
+    if block.hardness < 0.0 {
+        return None;
+    }
+    if block.hardness == 0.0 {
+        return Some(0);
+    }
+
+    let mut speed = match (block_tool(block), tool) {
+        (Some((kind, _)), Some(tool)) if tool.kind == kind => tool.tier.speed(),
+        _ => 1.0,
+    };
+    if speed > 1.0 && efficiency > 0 {
+        speed += (efficiency * efficiency + 1) as f32;
+    }
+    speed *= 1.0 + 0.2 * haste as f32;
+    if !on_ground {
+        speed /= 5.0;
+    }
+
+    let damage = speed / block.hardness / if can_harvest(block, tool) { 30.0 } else { 100.0 };
+    if damage > 1.0 {
+        Some(0)
+    } else {
+        Some((1.0 / damage).ceil() as u32)
+    }
+}
+
+/// A block a survival player has started digging.
+#[derive(Clone, Debug)]
+pub struct DigState {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub started: Instant,
+    /// Ticks the break was expected to take when digging started.
+    pub ticks: u32,
+}
+
+impl DigState {
+    pub fn new(x: i32, y: i32, z: i32, ticks: u32) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            started: Instant::now(),
+            ticks,
+        }
+    }
+
+    /// Whether enough time has passed to finish breaking the block.
+    pub fn is_done(&self) -> bool {
+        let elapsed = self.started.elapsed().as_millis() as f64 / TICK_MILLIS;
+        elapsed >= self.ticks as f64 * DIG_TOLERANCE
+    }
+}
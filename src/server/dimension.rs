@@ -0,0 +1,386 @@
+use mcproto_rs::nbt::{NamedTag, Tag};
+use mcproto_rs::types::NamedNbtTag;
+
+/// Entry of one of the registries sent in the dimension codec.
+pub trait RegistryEntry {
+    fn name(&self) -> &str;
+    fn to_nbt(&self) -> Tag;
+}
+
+/// Named entries in the order their protocol ids are assigned.
+#[derive(Clone, Debug)]
+pub struct Registry<T: RegistryEntry> {
+    kind: &'static str,
+    entries: Vec<T>,
+}
+
+impl<T: RegistryEntry> Registry<T> {
+    pub fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            entries: vec![],
+        }
+    }
+
+    /// Adds an entry, replacing any entry with the same name while keeping its id.
+    pub fn register(&mut self, entry: T) -> i32 {
+        if let Some(id) = self.id_of(entry.name()) {
+            self.entries[id as usize] = entry;
+            id
+        } else {
+            self.entries.push(entry);
+            self.entries.len() as i32 - 1
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.entries.iter().find(|entry| entry.name() == name)
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<i32> {
+        self.entries.iter().position(|entry| entry.name() == name).map(|id| id as i32)
+    }
+
+    pub fn entries(&self) -> &[T] {
+        &self.entries
+    }
+
+    pub fn to_nbt(&self) -> Tag {
+        let values = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(id, entry)| {
+                Tag::Compound(vec![
+                    Tag::String(entry.name().to_string()).with_name("name"),
+                    Tag::Int(id as i32).with_name("id"),
+                    entry.to_nbt().with_name("element"),
+                ])
+            })
+            .collect();
+
+        Tag::Compound(vec![
+            Tag::String(self.kind.to_string()).with_name("type"),
+            Tag::List(values).with_name("value"),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DimensionType {
+    pub name: String,
+    pub piglin_safe: bool,
+    pub natural: bool,
+    pub ambient_light: f32,
+    /// Time of day the dimension is locked to, if any.
+    pub fixed_time: Option<i64>,
+    pub infiniburn: String,
+    pub respawn_anchor_works: bool,
+    pub has_skylight: bool,
+    pub bed_works: bool,
+    pub effects: String,
+    pub has_raids: bool,
+    pub logical_height: i32,
+    /// Factor applied to coordinates when travelling between dimensions, 8 for the nether.
+    pub coordinate_scale: f64,
+    pub ultrawarm: bool,
+    pub has_ceiling: bool,
+}
+
+impl DimensionType {
+    pub fn overworld() -> Self {
+        Self {
+            name: "minecraft:overworld".to_string(),
+            piglin_safe: false,
+            natural: true,
+            ambient_light: 0.0,
+            fixed_time: None,
+            infiniburn: "minecraft:infiniburn_overworld".to_string(),
+            respawn_anchor_works: false,
+            has_skylight: true,
+            bed_works: true,
+            effects: "minecraft:overworld".to_string(),
+            has_raids: true,
+            logical_height: 256,
+            coordinate_scale: 1.0,
+            ultrawarm: false,
+            has_ceiling: false,
+        }
+    }
+
+    pub fn overworld_caves() -> Self {
+        Self {
+            name: "minecraft:overworld_caves".to_string(),
+            has_ceiling: true,
+            ..Self::overworld()
+        }
+    }
+
+    pub fn nether() -> Self {
+        Self {
+            name: "minecraft:the_nether".to_string(),
+            piglin_safe: true,
+            natural: false,
+            ambient_light: 0.1,
+            fixed_time: Some(18000),
+            infiniburn: "minecraft:infiniburn_nether".to_string(),
+            respawn_anchor_works: true,
+            has_skylight: false,
+            bed_works: false,
+            effects: "minecraft:the_nether".to_string(),
+            has_raids: false,
+            logical_height: 128,
+            coordinate_scale: 8.0,
+            ultrawarm: true,
+            has_ceiling: true,
+        }
+    }
+
+    pub fn end() -> Self {
+        Self {
+            name: "minecraft:the_end".to_string(),
+            piglin_safe: false,
+            natural: false,
+            ambient_light: 0.0,
+            fixed_time: Some(6000),
+            infiniburn: "minecraft:infiniburn_end".to_string(),
+            respawn_anchor_works: false,
+            has_skylight: false,
+            bed_works: false,
+            effects: "minecraft:the_end".to_string(),
+            has_raids: true,
+            logical_height: 256,
+            coordinate_scale: 1.0,
+            ultrawarm: false,
+            has_ceiling: false,
+        }
+    }
+
+    /// The compound sent as the `dimension` of Join Game and Respawn.
+    pub fn to_named_nbt(&self) -> NamedNbtTag {
+        NamedNbtTag {
+            root: self.to_nbt().with_name(""),
+        }
+    }
+}
+
+impl RegistryEntry for DimensionType {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn to_nbt(&self) -> Tag {
+        let mut tags = vec![
+            byte(self.piglin_safe).with_name("piglin_safe"),
+            byte(self.natural).with_name("natural"),
+            Tag::Float(self.ambient_light).with_name("ambient_light"),
+            Tag::String(self.infiniburn.clone()).with_name("infiniburn"),
+            byte(self.respawn_anchor_works).with_name("respawn_anchor_works"),
+            byte(self.has_skylight).with_name("has_skylight"),
+            byte(self.bed_works).with_name("bed_works"),
+            Tag::String(self.effects.clone()).with_name("effects"),
+            byte(self.has_raids).with_name("has_raids"),
+            Tag::Int(self.logical_height).with_name("logical_height"),
+            Tag::Double(self.coordinate_scale).with_name("coordinate_scale"),
+            byte(self.ultrawarm).with_name("ultrawarm"),
+            byte(self.has_ceiling).with_name("has_ceiling"),
+        ];
+        if let Some(fixed_time) = self.fixed_time {
+            tags.push(Tag::Long(fixed_time).with_name("fixed_time"));
+        }
+        Tag::Compound(tags)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Precipitation {
+    None,
+    Rain,
+    Snow,
+}
+
+impl Precipitation {
+    pub fn name(&self) -> &str {
+        match self {
+            Precipitation::None => "none",
+            Precipitation::Rain => "rain",
+            Precipitation::Snow => "snow",
+        }
+    }
+}
+
+/// Ambient sound played at random while the player is in a dark area of the biome.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoodSound {
+    pub sound: String,
+    pub tick_delay: i32,
+    pub offset: f64,
+    pub block_search_extent: i32,
+}
+
+impl MoodSound {
+    pub fn cave() -> Self {
+        Self {
+            sound: "minecraft:ambient.cave".to_string(),
+            tick_delay: 6000,
+            offset: 2.0,
+            block_search_extent: 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BiomeEffects {
+    pub sky_color: i32,
+    pub water_fog_color: i32,
+    pub fog_color: i32,
+    pub water_color: i32,
+    pub mood_sound: Option<MoodSound>,
+}
+
+impl BiomeEffects {
+    fn new(sky_color: i32, fog_color: i32) -> Self {
+        Self {
+            sky_color,
+            water_fog_color: 329011,
+            fog_color,
+            water_color: 4159204,
+            mood_sound: Some(MoodSound::cave()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Biome {
+    pub name: String,
+    pub precipitation: Precipitation,
+    pub depth: f32,
+    pub temperature: f32,
+    pub scale: f32,
+    pub downfall: f32,
+    pub category: String,
+    pub effects: BiomeEffects,
+}
+
+impl Biome {
+    fn new(name: &str, precipitation: Precipitation, depth: f32, scale: f32, temperature: f32, downfall: f32, category: &str, effects: BiomeEffects) -> Self {
+        Self {
+            name: name.to_string(),
+            precipitation,
+            depth,
+            temperature,
+            scale,
+            downfall,
+            category: category.to_string(),
+            effects,
+        }
+    }
+
+    pub fn ocean() -> Self {
+        Self::new("minecraft:ocean", Precipitation::Rain, -1.0, 0.1, 0.5, 0.5, "ocean", BiomeEffects::new(8103167, 12638463))
+    }
+
+    pub fn plains() -> Self {
+        Self::new("minecraft:plains", Precipitation::Rain, 0.125, 0.05, 0.8, 0.4, "plains", BiomeEffects::new(7907327, 12638463))
+    }
+
+    pub fn desert() -> Self {
+        Self::new("minecraft:desert", Precipitation::None, 0.125, 0.05, 2.0, 0.0, "desert", BiomeEffects::new(7254527, 12638463))
+    }
+
+    pub fn forest() -> Self {
+        Self::new("minecraft:forest", Precipitation::Rain, 0.1, 0.2, 0.7, 0.8, "forest", BiomeEffects::new(7972607, 12638463))
+    }
+
+    pub fn nether_wastes() -> Self {
+        let mut effects = BiomeEffects::new(7254527, 3344392);
+        effects.mood_sound = Some(MoodSound {
+            sound: "minecraft:ambient.nether_wastes.mood".to_string(),
+            ..MoodSound::cave()
+        });
+        Self::new("minecraft:nether_wastes", Precipitation::None, 0.1, 0.2, 2.0, 0.0, "nether", effects)
+    }
+
+    pub fn the_end() -> Self {
+        Self::new("minecraft:the_end", Precipitation::None, 0.1, 0.2, 0.5, 0.5, "the_end", BiomeEffects::new(0, 10518688))
+    }
+}
+
+impl RegistryEntry for Biome {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn to_nbt(&self) -> Tag {
+        let mut effects = vec![
+            Tag::Int(self.effects.sky_color).with_name("sky_color"),
+            Tag::Int(self.effects.water_fog_color).with_name("water_fog_color"),
+            Tag::Int(self.effects.fog_color).with_name("fog_color"),
+            Tag::Int(self.effects.water_color).with_name("water_color"),
+        ];
+        if let Some(mood_sound) = &self.effects.mood_sound {
+            effects.push(Tag::Compound(vec![
+                Tag::Int(mood_sound.tick_delay).with_name("tick_delay"),
+                Tag::Double(mood_sound.offset).with_name("offset"),
+                Tag::String(mood_sound.sound.clone()).with_name("sound"),
+                Tag::Int(mood_sound.block_search_extent).with_name("block_search_extent"),
+            ]).with_name("mood_sound"));
+        }
+
+        Tag::Compound(vec![
+            Tag::String(self.precipitation.name().to_string()).with_name("precipitation"),
+            Tag::Compound(effects).with_name("effects"),
+            Tag::Float(self.depth).with_name("depth"),
+            Tag::Float(self.temperature).with_name("temperature"),
+            Tag::Float(self.scale).with_name("scale"),
+            Tag::Float(self.downfall).with_name("downfall"),
+            Tag::String(self.category.clone()).with_name("category"),
+        ])
+    }
+}
+
+/// The dimension type and biome registries sent to clients in Join Game.
+#[derive(Clone, Debug)]
+pub struct DimensionCodec {
+    pub dimension_types: Registry<DimensionType>,
+    pub biomes: Registry<Biome>,
+}
+
+impl DimensionCodec {
+    pub fn vanilla() -> Self {
+        let mut dimension_types = Registry::new("minecraft:dimension_type");
+        dimension_types.register(DimensionType::overworld());
+        dimension_types.register(DimensionType::overworld_caves());
+        dimension_types.register(DimensionType::nether());
+        dimension_types.register(DimensionType::end());
+
+        let mut biomes = Registry::new("minecraft:worldgen/biome");
+        biomes.register(Biome::ocean());
+        biomes.register(Biome::plains());
+        biomes.register(Biome::desert());
+        biomes.register(Biome::forest());
+        biomes.register(Biome::nether_wastes());
+        biomes.register(Biome::the_end());
+
+        Self {
+            dimension_types,
+            biomes,
+        }
+    }
+
+    pub fn to_named_nbt(&self) -> NamedNbtTag {
+        NamedNbtTag {
+            root: NamedTag {
+                name: String::new(),
+                payload: Tag::Compound(vec![
+                    self.dimension_types.to_nbt().with_name("minecraft:dimension_type"),
+                    self.biomes.to_nbt().with_name("minecraft:worldgen/biome"),
+                ]),
+            },
+        }
+    }
+}
+
+fn byte(value: bool) -> Tag {
+    Tag::Byte(value as i8)
+}
@@ -0,0 +1,69 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Hands out entity ids shared by players and every other entity type. Fresh ids come from an
+/// atomic counter, ids of destroyed entities are recycled before the counter is advanced.
+pub struct EntityIdAllocator {
+    next: AtomicI32,
+    released: Mutex<Vec<i32>>,
+}
+
+impl EntityIdAllocator {
+    pub fn new() -> Self {
+        Self {
+            next: AtomicI32::new(1),
+            released: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn allocate(&self) -> i32 {
+        if let Some(id) = self.released.lock().unwrap().pop() {
+            return id;
+        }
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Makes an id available again. Only call this once the entity has been destroyed for every
+    /// client that could see it.
+    pub fn release(&self, id: i32) {
+        self.released.lock().unwrap().push(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn allocate_concurrently(allocator: &Arc<EntityIdAllocator>, threads: usize, per_thread: usize) -> Vec<i32> {
+        let handles = (0..threads).map(|_| {
+            let allocator = allocator.clone();
+            thread::spawn(move || (0..per_thread).map(|_| allocator.allocate()).collect::<Vec<i32>>())
+        }).collect::<Vec<_>>();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    }
+
+    #[test]
+    fn concurrent_ids_are_unique() {
+        let allocator = Arc::new(EntityIdAllocator::new());
+        let ids = allocate_concurrently(&allocator, 16, 1000);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    }
+
+    #[test]
+    fn released_ids_are_reused_without_duplicates() {
+        let allocator = Arc::new(EntityIdAllocator::new());
+        let first = allocate_concurrently(&allocator, 8, 500);
+        let (released, kept) = first.split_at(first.len() / 2);
+        for id in released {
+            allocator.release(*id);
+        }
+
+        let second = allocate_concurrently(&allocator, 8, 500);
+        let live = kept.iter().chain(second.iter()).collect::<Vec<_>>();
+        assert_eq!(live.iter().collect::<HashSet<_>>().len(), live.len());
+        assert!(released.iter().all(|id| second.contains(id)));
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Result};
+
+/// Server-wide game rules, named after their vanilla counterparts.
+#[derive(Clone, Debug)]
+pub struct GameRules {
+    pub do_daylight_cycle: bool,
+    pub keep_inventory: bool,
+    pub natural_regeneration: bool,
+    /// Blocks around the world spawn that players may be placed at.
+    pub spawn_radius: i32,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            do_daylight_cycle: true,
+            keep_inventory: false,
+            natural_regeneration: true,
+            spawn_radius: 10,
+        }
+    }
+}
+
+impl GameRules {
+    /// Names of every rule, as used by `get` and `set`.
+    pub const NAMES: &'static [&'static str] = &["doDaylightCycle", "keepInventory", "naturalRegeneration", "spawnRadius"];
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "doDaylightCycle" => Some(self.do_daylight_cycle.to_string()),
+            "keepInventory" => Some(self.keep_inventory.to_string()),
+            "naturalRegeneration" => Some(self.natural_regeneration.to_string()),
+            "spawnRadius" => Some(self.spawn_radius.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let invalid = || anyhow!("Invalid value {} for game rule {}.", value, name);
+        match name {
+            "doDaylightCycle" => self.do_daylight_cycle = value.parse().map_err(|_| invalid())?,
+            "keepInventory" => self.keep_inventory = value.parse().map_err(|_| invalid())?,
+            "naturalRegeneration" => self.natural_regeneration = value.parse().map_err(|_| invalid())?,
+            "spawnRadius" => self.spawn_radius = value.parse::<i32>().map_err(|_| invalid())?.max(0),
+            _ => return Err(anyhow!("Unknown game rule {}.", name)),
+        }
+        Ok(())
+    }
+}
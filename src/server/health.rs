@@ -0,0 +1,240 @@
+use mcproto_rs::types::Chat;
+use crate::server::block;
+use crate::server::player::Position;
+use crate::server::world::World;
+
+pub const MAX_HEALTH: f32 = 20.0;
+pub const MAX_FOOD: i32 = 20;
+/// Ticks of air a player can hold underwater.
+pub const MAX_AIR: i32 = 300;
+/// Falls up to this many blocks deal no damage.
+const SAFE_FALL_DISTANCE: f64 = 3.0;
+/// Players below this height take void damage.
+const VOID_DEPTH: f64 = -64.0;
+/// Ticks after taking damage during which further damage is ignored.
+const INVULNERABLE_TICKS: i32 = 10;
+/// Ticks a player keeps burning after leaving fire or lava.
+const FIRE_TICKS: i32 = 160;
+const LAVA_FIRE_TICKS: i32 = 300;
+const EYE_HEIGHT: f64 = 1.62;
+
+/// Exhaustion costs, which drain saturation then food every 4 points.
+pub const SPRINT_EXHAUSTION: f32 = 0.1;
+pub const JUMP_EXHAUSTION: f32 = 0.05;
+pub const SPRINT_JUMP_EXHAUSTION: f32 = 0.2;
+pub const DAMAGE_EXHAUSTION: f32 = 0.1;
+pub const BREAK_EXHAUSTION: f32 = 0.005;
+const REGEN_EXHAUSTION: f32 = 6.0;
+const EXHAUSTION_PER_FOOD: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DamageCause {
+    Fall,
+    Void,
+    Drowning,
+    InFire,
+    OnFire,
+    Lava,
+    Starvation,
+    Kill,
+}
+
+impl DamageCause {
+    /// Whether the damage still applies to creative players and through invulnerability ticks.
+    pub fn bypasses_invulnerability(&self) -> bool {
+        *self == DamageCause::Void || *self == DamageCause::Kill
+    }
+
+    fn causes_exhaustion(&self) -> bool {
+        !matches!(self, DamageCause::Void | DamageCause::Starvation | DamageCause::Kill)
+    }
+
+    pub fn death_message(&self, name: &str) -> Chat {
+        Chat::from_text(&match self {
+            DamageCause::Fall => format!("{} hit the ground too hard", name),
+            DamageCause::Void => format!("{} fell out of the world", name),
+            DamageCause::Drowning => format!("{} drowned", name),
+            DamageCause::InFire => format!("{} went up in flames", name),
+            DamageCause::OnFire => format!("{} burned to death", name),
+            DamageCause::Lava => format!("{} tried to swim in lava", name),
+            DamageCause::Starvation => format!("{} starved to death", name),
+            DamageCause::Kill => format!("{} died", name),
+        })
+    }
+}
+
+/// A player's health and food, along with the timers that drive damage and regeneration.
+#[derive(Clone, Debug)]
+pub struct Health {
+    pub health: f32,
+    pub hunger: i32,
+    pub saturation: f32,
+    pub exhaustion: f32,
+    /// Ticks since the last regeneration or starvation damage.
+    food_ticks: u32,
+    pub air: i32,
+    /// Blocks fallen since last touching the ground.
+    pub fall_distance: f64,
+    pub fire_ticks: i32,
+    invulnerable_ticks: i32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            health: MAX_HEALTH,
+            hunger: MAX_FOOD,
+            saturation: 5.0,
+            exhaustion: 0.0,
+            food_ticks: 0,
+            air: MAX_AIR,
+            fall_distance: 0.0,
+            fire_ticks: 0,
+            invulnerable_ticks: 0,
+        }
+    }
+}
+
+impl Health {
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.0
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.health = (self.health + amount).min(MAX_HEALTH);
+        }
+    }
+
+    pub fn add_exhaustion(&mut self, amount: f32) {
+        self.exhaustion = (self.exhaustion + amount).min(40.0);
+    }
+
+    /// Applies damage unless the player is still invulnerable from the last hit. Returns whether
+    /// any was dealt.
+    pub fn damage(&mut self, amount: f32, cause: DamageCause) -> bool {
+        if self.is_dead() || amount <= 0.0 || (self.invulnerable_ticks > 0 && !cause.bypasses_invulnerability()) {
+            return false;
+        }
+        self.health = (self.health - amount).max(0.0);
+        self.invulnerable_ticks = INVULNERABLE_TICKS;
+        if cause.causes_exhaustion() {
+            self.add_exhaustion(DAMAGE_EXHAUSTION);
+        }
+        true
+    }
+
+    /// Tracks a vertical move, returning the fall damage taken on landing.
+    pub fn update_fall(&mut self, dy: f64, on_ground: bool, in_water: bool) -> f32 {
+        if in_water {
+            self.fall_distance = 0.0;
+            return 0.0;
+        }
+        if dy < 0.0 {
+            self.fall_distance -= dy;
+        }
+        if !on_ground {
+            return 0.0;
+        }
+        let damage = (self.fall_distance - SAFE_FALL_DISTANCE).ceil().max(0.0) as f32;
+        self.fall_distance = 0.0;
+        damage
+    }
+
+    /// Advances food by one tick following the vanilla rules for normal difficulty: exhaustion
+    /// drains saturation then food, a full bar regenerates health and an empty one starves the
+    /// player down to half a heart. Returns whether starvation damage is due.
+    pub fn tick_food(&mut self, natural_regeneration: bool) -> bool {
+        if self.invulnerable_ticks > 0 {
+            self.invulnerable_ticks -= 1;
+        }
+        if self.exhaustion > EXHAUSTION_PER_FOOD {
+            self.exhaustion -= EXHAUSTION_PER_FOOD;
+            if self.saturation > 0.0 {
+                self.saturation = (self.saturation - 1.0).max(0.0);
+            } else {
+                self.hunger = (self.hunger - 1).max(0);
+            }
+        }
+
+        let hurt = self.health < MAX_HEALTH;
+        if natural_regeneration && self.saturation > 0.0 && hurt && self.hunger >= MAX_FOOD {
+            self.food_ticks += 1;
+            if self.food_ticks >= 10 {
+                let amount = self.saturation.min(REGEN_EXHAUSTION);
+                self.heal(amount / REGEN_EXHAUSTION);
+                self.add_exhaustion(amount);
+                self.food_ticks = 0;
+            }
+        } else if natural_regeneration && self.hunger >= 18 && hurt {
+            self.food_ticks += 1;
+            if self.food_ticks >= 80 {
+                self.heal(1.0);
+                self.add_exhaustion(REGEN_EXHAUSTION);
+                self.food_ticks = 0;
+            }
+        } else if self.hunger <= 0 {
+            self.food_ticks += 1;
+            if self.food_ticks >= 80 {
+                self.food_ticks = 0;
+                return self.health > 1.0;
+            }
+        } else {
+            self.food_ticks = 0;
+        }
+        false
+    }
+
+    /// Advances air, fire and void checks by one tick for a player at the given position,
+    /// returning the damage they take, if any.
+    pub fn tick_environment(&mut self, world: &mut World, position: &Position) -> Option<(f32, DamageCause)> {
+        if position.y < VOID_DEPTH {
+            return Some((4.0, DamageCause::Void));
+        }
+
+        let (x, z) = (position.x.floor() as i32, position.z.floor() as i32);
+        let feet = block_name(world, x, position.y.floor() as i32, z);
+        let head = block_name(world, x, (position.y + EYE_HEIGHT).floor() as i32, z);
+        let in_water = feet == "minecraft:water" || head == "minecraft:water";
+        let in_lava = feet == "minecraft:lava" || head == "minecraft:lava";
+        let in_fire = feet == "minecraft:fire";
+
+        let mut damage = None;
+        if head == "minecraft:water" {
+            self.air -= 1;
+            if self.air <= -20 {
+                self.air = 0;
+                damage = Some((2.0, DamageCause::Drowning));
+            }
+        } else {
+            self.air = (self.air + 4).min(MAX_AIR);
+        }
+
+        if in_water {
+            self.fire_ticks = 0;
+        } else if in_lava {
+            self.fire_ticks = self.fire_ticks.max(LAVA_FIRE_TICKS);
+            damage = damage.or(Some((4.0, DamageCause::Lava)));
+        } else if in_fire {
+            self.fire_ticks = self.fire_ticks.max(FIRE_TICKS);
+            damage = damage.or(Some((1.0, DamageCause::InFire)));
+        }
+
+        if self.fire_ticks > 0 {
+            if self.fire_ticks % 20 == 0 {
+                damage = damage.or(Some((1.0, DamageCause::OnFire)));
+            }
+            self.fire_ticks -= 1;
+        }
+        damage
+    }
+}
+
+fn block_name(world: &mut World, x: i32, y: i32, z: i32) -> &'static str {
+    block::get_block(world.get_block(x, y, z)).map(|block| block.name).unwrap_or("")
+}
+
+/// Whether a player at the position is standing in water, which cancels falls.
+pub fn in_water(world: &mut World, position: &Position) -> bool {
+    block_name(world, position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32) == "minecraft:water"
+}
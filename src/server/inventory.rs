@@ -0,0 +1,427 @@
+use std::ops::Range;
+use anyhow::{anyhow, Result};
+use mcproto_rs::types::{ItemStack, Slot};
+use mcproto_rs::v1_16_3::InventoryOperationMode;
+use crate::server::item;
+use crate::server::player::{CRAFTING_OUTPUT_SLOT, HOTBAR_SLOTS, INVENTORY_SIZE, MAIN_SLOTS, OFFHAND_SLOT};
+
+/// Id of the player's own inventory window, which is always open.
+pub const PLAYER_WINDOW_ID: u8 = 0;
+/// Window id and slot used by Set Slot to update the item held on the cursor.
+pub const CURSOR_WINDOW_ID: u8 = 255;
+pub const CURSOR_SLOT: i16 = -1;
+/// Slot sent for clicks outside of the window, which drop the cursor.
+pub const OUTSIDE_WINDOW: i16 = -999;
+/// Button a number click uses for the swap offhand key.
+const OFFHAND_BUTTON: i8 = 40;
+const DEFAULT_MAX_STACK: i8 = 64;
+
+/// Rules a window imposes on clicks: where items may be placed and where shift-clicks send them.
+pub trait WindowLayout {
+    fn size(&self) -> usize;
+
+    /// Whether the player may put the item in the slot. Output slots only allow taking.
+    fn can_place(&self, slot: usize, item: &ItemStack) -> bool;
+
+    /// Slot ranges a shift-clicked stack is moved into, tried in order.
+    fn shift_targets(&self, slot: usize) -> Vec<Range<usize>>;
+
+    /// Window slot of the given hotbar slot, from 0 to 8.
+    fn hotbar_slot(&self, index: usize) -> usize;
+
+    fn offhand_slot(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the slot holds a crafting result, which double clicks do not collect from since
+    /// taking it consumes the ingredients.
+    fn is_result(&self, _slot: usize) -> bool {
+        false
+    }
+}
+
+/// The player's inventory window: crafting output, 2x2 crafting input, armor, main inventory,
+/// hotbar and offhand.
+pub struct PlayerWindow;
+
+impl WindowLayout for PlayerWindow {
+    fn size(&self) -> usize {
+        INVENTORY_SIZE
+    }
+
+    fn can_place(&self, slot: usize, _item: &ItemStack) -> bool {
+        slot != CRAFTING_OUTPUT_SLOT
+    }
+
+    fn shift_targets(&self, slot: usize) -> Vec<Range<usize>> {
+        if MAIN_SLOTS.contains(&slot) {
+            vec![HOTBAR_SLOTS]
+        } else if HOTBAR_SLOTS.contains(&slot) {
+            vec![MAIN_SLOTS]
+        } else {
+            vec![MAIN_SLOTS.start..HOTBAR_SLOTS.end]
+        }
+    }
+
+    fn hotbar_slot(&self, index: usize) -> usize {
+        HOTBAR_SLOTS.start + index
+    }
+
+    fn offhand_slot(&self) -> Option<usize> {
+        Some(OFFHAND_SLOT)
+    }
+
+    fn is_result(&self, slot: usize) -> bool {
+        slot == CRAFTING_OUTPUT_SLOT
+    }
+}
+
+/// Largest stack an item can form.
+pub fn max_stack_size(item: &ItemStack) -> i8 {
+    item::get_item(item.item_id.0).map(|item| item.max_stack).unwrap_or(DEFAULT_MAX_STACK)
+}
+
+/// Whether two stacks are the same item and can be merged.
+pub fn same_item(a: &ItemStack, b: &ItemStack) -> bool {
+    a.item_id == b.item_id && a.nbt == b.nbt
+}
+
+fn with_count(item: &ItemStack, count: i8) -> Slot {
+    if count <= 0 {
+        None
+    } else {
+        Some(ItemStack {
+            item_count: count,
+            ..item.clone()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DragKind {
+    /// Splits the cursor evenly across the slots.
+    Even,
+    /// Places one item in each slot.
+    Single,
+    /// Fills each slot with a full stack, creative only.
+    Clone,
+}
+
+#[derive(Clone, Debug)]
+struct Drag {
+    kind: DragKind,
+    slots: Vec<usize>,
+}
+
+/// Click state of a client that outlives a single click: the item on the cursor and any drag in
+/// progress.
+#[derive(Clone, Debug, Default)]
+pub struct WindowState {
+    pub cursor: Slot,
+    drag: Option<Drag>,
+    /// Action number of a rejected click the client has yet to acknowledge. Clicks are ignored
+    /// until it does.
+    pub pending_confirmation: Option<i16>,
+    /// Items from a closed crafting grid that fit nowhere, given back the next time a window
+    /// closes.
+    pub overflow: Vec<ItemStack>,
+}
+
+/// A click as sent in Click Window.
+pub struct Click {
+    pub slot: i16,
+    pub button: i8,
+    pub mode: InventoryOperationMode,
+}
+
+/// Applies a click to the window's slots. An error means the click was invalid and the client has
+/// to be resynchronised. Item entities do not exist yet, so clicks that would drop items are
+/// refused rather than deleting them.
+pub fn click(layout: &dyn WindowLayout, slots: &mut [Slot], state: &mut WindowState, click: &Click, creative: bool) -> Result<()> {
+    if click.mode != InventoryOperationMode::Drag && state.drag.take().is_some() {
+        return Err(anyhow!("Drag was interrupted by another click."));
+    }
+
+    if click.slot == OUTSIDE_WINDOW {
+        return match click.mode {
+            InventoryOperationMode::MouseClick if state.cursor.is_some() => Err(anyhow!("Items can not be dropped yet.")),
+            InventoryOperationMode::MouseClick => Ok(()),
+            InventoryOperationMode::Drag => drag(layout, slots, state, click, creative),
+            _ => Ok(()),
+        };
+    }
+
+    let slot = click.slot as usize;
+    if click.slot < 0 || slot >= layout.size() || slot >= slots.len() {
+        return Err(anyhow!("Slot {} is outside of the window.", click.slot));
+    }
+
+    match click.mode {
+        InventoryOperationMode::MouseClick => mouse_click(layout, slots, state, slot, click.button == 1),
+        InventoryOperationMode::ShiftClick => {
+            shift_click(layout, slots, slot);
+            Ok(())
+        }
+        InventoryOperationMode::NumberClick => number_click(layout, slots, slot, click.button),
+        InventoryOperationMode::MiddleClick => {
+            if creative && state.cursor.is_none() {
+                if let Some(item) = &slots[slot] {
+                    state.cursor = with_count(item, max_stack_size(item));
+                }
+            }
+            Ok(())
+        }
+        InventoryOperationMode::DropClick => {
+            if state.cursor.is_some() || slots[slot].is_none() {
+                return Ok(());
+            }
+            Err(anyhow!("Items can not be dropped yet."))
+        }
+        InventoryOperationMode::Drag => drag(layout, slots, state, click, creative),
+        InventoryOperationMode::DoubleClick => {
+            collect(layout, slots, state);
+            Ok(())
+        }
+    }
+}
+
+fn mouse_click(layout: &dyn WindowLayout, slots: &mut [Slot], state: &mut WindowState, slot: usize, right: bool) -> Result<()> {
+    let current = slots[slot].take();
+    let cursor = state.cursor.take();
+
+    let (slot_item, cursor_item) = match (current, cursor) {
+        (None, None) => (None, None),
+        (Some(item), None) => {
+            if right {
+                let taken = (item.item_count + 1) / 2;
+                (with_count(&item, item.item_count - taken), with_count(&item, taken))
+            } else {
+                (None, Some(item))
+            }
+        }
+        (None, Some(cursor)) => {
+            if !layout.can_place(slot, &cursor) {
+                (None, Some(cursor))
+            } else {
+                let placed = if right { 1 } else { cursor.item_count.min(max_stack_size(&cursor)) };
+                (with_count(&cursor, placed), with_count(&cursor, cursor.item_count - placed))
+            }
+        }
+        (Some(item), Some(cursor)) if same_item(&item, &cursor) => {
+            let max = max_stack_size(&item);
+            if !layout.can_place(slot, &cursor) {
+                // Taking from an output slot adds to the cursor when everything fits.
+                if item.item_count as i32 + cursor.item_count as i32 <= max as i32 {
+                    (None, with_count(&cursor, item.item_count + cursor.item_count))
+                } else {
+                    (Some(item), Some(cursor))
+                }
+            } else {
+                let wanted = if right { 1 } else { cursor.item_count };
+                let moved = wanted.min(max - item.item_count).max(0);
+                (with_count(&item, item.item_count + moved), with_count(&cursor, cursor.item_count - moved))
+            }
+        }
+        (Some(item), Some(cursor)) => {
+            if layout.can_place(slot, &cursor) && cursor.item_count <= max_stack_size(&cursor) {
+                (Some(cursor), Some(item))
+            } else {
+                (Some(item), Some(cursor))
+            }
+        }
+    };
+
+    slots[slot] = slot_item;
+    state.cursor = cursor_item;
+    Ok(())
+}
+
+/// Moves a stack into the window's other section, topping up matching stacks before using empty
+/// slots.
+fn shift_click(layout: &dyn WindowLayout, slots: &mut [Slot], slot: usize) {
+    let mut item = match slots[slot].take() {
+        Some(item) => item,
+        None => return,
+    };
+
+    for range in layout.shift_targets(slot) {
+        let range = range.start.min(slots.len())..range.end.min(slots.len());
+        for fill_empty in [false, true].iter() {
+            for target in range.clone() {
+                if target == slot || !layout.can_place(target, &item) {
+                    continue;
+                }
+                let max = max_stack_size(&item);
+                match &mut slots[target] {
+                    Some(existing) if !fill_empty && same_item(existing, &item) && existing.item_count < max => {
+                        let moved = item.item_count.min(max - existing.item_count);
+                        existing.item_count += moved;
+                        item.item_count -= moved;
+                    }
+                    empty @ None if *fill_empty => {
+                        let moved = item.item_count.min(max);
+                        *empty = with_count(&item, moved);
+                        item.item_count -= moved;
+                    }
+                    _ => {}
+                }
+                if item.item_count <= 0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    slots[slot] = Some(item);
+}
+
+fn number_click(layout: &dyn WindowLayout, slots: &mut [Slot], slot: usize, button: i8) -> Result<()> {
+    let other = match button {
+        0..=8 => layout.hotbar_slot(button as usize),
+        OFFHAND_BUTTON => layout.offhand_slot().ok_or(anyhow!("Window has no offhand slot."))?,
+        _ => return Err(anyhow!("Invalid number key {}.", button)),
+    };
+    if other >= slots.len() || other == slot {
+        return Ok(());
+    }
+
+    let allowed = slots[other].as_ref().map(|item| layout.can_place(slot, item)).unwrap_or(true)
+        && slots[slot].as_ref().map(|item| layout.can_place(other, item)).unwrap_or(true);
+    if allowed {
+        slots.swap(slot, other);
+    }
+    Ok(())
+}
+
+fn drag(layout: &dyn WindowLayout, slots: &mut [Slot], state: &mut WindowState, click: &Click, creative: bool) -> Result<()> {
+    let kind = match click.button / 4 {
+        0 => DragKind::Even,
+        1 => DragKind::Single,
+        2 if creative => DragKind::Clone,
+        _ => return Err(anyhow!("Invalid drag button {}.", click.button)),
+    };
+
+    match click.button % 4 {
+        0 => {
+            if state.cursor.is_none() || state.drag.is_some() {
+                state.drag = None;
+                return Err(anyhow!("Drag started without an item on the cursor."));
+            }
+            state.drag = Some(Drag { kind, slots: vec![] });
+            Ok(())
+        }
+        1 => {
+            let slot = click.slot as usize;
+            match &mut state.drag {
+                Some(drag) if drag.kind == kind && click.slot >= 0 && slot < slots.len() => {
+                    if !drag.slots.contains(&slot) {
+                        drag.slots.push(slot);
+                    }
+                    Ok(())
+                }
+                _ => {
+                    state.drag = None;
+                    Err(anyhow!("Invalid drag slot {}.", click.slot))
+                }
+            }
+        }
+        2 => {
+            let drag = state.drag.take().ok_or(anyhow!("Drag ended before it started."))?;
+            let mut cursor = match state.cursor.take() {
+                Some(cursor) => cursor,
+                None => return Ok(()),
+            };
+            let max = max_stack_size(&cursor);
+            let targets: Vec<usize> = drag
+                .slots
+                .into_iter()
+                .filter(|slot| layout.can_place(*slot, &cursor))
+                .filter(|slot| slots[*slot].as_ref().map(|item| same_item(item, &cursor)).unwrap_or(true))
+                .collect();
+            if targets.is_empty() {
+                state.cursor = Some(cursor);
+                return Ok(());
+            }
+
+            let per_slot = match drag.kind {
+                DragKind::Even => (cursor.item_count as usize / targets.len()).max(1) as i8,
+                DragKind::Single => 1,
+                DragKind::Clone => max,
+            };
+            for target in targets {
+                if drag.kind != DragKind::Clone && cursor.item_count <= 0 {
+                    break;
+                }
+                let existing = slots[target].as_ref().map(|item| item.item_count).unwrap_or(0);
+                let mut moved = per_slot.min(max - existing).max(0);
+                if drag.kind != DragKind::Clone {
+                    moved = moved.min(cursor.item_count);
+                    cursor.item_count -= moved;
+                }
+                slots[target] = with_count(&cursor, existing + moved);
+            }
+            state.cursor = with_count(&cursor, cursor.item_count);
+            Ok(())
+        }
+        _ => Err(anyhow!("Invalid drag button {}.", click.button)),
+    }
+}
+
+/// Gathers items matching the cursor from the whole window but its result slots, partial stacks
+/// first.
+fn collect(layout: &dyn WindowLayout, slots: &mut [Slot], state: &mut WindowState) {
+    let cursor = match &mut state.cursor {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let max = max_stack_size(cursor);
+
+    for take_full in [false, true].iter() {
+        for (index, slot) in slots.iter_mut().enumerate() {
+            if cursor.item_count >= max {
+                return;
+            }
+            if layout.is_result(index) {
+                continue;
+            }
+            let item = match slot {
+                Some(item) if same_item(item, cursor) && (*take_full || item.item_count < max_stack_size(item)) => item,
+                _ => continue,
+            };
+            let moved = item.item_count.min(max - cursor.item_count);
+            cursor.item_count += moved;
+            item.item_count -= moved;
+            if item.item_count <= 0 {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Adds an item to the first matching stacks then empty slots in the given range, returning
+/// whatever did not fit.
+pub fn add_item(slots: &mut [Slot], range: Range<usize>, mut item: ItemStack) -> Option<ItemStack> {
+    let range = range.start.min(slots.len())..range.end.min(slots.len());
+    let max = max_stack_size(&item);
+    for fill_empty in [false, true].iter() {
+        for slot in slots[range.clone()].iter_mut() {
+            match slot {
+                Some(existing) if !fill_empty && same_item(existing, &item) && existing.item_count < max => {
+                    let moved = item.item_count.min(max - existing.item_count);
+                    existing.item_count += moved;
+                    item.item_count -= moved;
+                }
+                None if *fill_empty => {
+                    let moved = item.item_count.min(max);
+                    *slot = with_count(&item, moved);
+                    item.item_count -= moved;
+                }
+                _ => {}
+            }
+            if item.item_count <= 0 {
+                return None;
+            }
+        }
+    }
+    Some(item)
+}
@@ -0,0 +1,538 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use mcproto_rs::nbt::{NamedTag, Tag};
+use mcproto_rs::types::{Chat, ItemStack, VarInt};
+use crate::server::block::{self, Block};
+
+/// An item type known to the server, with its 1.16.3 protocol id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: i32,
+    /// Namespaced identifier, e.g. `minecraft:stone`.
+    pub name: &'static str,
+    pub max_stack: i8,
+    /// Uses before the item breaks, 0 if it does not take damage.
+    pub max_damage: i32,
+    /// Block placed by the item, if any.
+    pub block: Option<&'static str>,
+}
+
+impl Item {
+    const fn new(id: i32, name: &'static str, max_stack: i8, max_damage: i32, block: Option<&'static str>) -> Self {
+        Self {
+            id,
+            name,
+            max_stack,
+            max_damage,
+            block,
+        }
+    }
+
+    const fn block(id: i32, name: &'static str) -> Self {
+        Self::new(id, name, 64, 0, Some(name))
+    }
+
+    const fn material(id: i32, name: &'static str) -> Self {
+        Self::new(id, name, 64, 0, None)
+    }
+
+    const fn tool(id: i32, name: &'static str, max_damage: i32) -> Self {
+        Self::new(id, name, 1, max_damage, None)
+    }
+
+    pub fn get_block(&self) -> Option<&'static Block> {
+        self.block.and_then(block::get_block_by_name)
+    }
+}
+
+pub const FLINT_AND_STEEL: i32 = 575;
+
+/// Vanilla data generator report listing every registry, read at startup when present.
+pub const REGISTRY_PATH: &str = "registries.json";
+
+/// Built-in items, used when no registry report was loaded. Their block placement overrides what
+/// the report suggests, since some items place blocks with a different name.
+pub const ITEMS: &[Item] = &[
+    Item::block(1, "minecraft:stone"),
+    Item::block(2, "minecraft:granite"),
+    Item::block(3, "minecraft:polished_granite"),
+    Item::block(4, "minecraft:diorite"),
+    Item::block(5, "minecraft:polished_diorite"),
+    Item::block(6, "minecraft:andesite"),
+    Item::block(7, "minecraft:polished_andesite"),
+    Item::block(8, "minecraft:grass_block"),
+    Item::block(9, "minecraft:dirt"),
+    Item::block(10, "minecraft:coarse_dirt"),
+    Item::block(11, "minecraft:podzol"),
+    Item::block(14, "minecraft:cobblestone"),
+    Item::block(15, "minecraft:oak_planks"),
+    Item::block(16, "minecraft:spruce_planks"),
+    Item::block(17, "minecraft:birch_planks"),
+    Item::block(18, "minecraft:jungle_planks"),
+    Item::block(19, "minecraft:acacia_planks"),
+    Item::block(20, "minecraft:dark_oak_planks"),
+    Item::block(29, "minecraft:bedrock"),
+    Item::block(30, "minecraft:sand"),
+    Item::block(31, "minecraft:red_sand"),
+    Item::block(32, "minecraft:gravel"),
+    Item::block(33, "minecraft:gold_ore"),
+    Item::block(34, "minecraft:iron_ore"),
+    Item::block(35, "minecraft:coal_ore"),
+    Item::block(36, "minecraft:nether_gold_ore"),
+    Item::block(37, "minecraft:oak_log"),
+    Item::block(38, "minecraft:spruce_log"),
+    Item::block(39, "minecraft:birch_log"),
+    Item::block(40, "minecraft:jungle_log"),
+    Item::block(41, "minecraft:acacia_log"),
+    Item::block(42, "minecraft:dark_oak_log"),
+    Item::block(172, "minecraft:obsidian"),
+    Item::block(181, "minecraft:oak_stairs"),
+    Item::block(182, "minecraft:chest"),
+    Item::block(185, "minecraft:crafting_table"),
+    Item::block(187, "minecraft:furnace"),
+    Item::tool(FLINT_AND_STEEL, "minecraft:flint_and_steel", 64),
+    Item::material(576, "minecraft:apple"),
+    Item::tool(577, "minecraft:bow", 384),
+    Item::material(578, "minecraft:arrow"),
+    Item::material(579, "minecraft:coal"),
+    Item::material(580, "minecraft:charcoal"),
+    Item::material(581, "minecraft:diamond"),
+    Item::material(582, "minecraft:iron_ingot"),
+    Item::material(583, "minecraft:gold_ingot"),
+    Item::material(584, "minecraft:netherite_ingot"),
+    Item::material(585, "minecraft:netherite_scrap"),
+    Item::tool(586, "minecraft:wooden_sword", 59),
+    Item::tool(587, "minecraft:wooden_shovel", 59),
+    Item::tool(588, "minecraft:wooden_pickaxe", 59),
+    Item::tool(589, "minecraft:wooden_axe", 59),
+    Item::tool(590, "minecraft:wooden_hoe", 59),
+    Item::tool(591, "minecraft:stone_sword", 131),
+    Item::tool(592, "minecraft:stone_shovel", 131),
+    Item::tool(593, "minecraft:stone_pickaxe", 131),
+    Item::tool(594, "minecraft:stone_axe", 131),
+    Item::tool(595, "minecraft:stone_hoe", 131),
+    Item::tool(596, "minecraft:golden_sword", 32),
+    Item::tool(597, "minecraft:golden_shovel", 32),
+    Item::tool(598, "minecraft:golden_pickaxe", 32),
+    Item::tool(599, "minecraft:golden_axe", 32),
+    Item::tool(600, "minecraft:golden_hoe", 32),
+    Item::tool(601, "minecraft:iron_sword", 250),
+    Item::tool(602, "minecraft:iron_shovel", 250),
+    Item::tool(603, "minecraft:iron_pickaxe", 250),
+    Item::tool(604, "minecraft:iron_axe", 250),
+    Item::tool(605, "minecraft:iron_hoe", 250),
+    Item::tool(606, "minecraft:diamond_sword", 1561),
+    Item::tool(607, "minecraft:diamond_shovel", 1561),
+    Item::tool(608, "minecraft:diamond_pickaxe", 1561),
+    Item::tool(609, "minecraft:diamond_axe", 1561),
+    Item::tool(610, "minecraft:diamond_hoe", 1561),
+    Item::tool(611, "minecraft:netherite_sword", 2031),
+    Item::tool(612, "minecraft:netherite_shovel", 2031),
+    Item::tool(613, "minecraft:netherite_pickaxe", 2031),
+    Item::tool(614, "minecraft:netherite_axe", 2031),
+    Item::tool(615, "minecraft:netherite_hoe", 2031),
+    Item::material(616, "minecraft:stick"),
+    Item::new(617, "minecraft:bowl", 64, 0, None),
+    Item::new(719, "minecraft:white_bed", 1, 0, Some("minecraft:white_bed")),
+    Item::new(720, "minecraft:orange_bed", 1, 0, Some("minecraft:orange_bed")),
+    Item::new(721, "minecraft:magenta_bed", 1, 0, Some("minecraft:magenta_bed")),
+    Item::new(722, "minecraft:light_blue_bed", 1, 0, Some("minecraft:light_blue_bed")),
+    Item::new(723, "minecraft:yellow_bed", 1, 0, Some("minecraft:yellow_bed")),
+    Item::new(724, "minecraft:lime_bed", 1, 0, Some("minecraft:lime_bed")),
+    Item::new(725, "minecraft:pink_bed", 1, 0, Some("minecraft:pink_bed")),
+    Item::new(726, "minecraft:gray_bed", 1, 0, Some("minecraft:gray_bed")),
+    Item::new(727, "minecraft:light_gray_bed", 1, 0, Some("minecraft:light_gray_bed")),
+    Item::new(728, "minecraft:cyan_bed", 1, 0, Some("minecraft:cyan_bed")),
+    Item::new(729, "minecraft:purple_bed", 1, 0, Some("minecraft:purple_bed")),
+    Item::new(730, "minecraft:blue_bed", 1, 0, Some("minecraft:blue_bed")),
+    Item::new(731, "minecraft:brown_bed", 1, 0, Some("minecraft:brown_bed")),
+    Item::new(732, "minecraft:green_bed", 1, 0, Some("minecraft:green_bed")),
+    Item::new(733, "minecraft:red_bed", 1, 0, Some("minecraft:red_bed")),
+    Item::new(734, "minecraft:black_bed", 1, 0, Some("minecraft:black_bed")),
+    Item::block(936, "minecraft:barrel"),
+];
+
+/// Every item of the game, indexed by protocol id, once `load_registry` has run.
+struct ItemRegistry {
+    items: Vec<Item>,
+    by_name: HashMap<&'static str, usize>,
+}
+
+static REGISTRY: OnceLock<ItemRegistry> = OnceLock::new();
+
+/// Tool tiers and the durability of their tools.
+const TOOL_DURABILITY: &[(&str, i32)] = &[
+    ("wooden", 59), ("stone", 131), ("iron", 250), ("golden", 32), ("diamond", 1561), ("netherite", 2031),
+];
+const TOOLS: &[&str] = &["sword", "shovel", "pickaxe", "axe", "hoe"];
+/// Armor materials and the multiplier of their durability.
+const ARMOR_MULTIPLIERS: &[(&str, i32)] = &[
+    ("leather", 5), ("chainmail", 15), ("iron", 15), ("golden", 7), ("diamond", 33), ("netherite", 37),
+];
+/// Armor pieces and their durability before the material's multiplier.
+const ARMOR_DURABILITY: &[(&str, i32)] = &[("helmet", 11), ("chestplate", 16), ("leggings", 15), ("boots", 13)];
+/// Damageable items that are neither tools nor armor.
+const OTHER_DURABILITY: &[(&str, i32)] = &[
+    ("bow", 384), ("crossbow", 326), ("trident", 250), ("shield", 336), ("elytra", 432), ("fishing_rod", 64),
+    ("carrot_on_a_stick", 25), ("warped_fungus_on_a_stick", 100), ("flint_and_steel", 64), ("shears", 238),
+    ("turtle_helmet", 275),
+];
+/// Items that do not stack, besides damageable ones and those matched by `UNSTACKABLE_SUFFIXES`.
+const UNSTACKABLE: &[&str] = &[
+    "water_bucket", "lava_bucket", "milk_bucket", "cod_bucket", "salmon_bucket", "potion", "splash_potion", "lingering_potion", "minecart",
+    "saddle", "cake", "enchanted_book", "totem_of_undying", "writable_book", "knowledge_book", "debug_stick",
+    "mushroom_stew", "rabbit_stew", "beetroot_soup", "suspicious_stew",
+];
+const UNSTACKABLE_SUFFIXES: &[&str] = &["_boat", "_minecart", "_bed", "shulker_box", "_horse_armor", "_banner_pattern", "fish_bucket"];
+const UNSTACKABLE_PREFIXES: &[&str] = &["music_disc_"];
+/// Items that stack to 16, besides signs and banners.
+const STACKS_TO_16: &[&str] = &["ender_pearl", "snowball", "egg", "bucket", "honey_bottle", "armor_stand", "written_book"];
+
+/// Durability of a vanilla item by its name without namespace, 0 if it does not take damage.
+pub fn vanilla_max_damage(name: &str) -> i32 {
+    if let Some((_, durability)) = OTHER_DURABILITY.iter().find(|(item, _)| *item == name) {
+        return *durability;
+    }
+    if let Some((material, kind)) = name.rsplit_once('_') {
+        if TOOLS.contains(&kind) {
+            if let Some((_, durability)) = TOOL_DURABILITY.iter().find(|(tier, _)| *tier == material) {
+                return *durability;
+            }
+        }
+        if let (Some((_, multiplier)), Some((_, base))) = (ARMOR_MULTIPLIERS.iter().find(|(tier, _)| *tier == material), ARMOR_DURABILITY.iter().find(|(piece, _)| *piece == kind)) {
+            return base * multiplier;
+        }
+    }
+    0
+}
+
+/// Stack size of a vanilla item by its name without namespace.
+pub fn vanilla_max_stack(name: &str) -> i8 {
+    if vanilla_max_damage(name) > 0
+        || UNSTACKABLE.contains(&name)
+        || UNSTACKABLE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        || UNSTACKABLE_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+        return 1;
+    }
+    if STACKS_TO_16.contains(&name) || name.ends_with("_sign") || name.ends_with("_banner") {
+        return 16;
+    }
+    64
+}
+
+/// Builds the item table from a `registries.json` report. Items that share their name with a
+/// block place that block, unless a built-in item says otherwise.
+fn parse_registry(json: &Value) -> Result<Vec<Item>> {
+    let entries = json["minecraft:item"]["entries"].as_object().ok_or(anyhow!("The report has no item registry"))?;
+    let blocks = json["minecraft:block"]["entries"].as_object()
+        .map(|blocks| blocks.keys().map(String::as_str).collect::<HashSet<&str>>())
+        .unwrap_or_default();
+    let mut items = entries.iter().map(|(name, entry)| {
+        let id = entry["protocol_id"].as_i64().ok_or(anyhow!("{} has no protocol id", name))? as i32;
+        if let Some(builtin) = ITEMS.iter().find(|item| item.name == name) {
+            return Ok(Item { id, ..builtin.clone() });
+        }
+        let short = name.trim_start_matches("minecraft:");
+        let name: &'static str = Box::leak(name.clone().into_boxed_str());
+        let block = if blocks.contains(name) { Some(name) } else { None };
+        Ok(Item::new(id, name, vanilla_max_stack(short), vanilla_max_damage(short), block))
+    }).collect::<Result<Vec<Item>>>()?;
+    items.sort_by_key(|item| item.id);
+    Ok(items)
+}
+
+/// Loads every item of the game from a vanilla `registries.json` report, which the server's data
+/// generator writes to `generated/reports`. Can only be done once, before items are looked up.
+pub fn load_registry<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let json: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let items = parse_registry(&json)?;
+    let by_name = items.iter().enumerate().map(|(index, item)| (item.name, index)).collect();
+    let count = items.len();
+    REGISTRY.set(ItemRegistry { items, by_name }).map_err(|_| anyhow!("The item registry was already loaded"))?;
+    Ok(count)
+}
+
+/// Every known item, from the loaded registry or else the built-in table.
+pub fn items() -> &'static [Item] {
+    match REGISTRY.get() {
+        Some(registry) => &registry.items,
+        None => ITEMS,
+    }
+}
+
+pub fn get_item(id: i32) -> Option<&'static Item> {
+    match REGISTRY.get() {
+        Some(registry) => registry.items.get(id as usize).filter(|item| item.id == id)
+            .or_else(|| registry.items.iter().find(|item| item.id == id)),
+        None => ITEMS.iter().find(|item| item.id == id),
+    }
+}
+
+pub fn get_item_by_name(name: &str) -> Option<&'static Item> {
+    let name = if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{}", name)
+    };
+
+    match REGISTRY.get() {
+        Some(registry) => registry.by_name.get(name.as_str()).map(|index| &registry.items[*index]),
+        None => ITEMS.iter().find(|item| item.name == name),
+    }
+}
+
+/// Builds item stacks along with the NBT vanilla uses for names, lore, enchantments and damage.
+#[derive(Clone, Debug)]
+pub struct ItemBuilder {
+    item: &'static Item,
+    count: i8,
+    display_name: Option<Chat>,
+    lore: Vec<Chat>,
+    enchantments: Vec<(String, i16)>,
+    damage: i32,
+    unbreakable: bool,
+    custom: Vec<NamedTag>,
+}
+
+impl ItemBuilder {
+    pub fn new(item: &'static Item) -> Self {
+        Self {
+            item,
+            count: 1,
+            display_name: None,
+            lore: vec![],
+            enchantments: vec![],
+            damage: 0,
+            unbreakable: false,
+            custom: vec![],
+        }
+    }
+
+    /// Starts building the item with the given name, `minecraft:` may be left out.
+    pub fn from_name(name: &str) -> Result<Self> {
+        get_item_by_name(name)
+            .map(Self::new)
+            .ok_or(anyhow!("Unknown item {}.", name))
+    }
+
+    pub fn from_id(id: i32) -> Result<Self> {
+        get_item(id).map(Self::new).ok_or(anyhow!("Unknown item id {}.", id))
+    }
+
+    /// Sets the stack size, clamped between 1 and the item's max stack size.
+    pub fn count(mut self, count: i8) -> Self {
+        self.count = count.max(1).min(self.item.max_stack);
+        self
+    }
+
+    pub fn display_name(mut self, name: Chat) -> Self {
+        self.display_name = Some(name);
+        self
+    }
+
+    pub fn lore(mut self, lore: Vec<Chat>) -> Self {
+        self.lore = lore;
+        self
+    }
+
+    pub fn add_lore(mut self, line: Chat) -> Self {
+        self.lore.push(line);
+        self
+    }
+
+    /// Adds an enchantment, e.g. `efficiency`, replacing any existing level of it.
+    pub fn enchantment(mut self, name: &str, level: i16) -> Self {
+        let name = if name.contains(':') {
+            name.to_string()
+        } else {
+            format!("minecraft:{}", name)
+        };
+        self.enchantments.retain(|(existing, _)| *existing != name);
+        self.enchantments.push((name, level));
+        self
+    }
+
+    /// Sets how many uses the item has lost, clamped to its durability.
+    pub fn damage(mut self, damage: i32) -> Self {
+        self.damage = damage.max(0).min(self.item.max_damage);
+        self
+    }
+
+    pub fn unbreakable(mut self, unbreakable: bool) -> Self {
+        self.unbreakable = unbreakable;
+        self
+    }
+
+    /// Adds a custom tag to the item's NBT, replacing any tag with the same name.
+    pub fn nbt(mut self, tag: NamedTag) -> Self {
+        self.custom.retain(|existing| existing.name != tag.name);
+        self.custom.push(tag);
+        self
+    }
+
+    pub fn build(self) -> ItemStack {
+        let mut tags = vec![];
+
+        let mut display = vec![];
+        if let Some(name) = &self.display_name {
+            display.push(Tag::String(chat_json(name)).with_name("Name"));
+        }
+        if !self.lore.is_empty() {
+            let lore = self.lore.iter().map(|line| Tag::String(chat_json(line))).collect();
+            display.push(Tag::List(lore).with_name("Lore"));
+        }
+        if !display.is_empty() {
+            tags.push(Tag::Compound(display).with_name("display"));
+        }
+
+        if !self.enchantments.is_empty() {
+            let enchantments = self
+                .enchantments
+                .iter()
+                .map(|(name, level)| {
+                    Tag::Compound(vec![
+                        Tag::String(name.clone()).with_name("id"),
+                        Tag::Short(*level).with_name("lvl"),
+                    ])
+                })
+                .collect();
+            tags.push(Tag::List(enchantments).with_name("Enchantments"));
+        }
+
+        if self.item.max_damage > 0 {
+            tags.push(Tag::Int(self.damage).with_name("Damage"));
+        }
+        if self.unbreakable {
+            tags.push(Tag::Byte(1).with_name("Unbreakable"));
+        }
+
+        for tag in self.custom {
+            tags.retain(|existing| existing.name != tag.name);
+            tags.push(tag);
+        }
+
+        ItemStack {
+            item_id: VarInt::from(self.item.id),
+            item_count: self.count,
+            nbt: if tags.is_empty() {
+                None
+            } else {
+                Some(Tag::Compound(tags).with_name(""))
+            },
+        }
+    }
+}
+
+/// Level of an enchantment on a stack, 0 if it is not enchanted with it.
+pub fn enchantment_level(item: &ItemStack, name: &str) -> i16 {
+    let enchantments = match compound_tag(item, "Enchantments") {
+        Some(Tag::List(enchantments)) => enchantments,
+        _ => return 0,
+    };
+
+    for enchantment in enchantments {
+        if let Tag::Compound(tags) = enchantment {
+            let id = tags.iter().find(|tag| tag.name == "id");
+            let level = tags.iter().find(|tag| tag.name == "lvl");
+            if let (Some(NamedTag { payload: Tag::String(id), .. }), Some(level)) = (id, level) {
+                if id == name || id.trim_start_matches("minecraft:") == name {
+                    return match level.payload {
+                        Tag::Short(level) => level,
+                        Tag::Int(level) => level as i16,
+                        _ => 0,
+                    };
+                }
+            }
+        }
+    }
+    0
+}
+
+/// Uses a stack has lost, from its `Damage` tag.
+pub fn damage(item: &ItemStack) -> i32 {
+    match compound_tag(item, "Damage") {
+        Some(Tag::Int(damage)) => *damage,
+        _ => 0,
+    }
+}
+
+/// Returns the stack with its `Damage` tag set, keeping the rest of its NBT.
+pub fn set_damage(mut item: ItemStack, damage: i32) -> ItemStack {
+    let tag = Tag::Int(damage).with_name("Damage");
+    match &mut item.nbt {
+        Some(NamedTag { payload: Tag::Compound(tags), .. }) => {
+            tags.retain(|existing| existing.name != "Damage");
+            tags.push(tag);
+        }
+        _ => item.nbt = Some(Tag::Compound(vec![tag]).with_name("")),
+    }
+    item
+}
+
+pub fn is_unbreakable(item: &ItemStack) -> bool {
+    matches!(compound_tag(item, "Unbreakable"), Some(Tag::Byte(1)))
+}
+
+fn compound_tag<'a>(item: &'a ItemStack, name: &str) -> Option<&'a Tag> {
+    match &item.nbt {
+        Some(NamedTag { payload: Tag::Compound(tags), .. }) => tags.iter().find(|tag| tag.name == name).map(|tag| &tag.payload),
+        _ => None,
+    }
+}
+
+fn chat_json(chat: &Chat) -> String {
+    serde_json::to_string(chat).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stack_sizes_follow_vanilla() {
+        for name in ["ender_pearl", "oak_sign", "white_banner", "bucket", "snowball"] {
+            assert_eq!(vanilla_max_stack(name), 16, "{}", name);
+        }
+        for name in ["water_bucket", "potion", "oak_boat", "red_bed", "music_disc_cat", "diamond_sword", "creeper_banner_pattern", "shulker_box", "cod_bucket"] {
+            assert_eq!(vanilla_max_stack(name), 1, "{}", name);
+        }
+        for name in ["stone", "stick", "filled_map", "redstone"] {
+            assert_eq!(vanilla_max_stack(name), 64, "{}", name);
+        }
+    }
+
+    #[test]
+    fn durability_follows_vanilla() {
+        assert_eq!(vanilla_max_damage("diamond_pickaxe"), 1561);
+        assert_eq!(vanilla_max_damage("golden_hoe"), 32);
+        assert_eq!(vanilla_max_damage("iron_chestplate"), 240);
+        assert_eq!(vanilla_max_damage("leather_boots"), 65);
+        assert_eq!(vanilla_max_damage("elytra"), 432);
+        assert_eq!(vanilla_max_damage("stone"), 0);
+        assert_eq!(vanilla_max_damage("pickaxe"), 0);
+    }
+
+    #[test]
+    fn registry_report_is_parsed() {
+        let report = json!({
+            "minecraft:block": { "entries": { "minecraft:stone": { "protocol_id": 1 }, "minecraft:redstone_wire": { "protocol_id": 2 } } },
+            "minecraft:item": { "entries": {
+                "minecraft:air": { "protocol_id": 0 },
+                "minecraft:stone": { "protocol_id": 1 },
+                "minecraft:ender_pearl": { "protocol_id": 2 },
+                "minecraft:iron_sword": { "protocol_id": 3 },
+            } },
+        });
+        let items = parse_registry(&report).unwrap();
+        assert_eq!(items.iter().map(|item| item.id).collect::<Vec<i32>>(), vec![0, 1, 2, 3]);
+        assert_eq!(items[1].block, Some("minecraft:stone"));
+        assert_eq!(items[2].max_stack, 16);
+        assert_eq!(items[2].block, None);
+        assert_eq!((items[3].max_stack, items[3].max_damage), (1, 250));
+    }
+}
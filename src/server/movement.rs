@@ -0,0 +1,222 @@
+use std::fmt;
+use std::time::Instant;
+use mcproto_rs::types::Vec3;
+use mcproto_rs::v1_16_3::{EntityEffectKind, GameMode};
+use crate::server::block;
+use crate::server::player::{Player, Position};
+use crate::server::world::World;
+
+const PLAYER_HALF_WIDTH: f64 = 0.3;
+const PLAYER_HEIGHT: f64 = 1.8;
+const COLLISION_EPSILON: f64 = 0.001;
+/// Distance between collision samples taken along a movement.
+const PATH_STEP: f64 = 0.5;
+
+/// Thresholds used when validating client movement. Speeds are in blocks per tick.
+#[derive(Clone, Debug)]
+pub struct MovementConfig {
+    pub enabled: bool,
+    /// Horizontal limit while walking, sprinting or jumping.
+    pub max_ground_speed: f64,
+    /// Horizontal limit while flying.
+    pub max_fly_speed: f64,
+    /// Upwards limit while not flying, before jump boost.
+    pub max_ascend_speed: f64,
+    /// Upwards limit while flying.
+    pub max_fly_ascend_speed: f64,
+    /// Multiplier applied to every limit to absorb latency jitter.
+    pub tolerance: f64,
+    /// Upper bound on the ticks a single packet may account for after a pause.
+    pub max_catchup_ticks: u32,
+    /// Ticks a survival player may hover or rise without touching the ground.
+    pub max_air_ticks: u32,
+    pub check_collision: bool,
+    pub check_flight: bool,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_ground_speed: 0.7,
+            max_fly_speed: 1.1,
+            max_ascend_speed: 0.6,
+            max_fly_ascend_speed: 1.0,
+            tolerance: 1.2,
+            max_catchup_ticks: 20,
+            max_air_ticks: 20,
+            check_collision: true,
+            check_flight: true,
+        }
+    }
+}
+
+/// Per-player bookkeeping needed to validate consecutive movement packets.
+pub struct MovementState {
+    /// Last position that passed validation, used as the rollback target.
+    pub last_valid: Position,
+    pub last_update: Instant,
+    pub air_ticks: u32,
+    /// Teleport the client still has to confirm, movement is ignored until then.
+    pub pending_teleport: Option<i32>,
+    pub next_teleport_id: i32,
+    pub violations: u32,
+}
+
+impl MovementState {
+    pub fn new(position: Position) -> Self {
+        Self {
+            last_valid: position,
+            last_update: Instant::now(),
+            air_ticks: 0,
+            pending_teleport: None,
+            next_teleport_id: 0,
+            violations: 0,
+        }
+    }
+
+    pub fn next_teleport_id(&mut self) -> i32 {
+        let id = self.next_teleport_id;
+        self.next_teleport_id = self.next_teleport_id.wrapping_add(1);
+        self.pending_teleport = Some(id);
+        id
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MovementViolation {
+    TooFast { distance: f64, allowed: f64 },
+    TooHigh { distance: f64, allowed: f64 },
+    NoClip { x: i32, y: i32, z: i32 },
+    Flying { air_ticks: u32 },
+}
+
+impl fmt::Display for MovementViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovementViolation::TooFast { distance, allowed } => write!(f, "moved too quickly ({:.3} > {:.3})", distance, allowed),
+            MovementViolation::TooHigh { distance, allowed } => write!(f, "ascended too quickly ({:.3} > {:.3})", distance, allowed),
+            MovementViolation::NoClip { x, y, z } => write!(f, "moved into a solid block at {} {} {}", x, y, z),
+            MovementViolation::Flying { air_ticks } => write!(f, "was flying for {} ticks", air_ticks),
+        }
+    }
+}
+
+/// Checks a move of `player` from its current position to `to`, updating the movement state
+/// used by later checks. The player's position itself is left untouched.
+pub fn check_move(config: &MovementConfig, player: &mut Player, world: &mut World, to: &Vec3<f64>, on_ground: bool) -> Result<(), MovementViolation> {
+    let now = Instant::now();
+    let elapsed = now.duration_since(player.movement.last_update);
+    player.movement.last_update = now;
+
+    if !config.enabled || player.gamemode == GameMode::Spectator {
+        return Ok(());
+    }
+
+    let ticks = ((elapsed.as_millis() / 50) as u32).clamp(1, config.max_catchup_ticks.max(1)) as f64;
+    let from = &player.position;
+    let dx = to.x - from.x;
+    let dy = to.y - from.y;
+    let dz = to.z - from.z;
+
+    let speed_multiplier = 1.0 + 0.2 * player.effect_level(&EntityEffectKind::Speed) as f64;
+    let horizontal = (dx * dx + dz * dz).sqrt();
+    let allowed = if player.flying { config.max_fly_speed } else { config.max_ground_speed }
+        * speed_multiplier * config.tolerance * ticks;
+    if horizontal > allowed {
+        return Err(MovementViolation::TooFast { distance: horizontal, allowed });
+    }
+
+    let ascend = if player.flying {
+        config.max_fly_ascend_speed
+    } else {
+        config.max_ascend_speed + 0.1 * player.effect_level(&EntityEffectKind::JumpBoost) as f64
+    } * config.tolerance * ticks;
+    if dy > ascend {
+        return Err(MovementViolation::TooHigh { distance: dy, allowed: ascend });
+    }
+
+    if config.check_collision && colliding_block(world, from.x, from.y, from.z).is_none() {
+        let length = (horizontal * horizontal + dy * dy).sqrt();
+        let steps = (length / PATH_STEP).ceil().max(1.0) as i32;
+        for step in 1..=steps {
+            let t = step as f64 / steps as f64;
+            if let Some((x, y, z)) = colliding_block(world, from.x + dx * t, from.y + dy * t, from.z + dz * t) {
+                return Err(MovementViolation::NoClip { x, y, z });
+            }
+        }
+    }
+
+    let may_fly = player.may_fly();
+    if config.check_flight && !may_fly {
+        let grounded = on_ground && standing_on_block(world, to.x, to.y, to.z);
+        if grounded || dy < 0.0 || in_liquid(world, to.x, to.y, to.z) {
+            player.movement.air_ticks = 0;
+        } else {
+            player.movement.air_ticks += ticks as u32;
+            if player.movement.air_ticks > config.max_air_ticks {
+                let air_ticks = player.movement.air_ticks;
+                player.movement.air_ticks = 0;
+                return Err(MovementViolation::Flying { air_ticks });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a player standing at the given position overlaps the block.
+pub fn intersects_block(position: &Position, x: i32, y: i32, z: i32) -> bool {
+    position.x + PLAYER_HALF_WIDTH > x as f64 + COLLISION_EPSILON
+        && position.x - PLAYER_HALF_WIDTH < (x + 1) as f64 - COLLISION_EPSILON
+        && position.y + PLAYER_HEIGHT > y as f64 + COLLISION_EPSILON
+        && position.y < (y + 1) as f64 - COLLISION_EPSILON
+        && position.z + PLAYER_HALF_WIDTH > z as f64 + COLLISION_EPSILON
+        && position.z - PLAYER_HALF_WIDTH < (z + 1) as f64 - COLLISION_EPSILON
+}
+
+/// Returns the first solid block intersecting a player's bounding box at the given feet position.
+fn colliding_block(world: &mut World, x: f64, y: f64, z: f64) -> Option<(i32, i32, i32)> {
+    let min_x = (x - PLAYER_HALF_WIDTH + COLLISION_EPSILON).floor() as i32;
+    let max_x = (x + PLAYER_HALF_WIDTH - COLLISION_EPSILON).floor() as i32;
+    let min_y = (y + COLLISION_EPSILON).floor() as i32;
+    let max_y = (y + PLAYER_HEIGHT - COLLISION_EPSILON).floor() as i32;
+    let min_z = (z - PLAYER_HALF_WIDTH + COLLISION_EPSILON).floor() as i32;
+    let max_z = (z + PLAYER_HALF_WIDTH - COLLISION_EPSILON).floor() as i32;
+
+    for block_x in min_x..=max_x {
+        for block_y in min_y..=max_y {
+            for block_z in min_z..=max_z {
+                if block::is_solid(world.get_block(block_x, block_y, block_z)) {
+                    return Some((block_x, block_y, block_z));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn standing_on_block(world: &mut World, x: f64, y: f64, z: f64) -> bool {
+    let below = (y - 0.5).floor() as i32;
+    let min_x = (x - PLAYER_HALF_WIDTH).floor() as i32;
+    let max_x = (x + PLAYER_HALF_WIDTH).floor() as i32;
+    let min_z = (z - PLAYER_HALF_WIDTH).floor() as i32;
+    let max_z = (z + PLAYER_HALF_WIDTH).floor() as i32;
+
+    for block_x in min_x..=max_x {
+        for block_z in min_z..=max_z {
+            if block::is_solid(world.get_block(block_x, below, block_z)) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn in_liquid(world: &mut World, x: f64, y: f64, z: f64) -> bool {
+    let state = world.get_block(x.floor() as i32, y.floor() as i32, z.floor() as i32);
+    match block::get_block(state) {
+        Some(block) => block.name == "minecraft:water" || block.name == "minecraft:lava",
+        None => false,
+    }
+}
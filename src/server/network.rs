@@ -1,8 +1,9 @@
 use std::collections::{HashMap, HashSet};
 use std::net::{SocketAddr};
+use std::path::Path;
 use std::sync::{Arc};
 use mcproto_rs::status::{StatusFaviconSpec, StatusPlayersSpec, StatusSpec, StatusVersionSpec};
-use mcproto_rs::types::{Chat, ChunkPosition, CountedArray, EntityLocation, EntityRotation, ItemStack, NamedNbtTag, RemainingBytes, VarInt, Vec3};
+use mcproto_rs::types::{Chat, ColorCode, Slot, IntPosition, ChunkPosition, CountedArray, EntityLocation, EntityRotation, RemainingBytes, VarInt, Vec3};
 use mcproto_rs::uuid::UUID4;
 use tokio::runtime::Runtime;
 use tokio::sync::{Mutex, MutexGuard};
@@ -10,17 +11,70 @@ use anyhow::{anyhow, Result};
 use mcproto_rs::protocol::State;
 use mcproto_rs::protocol::State::Play;
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
 use mcproto_rs::{v1_16_3 as proto};
-use mcproto_rs::nbt::{NamedTag, Tag};
-use mcproto_rs::v1_16_3::{AdvancementMappingEntrySpec, ChatPosition, ChunkData, GameMode, HandshakeNextState, LoginEncryptionRequestSpec, LoginSetCompressionSpec, LoginSuccessSpec, Packet753 as Packet, Packet753, PlayClientPluginMessageSpec, PlayServerPlayerPositionAndLookSpec, PositionAndLookFlags, PreviousGameMode, RawPacket753 as RawPacket, StatusResponseSpec};
+use mcproto_rs::v1_16_3::{AdvancementMappingEntrySpec, ChatPosition, ClientChatMode, GameMode, HandshakeNextState, LoginEncryptionRequestSpec, LoginSetCompressionSpec, LoginSuccessSpec, Packet753 as Packet, Packet753, ChunkSectionPosition, DiggingStatus, Hand, MultiBlockChangeRecord, PlayAcknowledgePlayerDiggingSpec, PlayBlockChangeSpec, PlayBlockPlacementSpec, PlayClickWindowSpec, PlayClientPluginMessageSpec, PlayClientSettingsSpec, PlaySpawnPositionSpec, PlayChangeGameStateSpec, GameChangeReason, ClientStatusAction, CombatEvent, CombatEntityDeadSpec, PlayCombatEventSpec, PlayEntityStatusSpec, PlayUpdateHealthSpec, PlayOpenWindowSpec, PlayServerCloseWindowSpec, PlayWindowPropertySpec, PlayCreativeInventoryActionSpec, PlayServerHeldItemChangeSpec, PlayServerWindowConfirmationSpec, PlaySetSlotSpec, PlayWindowItemsSpec, InventoryOperationMode, PlayMultiBlockChangeSpec, PlayPlayerDiggingSpec, PlayerDiggingStatus, PlayServerKeepAliveSpec, PlayServerPlayerPositionAndLookSpec, PlayRespawnSpec, PlayUnloadChunkSpec, PlayUpdateViewPositionSpec, PositionAndLookFlags, PreviousGameMode, EntityActionKind, EntityEffectKind, RawPacket753 as RawPacket, StatusResponseSpec};
 use mcproto_rs::v1_16_3::CommandParserSpec::NbtCompoundTag;
 use mcproto_rs::v1_16_3::Packet753::{PlayClientPluginMessage, PlayServerPlayerPositionAndLook};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpListener;
-use crate::server::player::Player;
+use crate::server::player::{self, Player, Position};
 use crate::server::client::Client;
+use crate::server::movement::{self, MovementConfig};
+use crate::server::world::{self, BlockChange, World, WorldManager};
+use crate::server::portal::{self, PortalKind};
+use crate::server::block;
+use crate::server::digging::{self, DigState};
+use crate::server::placement;
+use crate::server::item;
+use crate::server::inventory::{self, Click, PlayerWindow, WindowState};
+use crate::server::container::{self, BlockEntity, ContainerKind, ContainerWindow, FurnaceState, OpenContainer};
+use crate::server::recipe::{self, RecipeManager};
+use crate::server::health::{self, DamageCause, Health};
+use crate::server::spawn;
+use crate::server::rcon;
+use crate::server::chat::{self, Channel, Channels, ChatMember, ChatState};
+use crate::server::playerdata::{PlayerData, PlayerDataStore};
+use crate::server::text::{self, ChatTemplates, Translations};
+use crate::server::gamerule::GameRules;
+use crate::server::command::CommandDispatcher;
+use crate::server::permission::{self, Permissions};
+use crate::server::userlist::{BanEntry, IpBanEntry, OpEntry, UserEntry, UserList};
+use crate::server::tracker::{self, EntityTracker};
+use crate::server::tablist;
+use crate::server::entity::EntityIdAllocator;
+use crate::server::dimension::{Biome, DimensionCodec, DimensionType};
+use crate::server::auth::ProfileProperty;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod commands;
+
+pub use commands::CommandSource;
+use commands::{default_commands, CommandAction};
 
 pub type NameUUID = (String, UUID4);
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+const PLAYER_EYE_HEIGHT: f64 = 1.62;
+const TICK_INTERVAL: Duration = Duration::from_millis(50);
+/// Bounds of the view distance, in chunks, as in vanilla.
+const MIN_VIEW_DISTANCE: i32 = 2;
+const MAX_VIEW_DISTANCE: i32 = 32;
+/// Lets players format their chat messages with `&` codes.
+const CHAT_COLOR_PERMISSION: &str = "endstone.chat.color";
+/// Exempts players from the chat rate limit.
+const SPAM_BYPASS_PERMISSION: &str = "endstone.chat.spam.bypass";
+
+/// What happens when a player logs in while a session with their name or UUID is still connected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateLoginPolicy {
+    /// Disconnect the existing session and let the new one in, like vanilla.
+    KickExisting,
+    /// Turn the new login away.
+    RejectNew,
+}
+
 pub type ConnectedClients = Arc<Mutex<HashMap<Arc<NameUUID>, Arc<Mutex<ServerClient>>>>>;
 
 pub struct Server {
@@ -28,14 +82,46 @@ pub struct Server {
     address: SocketAddr,
     online: bool,
     status: ServerStatus,
-    entity_ids: Arc<Mutex<HashSet<i32>>>,
+    entity_ids: Arc<EntityIdAllocator>,
     hardcore: bool,
+    /// Gamemode new players join in.
+    default_gamemode: GameMode,
+    worlds: WorldManager,
+    movement_config: MovementConfig,
+    tracking_range: f64,
+    /// Furthest a client may see, in chunks. Clients asking for less get what they ask for.
+    view_distance: i32,
+    translations: Arc<Translations>,
+    tab_header: Chat,
+    tab_footer: Chat,
+    dimension_codec: DimensionCodec,
+    recipes: RecipeManager,
+    game_rules: GameRules,
+    commands: CommandDispatcher<CommandAction>,
+    ops: UserList<OpEntry>,
+    /// Level players made operator with `/op` get.
+    op_permission_level: u8,
+    permissions: Permissions,
+    banned_players: UserList<BanEntry>,
+    banned_ips: UserList<IpBanEntry>,
+    whitelist: UserList<UserEntry>,
+    whitelist_enabled: bool,
+    /// Address and password of the RCON listener, if enabled.
+    rcon: Option<(SocketAddr, String)>,
+    duplicate_login_policy: DuplicateLoginPolicy,
+    /// Names and UUIDs of players past the duplicate login check who are not in `clients` yet.
+    logging_in: HashSet<NameUUID>,
+    chat_templates: ChatTemplates,
+    channels: Channels,
+    player_data: PlayerDataStore,
+    /// Set to true once the server is stopping.
+    shutdown: watch::Sender<bool>,
 }
 
 impl Server {
     pub fn new(address: SocketAddr, description: &str, max_players: i32, online: bool) -> Self {
         let status = ServerStatus {
-            description: Chat::from_traditional(description, true),
+            description: text::legacy(description, true).build(),
             players: StatusPlayersSpec {
                 max: max_players,
                 online: 0,
@@ -48,21 +134,623 @@ impl Server {
             favicon: None,
         };
 
+        let mut permissions = commands::default_permissions(online);
+        permissions.register(CHAT_COLOR_PERMISSION, 2);
+        permissions.register(SPAM_BYPASS_PERMISSION, 1);
+        permissions.register(chat::STAFF_CHANNEL_PERMISSION, 1);
+
         Self {
             clients: Arc::new(Mutex::new(HashMap::new())),
             address,
             status,
             online,
-            entity_ids: Arc::new(Mutex::new(HashSet::new())),
+            entity_ids: Arc::new(EntityIdAllocator::new()),
             hardcore: false,
+            default_gamemode: GameMode::Survival,
+            worlds: WorldManager::vanilla(),
+            movement_config: MovementConfig::default(),
+            tracking_range: tracker::DEFAULT_TRACKING_RANGE,
+            view_distance: 10,
+            translations: Arc::new(Translations::default()),
+            tab_header: Chat::from_text(""),
+            tab_footer: Chat::from_text(""),
+            dimension_codec: DimensionCodec::vanilla(),
+            recipes: RecipeManager::vanilla(),
+            game_rules: GameRules::default(),
+            commands: default_commands(),
+            ops: UserList::new("ops.json", online),
+            op_permission_level: permission::MAX_OP_LEVEL,
+            permissions,
+            banned_players: UserList::new("banned-players.json", online),
+            banned_ips: UserList::new("banned-ips.json", online),
+            whitelist: UserList::new("whitelist.json", online),
+            whitelist_enabled: false,
+            rcon: None,
+            chat_templates: ChatTemplates::default(),
+            channels: Channels::new(),
+            player_data: PlayerDataStore::new("playerdata"),
+            duplicate_login_policy: DuplicateLoginPolicy::KickExisting,
+            logging_in: HashSet::new(),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    /// Registers a custom dimension type so worlds can use it, replacing any with the same name.
+    /// Only players joining afterwards receive it.
+    pub fn register_dimension_type(&mut self, dimension_type: DimensionType) {
+        self.dimension_codec.dimension_types.register(dimension_type);
+    }
+
+    pub fn register_biome(&mut self, biome: Biome) {
+        self.dimension_codec.biomes.register(biome);
+    }
+
+    /// Loads vanilla format recipe files from a directory on top of the current recipes. Only
+    /// players joining afterwards receive them.
+    pub fn load_recipes(&mut self, path: &str) -> Result<usize> {
+        self.recipes.load_dir(path)
+    }
+
+    pub fn recipes(&self) -> &RecipeManager {
+        &self.recipes
+    }
+
+    pub fn set_default_gamemode(&mut self, gamemode: GameMode) {
+        self.default_gamemode = gamemode;
+    }
+
+    /// Switches a player's gamemode, updating their abilities and everyone's tab list.
+    pub async fn set_gamemode(&self, client: &mut ServerClient, gamemode: GameMode) -> Result<()> {
+        if client.player.gamemode == gamemode {
+            return Ok(());
+        }
+        client.player.set_gamemode(gamemode.clone());
+        client.connection.write_packet(Packet::PlayChangeGameState(PlayChangeGameStateSpec {
+            reason: GameChangeReason::ChangeGameMode(gamemode),
+        })).await?;
+        client.connection.write_packet(client.player.abilities()).await?;
+        self.update_tab_gamemode(client).await;
+        Ok(())
+    }
+
+    pub fn set_op_permission_level(&mut self, level: u8) {
+        self.op_permission_level = level.min(permission::MAX_OP_LEVEL);
+    }
+
+    /// Operator level of a player, 0 if they are not an operator.
+    pub fn op_level(&self, name: &str, uuid: Option<UUID4>) -> u8 {
+        self.ops.get(name, uuid).map(|op| op.level).unwrap_or(0)
+    }
+
+    /// Whether a player has a permission, through their overrides, their groups or their operator
+    /// level.
+    pub fn has_permission(&self, name: &str, uuid: Option<UUID4>, permission: &str) -> bool {
+        self.permissions.has(name, uuid, self.op_level(name, uuid), permission)
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// Permissions can be registered and changed freely. Call `refresh_permissions` afterwards so
+    /// clients see the commands they may now use.
+    pub fn permissions_mut(&mut self) -> &mut Permissions {
+        &mut self.permissions
+    }
+
+    /// Tells a client its operator level and the commands it may use.
+    async fn send_permissions(&self, client: &mut ServerClient) {
+        let (name, uuid) = (client.name.clone(), client.uuid);
+        let status = permission::op_level_status(self.op_level(&name, Some(uuid)));
+        let commands = self.commands.declare_commands(&|permission| self.has_permission(&name, Some(uuid), permission));
+        let _ = client.send_packets(vec![
+            Packet::PlayEntityStatus(PlayEntityStatusSpec { entity_id: client.entity_id, raw_status: status }),
+            commands,
+        ]).await;
+    }
+
+    /// Resends operator levels and commands to everyone after permissions changed. None of the
+    /// players may be locked by the caller.
+    pub async fn refresh_permissions(&self) {
+        for client in self.clients.lock().await.values() {
+            self.send_permissions(&mut *client.lock().await).await;
+        }
+    }
+
+    pub fn set_duplicate_login_policy(&mut self, policy: DuplicateLoginPolicy) {
+        self.duplicate_login_policy = policy;
+    }
+
+    /// The message of the day shown in the server list.
+    pub fn set_motd(&mut self, motd: impl Into<Chat>) {
+        self.status.description = motd.into();
+    }
+
+    pub fn chat_templates_mut(&mut self) -> &mut ChatTemplates {
+        &mut self.chat_templates
+    }
+
+    pub fn channels(&self) -> &Channels {
+        &self.channels
+    }
+
+    /// Plugins add their own chat channels here.
+    pub fn channels_mut(&mut self) -> &mut Channels {
+        &mut self.channels
+    }
+
+    /// Moves a player to the chat channel with the given name, if they may talk in it.
+    pub fn join_channel(&self, client: &mut ServerClient, name: &str) -> Result<()> {
+        let channel = self.channels.get(name).ok_or(anyhow!("Unknown channel {}", name))?;
+        if !channel.filter.can_join(self, &client.chat_member()) {
+            return Err(anyhow!("You may not talk in {}", channel.name));
+        }
+        client.channel = channel.name.clone();
+        Ok(())
+    }
+
+    /// Saves a player's ignore list and other data kept between sessions.
+    pub fn save_player_data(&self, client: &ServerClient) -> Result<()> {
+        self.player_data.save(client.uuid, &client.data)
+    }
+
+    pub fn set_whitelist_enabled(&mut self, enabled: bool) {
+        self.whitelist_enabled = enabled;
+    }
+
+    /// Accepts RCON connections on the address once the server starts, authenticating them with
+    /// the password.
+    pub fn enable_rcon(&mut self, address: SocketAddr, password: &str) {
+        self.rcon = Some((address, password.to_string()));
+    }
+
+    pub fn game_rules(&self) -> &GameRules {
+        &self.game_rules
+    }
+
+    pub fn game_rules_mut(&mut self) -> &mut GameRules {
+        &mut self.game_rules
+    }
+
+    pub fn worlds(&self) -> &WorldManager {
+        &self.worlds
+    }
+
+    /// Loads a world so players can be moved to it. The world's dimension type must be registered.
+    pub fn add_world(&mut self, world: World) {
+        self.worlds.add_world(world);
+    }
+
+    /// Moves a player to another world, respawning them in its dimension and resending chunks.
+    /// Without a position the player is placed at the world's spawn.
+    pub async fn change_world(&mut self, client: &mut ServerClient, world_name: &str, position: Option<Position>) -> Result<()> {
+        self.move_to_world(client, world_name, position, true).await
+    }
+
+    /// Moves a player to another world. Clients keep the player's entity metadata only if
+    /// `copy_metadata` is set, which it is not after a death.
+    async fn move_to_world(&mut self, client: &mut ServerClient, world_name: &str, position: Option<Position>, copy_metadata: bool) -> Result<()> {
+        let world = self.worlds.get_mut(world_name).ok_or(anyhow!("There is no world named {}.", world_name))?;
+        let biome_id = self.dimension_codec.biomes.id_of(&world.biome).unwrap_or(0);
+
+        client.unload_chunks().await?;
+        client.tracker.clear();
+        client.connection.write_packet(Packet::PlayRespawn(PlayRespawnSpec {
+            dimension: world.dimension_type.to_named_nbt(),
+            world_name: world.name.clone(),
+            hashed_seed: 0,
+            gamemode: client.player.gamemode.clone(),
+            previous_gamemode: client.player.previous_gamemode.clone().unwrap_or(client.player.gamemode.clone()),
+            is_debug: false,
+            is_flat: world.is_flat,
+            copy_metadata,
+        })).await?;
+
+        client.connection.write_packet(Packet::PlaySpawnPosition(PlaySpawnPositionSpec { location: world.spawn.clone() })).await?;
+        let mut packets = vec![world.time_packet(self.game_rules.do_daylight_cycle)];
+        packets.extend(world.weather_packets());
+        client.send_packets(packets).await?;
+        let spawn_radius = self.game_rules.spawn_radius;
+        let mut position = position.unwrap_or_else(|| spawn_position(world, spawn_radius, &client.player.position));
+        position.world = world.name.clone();
+        client.player.position = position.clone();
+        client.update_chunks(world, biome_id).await?;
+        client.teleport(position).await?;
+        client.send_player_state().await?;
+
+        self.update_tracking(client).await;
+        Ok(())
+    }
+
+    /// Moves a world's spawn and points the compasses of everyone in it there. None of the
+    /// players may be locked by the caller.
+    pub async fn set_world_spawn(&mut self, world_name: &str, spawn: IntPosition) -> Result<()> {
+        let world = self.worlds.get_mut(world_name).ok_or(anyhow!("There is no world named {}.", world_name))?;
+        world.spawn = spawn.clone();
+        self.send_to_world(world_name, vec![Packet::PlaySpawnPosition(PlaySpawnPositionSpec { location: spawn })]).await;
+        Ok(())
+    }
+
+    /// Sends packets to every player in a world. None of them may be locked by the caller.
+    async fn send_to_world(&self, world_name: &str, packets: Vec<Packet>) {
+        for client in self.clients.lock().await.values() {
+            let mut client = client.lock().await;
+            if client.player.position.world == world_name {
+                let _ = client.send_packets(packets.clone()).await;
+            }
+        }
+    }
+
+    /// Brings the clocks of every player in line with their world's time.
+    async fn send_time_updates(&self) {
+        for world in self.worlds.iter() {
+            self.send_to_world(&world.name, vec![world.time_packet(self.game_rules.do_daylight_cycle)]).await;
+        }
+    }
+
+    async fn send_weather(&self, world_name: &str) {
+        if let Some(world) = self.worlds.get(world_name) {
+            self.send_to_world(world_name, world.weather_packets()).await;
+        }
+    }
+
+    /// Moves a player anywhere, changing their world if needed.
+    pub async fn teleport_player(&mut self, client: &mut ServerClient, position: Position) -> Result<()> {
+        if position.world != client.player.position.world {
+            let world_name = position.world.clone();
+            return self.change_world(client, &world_name, Some(position)).await;
+        }
+        let world = self.worlds.get_mut(&position.world).ok_or(anyhow!("There is no world named {}.", position.world))?;
+        let biome_id = self.dimension_codec.biomes.id_of(&world.biome).unwrap_or(0);
+        client.player.position = position.clone();
+        client.update_chunks(world, biome_id).await?;
+        client.teleport(position).await?;
+        self.update_tracking(client).await;
+        Ok(())
+    }
+
+    /// Sends a player through a portal of the given kind from wherever they are standing, building
+    /// the portal or platform they arrive at if needed.
+    pub async fn travel(&mut self, client: &mut ServerClient, kind: PortalKind) -> Result<()> {
+        let from = self.worlds.get(&client.player.position.world).ok_or(anyhow!("{} is not in a loaded world.", client.name))?;
+        let from_dimension = from.dimension_type.clone();
+        let link = match kind {
+            PortalKind::Nether => from.nether_link.clone(),
+            PortalKind::End => from.end_link.clone(),
+            PortalKind::Gateway => Some(from.name.clone()),
+        }.ok_or(anyhow!("{} has no world linked to its portals.", from.name))?;
+
+        let target = self.worlds.get_mut(&link).ok_or(anyhow!("There is no world named {}.", link))?;
+        let (position, changes) = match kind {
+            PortalKind::Nether => portal::nether_destination(target, &from_dimension, &client.player.position),
+            PortalKind::End if target.dimension_type.effects == "minecraft:the_end" => portal::end_platform(target, &client.player.position),
+            PortalKind::End => (spawn_position(target, self.game_rules.spawn_radius, &client.player.position), vec![]),
+            PortalKind::Gateway => portal::gateway_destination(target, &client.player.position),
+        };
+        self.send_block_changes(&link, &changes, client).await;
+
+        if kind == PortalKind::Gateway {
+            self.teleport_player(client, position).await
+        } else {
+            self.change_world(client, &link, Some(position)).await
+        }
+    }
+
+    /// Lights the nether portal frame around the given block in the player's world. Returns
+    /// whether there was a valid frame.
+    pub async fn ignite_portal(&mut self, client: &mut ServerClient, x: i32, y: i32, z: i32) -> bool {
+        let world_name = client.player.position.world.clone();
+        let changes = match self.worlds.get_mut(&world_name).and_then(|world| portal::ignite(world, x, y, z)) {
+            Some(changes) => changes,
+            None => return false,
+        };
+        self.send_block_changes(&world_name, &changes, client).await;
+        true
+    }
+
+    /// Advances the server by one tick.
+    async fn tick(&mut self) {
+        self.tick_worlds().await;
+        self.tick_players().await;
+        self.tick_furnaces().await;
+    }
+
+    /// Advances time and weather, keeping clients in sync once a second and whenever the weather
+    /// changes.
+    async fn tick_worlds(&mut self) {
+        let daylight_cycle = self.game_rules.do_daylight_cycle;
+        let mut updates = vec![];
+        for world in self.worlds.iter_mut() {
+            let weather_changed = world.tick(daylight_cycle);
+            let mut packets = vec![];
+            if world.age % 20 == 0 {
+                packets.push(world.time_packet(daylight_cycle));
+            }
+            if weather_changed {
+                packets.extend(world.weather_packets());
+            }
+            if !packets.is_empty() {
+                updates.push((world.name.clone(), packets));
+            }
+        }
+        for (world_name, packets) in updates {
+            self.send_to_world(&world_name, packets).await;
+        }
+    }
+
+    /// Applies environmental damage, hunger and regeneration to every living player.
+    async fn tick_players(&mut self) {
+        let clients: Vec<Arc<Mutex<ServerClient>>> = self.clients.lock().await.values().cloned().collect();
+        for client in clients {
+            let mut client = client.lock().await;
+            if client.player.health.is_dead() || client.player.gamemode == GameMode::Spectator {
+                continue;
+            }
+            let world = match self.worlds.get_mut(&client.player.position.world) {
+                Some(world) => world,
+                None => continue,
+            };
+            let before = (client.player.health.health, client.player.health.hunger, client.player.health.saturation);
+            let position = client.player.position.clone();
+            let damage = client.player.health.tick_environment(world, &position);
+            let starving = client.player.health.tick_food(self.game_rules.natural_regeneration);
+
+            if let Some((amount, cause)) = damage {
+                self.damage_player(&mut client, amount, cause).await;
+            }
+            if starving {
+                self.damage_player(&mut client, 1.0, DamageCause::Starvation).await;
+            }
+            let after = (client.player.health.health, client.player.health.hunger, client.player.health.saturation);
+            if before != after && !client.player.health.is_dead() {
+                let _ = client.send_health().await;
+            }
+        }
+    }
+
+    /// Hurts a player, killing them if their health runs out. Creative players only take damage
+    /// that bypasses invulnerability.
+    pub async fn damage_player(&self, client: &mut ServerClient, amount: f32, cause: DamageCause) {
+        let vulnerable = client.player.gamemode == GameMode::Survival || client.player.gamemode == GameMode::Adventure;
+        if !vulnerable && !cause.bypasses_invulnerability() {
+            return;
+        }
+        if !client.player.health.damage(amount, cause) {
+            return;
+        }
+        let _ = client.send_health().await;
+        // Plays the hurt animation and sound.
+        self.broadcast_packet(Packet::PlayEntityStatus(PlayEntityStatusSpec { entity_id: client.entity_id, raw_status: 2 }), client).await;
+        if client.player.health.is_dead() {
+            self.kill(client, cause).await;
+        }
+    }
+
+    /// Shows the respawn screen to a player whose health ran out and announces their death.
+    /// Item entities do not exist yet, so their inventory is lost instead of dropped.
+    async fn kill(&self, client: &mut ServerClient, cause: DamageCause) {
+        let message = cause.death_message(&client.name);
+        println!("{} died ({:?}).", client.name, cause);
+        let _ = client.connection.write_packet(Packet::PlayCombatEvent(PlayCombatEventSpec {
+            event: CombatEvent::EntityDead(CombatEntityDeadSpec {
+                player_id: VarInt::from(client.entity_id),
+                entity_id: -1,
+                message: message.clone(),
+            }),
+        })).await;
+        self.broadcast_packet(Packet::PlayEntityStatus(PlayEntityStatusSpec { entity_id: client.entity_id, raw_status: 3 }), client).await;
+        self.broadcast_packet(Packet::PlayServerChatMessage(proto::PlayServerChatMessageSpec {
+            message,
+            position: ChatPosition::SystemMessage,
+            sender: UUID4::from(0),
+        }), client).await;
+
+        if !self.game_rules.keep_inventory {
+            client.player.inventory.clear();
+        }
+        client.window = WindowState::default();
+        client.container = None;
+        client.player.digging = None;
+    }
+
+    /// Brings a dead player back with full health at their bed or respawn anchor, or at the spawn
+    /// of the default world if they have none or it can no longer be used.
+    pub async fn respawn(&mut self, client: &mut ServerClient) -> Result<()> {
+        client.player.health = Health::default();
+        for (key, other) in self.clients.lock().await.iter() {
+            if key.1 == client.uuid {
+                continue;
+            }
+            let mut other = other.lock().await;
+            if let Some(packet) = other.tracker.destroy(&[client.entity_id]) {
+                let _ = other.connection.write_packet(packet).await;
+            }
+        }
+
+        let mut target = None;
+        if let Some(point) = client.player.spawn_point.clone() {
+            match self.worlds.get_mut(&point.world).and_then(|world| spawn::respawn_position(world, &point)) {
+                Some(((x, y, z), changes)) => {
+                    self.send_block_changes(&point.world, &changes, client).await;
+                    let position = Position {
+                        x: x as f64 + 0.5,
+                        y: y as f64,
+                        z: z as f64 + 0.5,
+                        on_ground: true,
+                        world: point.world.clone(),
+                        ..client.player.position.clone()
+                    };
+                    target = Some((point.world, Some(position)));
+                }
+                None => {
+                    client.player.spawn_point = None;
+                    let message = Chat::from_text("You have no home bed or charged respawn anchor, or it was obstructed");
+                    let _ = client.send_message(message, ChatPosition::SystemMessage, None).await;
+                }
+            }
+        }
+        let (world, position) = target.unwrap_or_else(|| (self.worlds.default_world_name().to_string(), None));
+        self.move_to_world(client, &world, position, false).await
+    }
+
+    /// Runs every furnace in every world for a tick.
+    async fn tick_furnaces(&mut self) {
+        // Furnaces whose progress changed, and whether their slots changed too.
+        let mut furnaces = vec![];
+        let mut changes: HashMap<String, Vec<BlockChange>> = HashMap::new();
+        for world in self.worlds.iter_mut() {
+            let world_name = world.name.clone();
+            let mut lit = vec![];
+            for (&position, entity) in world.block_entities_mut() {
+                let before = match &entity.furnace {
+                    Some(furnace) => furnace.clone(),
+                    None => continue,
+                };
+                let items_changed = entity.tick_furnace(&self.recipes);
+                let after = entity.furnace.clone().unwrap_or_default();
+                if (before.burn_time > 0) != (after.burn_time > 0) {
+                    lit.push((position, after.burn_time > 0));
+                }
+                if items_changed || before != after {
+                    furnaces.push((world_name.clone(), position, items_changed));
+                }
+            }
+            for ((x, y, z), lit) in lit {
+                let state = container::furnace_lit_state(world.get_block(x, y, z), lit);
+                world.set_block(x, y, z, state);
+                changes.entry(world_name.clone()).or_default().push(BlockChange { x, y, z, state });
+            }
+        }
+        if furnaces.is_empty() && changes.is_empty() {
+            return;
+        }
+
+        for client in self.clients.lock().await.values() {
+            let mut client = client.lock().await;
+            if let Some(changes) = changes.get(&client.player.position.world) {
+                let _ = client.send_block_changes(changes).await;
+            }
+            let open = match &client.container {
+                Some(open) if open.kind == ContainerKind::Furnace => open.clone(),
+                _ => continue,
+            };
+            for (world_name, position, items_changed) in &furnaces {
+                if !open.shows(world_name, *position) {
+                    continue;
+                }
+                let world = match self.worlds.get_mut(world_name) {
+                    Some(world) => world,
+                    None => continue,
+                };
+                let furnace = world.get_block_entity(position.0, position.1, position.2).and_then(|entity| entity.furnace.clone());
+                if let Some(furnace) = furnace {
+                    let _ = client.send_furnace_progress(&furnace).await;
+                }
+                if *items_changed {
+                    let _ = client.send_container(open.slots(world)).await;
+                }
+            }
+        }
+    }
+
+    /// Sends block changes to every player in the world that has the affected chunks loaded.
+    async fn send_block_changes(&self, world: &str, changes: &[BlockChange], sender: &mut ServerClient) {
+        if changes.is_empty() {
+            return;
+        }
+        for (key, other) in self.clients.lock().await.iter() {
+            if key.1 == sender.uuid {
+                continue;
+            }
+            let mut other = other.lock().await;
+            if other.player.position.world == world {
+                let _ = other.send_block_changes(changes).await;
+            }
+        }
+        if sender.player.position.world == world {
+            let _ = sender.send_block_changes(changes).await;
+        }
+    }
+
+    pub fn set_movement_config(&mut self, config: MovementConfig) {
+        self.movement_config = config;
+    }
+
+    /// Sets the furthest, in chunks, clients may see, within the vanilla bounds.
+    pub fn set_view_distance(&mut self, distance: i32) {
+        self.view_distance = distance.clamp(MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE);
+    }
+
+    /// Loads `<locale>.json` files of translations the server fills in before messages are sent,
+    /// for keys clients do not know. Only players joining afterwards receive them.
+    pub fn load_translations(&mut self, path: &str) -> Result<usize> {
+        Arc::make_mut(&mut self.translations).load_dir(path)
+    }
+
+    /// Sets how far apart, in blocks, two players can be while still seeing each other.
+    pub fn set_tracking_range(&mut self, range: f64) {
+        self.tracking_range = range;
+    }
+
+    pub async fn set_tab_list_header_footer(&mut self, header: Chat, footer: Chat) {
+        self.tab_header = header.clone();
+        self.tab_footer = footer.clone();
+        for client in self.clients.lock().await.values() {
+            let _ = client.lock().await.connection.write_packet(tablist::header_and_footer(header.clone(), footer.clone())).await;
+        }
+    }
+
+    /// Changes the name shown for a player in everyone's tab list, `None` restores their username.
+    pub async fn set_display_name(&self, client: &mut ServerClient, display_name: Option<Chat>) {
+        client.player.display_name = display_name.clone();
+        self.broadcast_packet(tablist::update_display_name(client.uuid, display_name), client).await;
+    }
+
+    /// Sends a player's current gamemode to everyone's tab list.
+    pub async fn update_tab_gamemode(&self, client: &mut ServerClient) {
+        let packet = tablist::update_gamemode(client.uuid, client.player.gamemode.clone());
+        self.broadcast_packet(packet, client).await;
+    }
+
+    /// Sends a packet to every connected client. `sender` is the client whose lock is already
+    /// held by the caller.
+    async fn broadcast_packet(&self, packet: Packet, sender: &mut ServerClient) {
+        for (key, client) in self.clients.lock().await.iter() {
+            if key.1 != sender.uuid {
+                let _ = client.lock().await.connection.write_packet(packet.clone()).await;
+            }
+        }
+        let _ = sender.connection.write_packet(packet).await;
+    }
+
+    /// Fills a newly joined player's tab list and adds them to everyone else's.
+    async fn add_to_tab_list(&self, client: &mut ServerClient) {
+        let clients = self.clients.lock().await;
+        let mut others = vec![];
+        for (key, other) in clients.iter() {
+            if key.1 == client.uuid {
+                continue;
+            }
+            let mut other = other.lock().await;
+            let _ = other.connection.write_packet(tablist::add_players(&[&client.player])).await;
+            others.push(other);
         }
+
+        let mut players: Vec<&Player> = others.iter().map(|other| &other.player).collect();
+        players.push(&client.player);
+        let packet = tablist::add_players(&players);
+        let _ = client.connection.write_packet(packet).await;
+        let _ = client.connection.write_packet(tablist::header_and_footer(self.tab_header.clone(), self.tab_footer.clone())).await;
     }
 
     pub async fn start(self_mutex: Arc<Mutex<Self>>, mut receiver: Receiver<()>, runtime: Arc<Mutex<Runtime>>) -> Result<()> {
         let mut listener: TcpListener;
         let connections: ConnectedClients;
+        let mut shutdown: watch::Receiver<bool>;
+        let rcon: Option<(SocketAddr, String)>;
+        let entity_ids: Arc<EntityIdAllocator>;
         {
-            let self_lock = self_mutex.lock().await;
+            let mut self_lock = self_mutex.lock().await;
             let bind = TcpListener::bind(self_lock.address.clone()).await;
             if let Ok(bind) = bind {
                 listener = bind;
@@ -71,17 +759,70 @@ impl Server {
                 println!("{}", bind.err().unwrap());
                 return Err(anyhow!("Failed to bind to {}", self_lock.address.clone()));
             }
+            self_lock.reload_lists()?;
+            if Path::new(item::REGISTRY_PATH).exists() {
+                println!("Loaded {} items from {}", item::load_registry(item::REGISTRY_PATH)?, item::REGISTRY_PATH);
+            } else {
+                println!("{} not found, only {} built-in items are known", item::REGISTRY_PATH, item::ITEMS.len());
+            }
+            if Path::new(recipe::DATA_PACK_PATH).exists() {
+                self_lock.recipes = RecipeManager::load_data_pack(recipe::DATA_PACK_PATH)?;
+            } else {
+                println!("{} not found, only {} built-in recipes are known", recipe::DATA_PACK_PATH, self_lock.recipes.recipes().len());
+            }
+            shutdown = self_lock.shutdown.subscribe();
+            rcon = self_lock.rcon.clone();
+            entity_ids = self_lock.entity_ids.clone();
+        }
+
+        let console_arc = self_mutex.clone();
+        let console_loop = async move {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command = line.trim();
+                if !command.is_empty() {
+                    console_arc.lock().await.execute_console_command(command.strip_prefix('/').unwrap_or(command)).await;
+                }
+            }
+        };
+        runtime.lock().await.spawn(console_loop);
+
+        if let Some((address, password)) = rcon {
+            let rcon_loop = rcon::listen(self_mutex.clone(), address, password, shutdown.clone(), runtime.clone());
+            runtime.lock().await.spawn(async move {
+                if let Err(e) = rcon_loop.await {
+                    println!("RCON stopped: {}", e);
+                }
+            });
         }
 
+        let tick_arc = self_mutex.clone();
+        let mut tick_shutdown = shutdown.clone();
+        let tick_loop = async move {
+            let mut interval = tokio::time::interval(TICK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => tick_arc.lock().await.tick().await,
+                    _ = tick_shutdown.changed() => break,
+                }
+            }
+        };
+        runtime.lock().await.spawn(tick_loop);
+
         loop {
             if let Err(err) = receiver.try_recv() {
                 if err == tokio::sync::mpsc::error::TryRecvError::Disconnected {
                     break;
                 }
 
-                if let Ok((socket, address)) = listener.accept().await {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = shutdown.changed() => break,
+                };
+                if let Ok((socket, address)) = accepted {
                     let self_join_arc = self_mutex.clone();
                     let connections = connections.clone();
+                    let entity_ids = entity_ids.clone();
                     let runtime_arc = runtime.clone();
 
                     let join = async move {
@@ -101,7 +842,7 @@ impl Server {
                                         >= self_lock.status.players.max.try_into().unwrap()
                                     {
                                         let _kick = Self::login_kick(
-                                            client,
+                                            &mut client,
                                             Chat::from_text(
                                                 "Server is full, wait for another player to leave.",
                                             ),
@@ -109,76 +850,121 @@ impl Server {
                                             .await;
                                         return;
                                     }
-                                    login = self_lock.handle_login(&mut client, 256).await;
+                                    login = self_lock.handle_login(&mut client, 256, address).await;
                                 }
                                 if let Ok(login) = login {
-                                    let mut entity_id = i32::MIN;
-                                    {
-                                        let mut self_lock = self_join_arc.lock().await;
-                                        while self_lock.entity_ids.lock().await.contains(&entity_id) {
-                                            entity_id = entity_id + 1;
-                                        }
-                                        self_lock.entity_ids.lock().await.insert(entity_id);
-                                    }
+                                    let entity_id = entity_ids.allocate();
+                                    let default_gamemode = self_join_arc.lock().await.default_gamemode.clone();
+                                    let mut player = Player::new(
+                                        login.0.clone(),
+                                        login.1.clone(),
+                                        entity_id,
+                                        default_gamemode,
+                                    );
+                                    player.properties = login.2;
+                                    let data = self_join_arc.lock().await.player_data.load(login.1).unwrap_or_else(|e| {
+                                        println!("Failed to load player data of {}: {}", login.0, e);
+                                        PlayerData::default()
+                                    });
+                                    let (view_distance, translations) = {
+                                        let self_lock = self_join_arc.lock().await;
+                                        (self_lock.view_distance, self_lock.translations.clone())
+                                    };
                                     let server_client = Arc::new(Mutex::new(ServerClient {
                                         name: login.0.clone(),
                                         uuid: login.1.clone(),
+                                        address,
                                         entity_id,
-                                        player: Player::new(
-                                            login.0.clone(),
-                                            login.1.clone(),
-                                            entity_id,
-                                        ),
+                                        player,
                                         connection: client,
-                                        view_distance: 10,
+                                        view_distance,
+                                        locale: text::DEFAULT_LOCALE.to_string(),
+                                        translations,
+                                        loaded_chunks: HashSet::new(),
+                                        window: WindowState::default(),
+                                        container: None,
+                                        last_window_id: 0,
+                                        tracker: EntityTracker::new(),
+                                        keep_alive: None,
+                                        disconnected: false,
+                                        chat: ChatState::new(),
+                                        chat_mode: ClientChatMode::Enabled,
+                                        data,
+                                        channel: chat::GLOBAL_CHANNEL.to_string(),
+                                        reply_to: None,
                                     }));
                                     {
-                                        for player in connections.lock().await.keys() {
-                                            if player.0 == login.0 || player.1 == login.1 {
-                                                let _kick = server_client.lock().await.kick(Chat::from_text("Someone with the same name or UUID as you is already connected.")).await;
-                                                return;
-                                            }
-                                        }
-                                    }
-                                    {
-                                        let self_lock = self_join_arc.lock().await;
-                                        if let Err(_) = server_client.lock().await.join_world(self_lock.hardcore, self_lock.status.players.max).await {
+                                        let mut self_lock = self_join_arc.lock().await;
+                                        let server = &mut *self_lock;
+                                        let world_names = server.worlds.names();
+                                        let world = server.worlds.default_world_mut();
+                                        let biome_id = server.dimension_codec.biomes.id_of(&world.biome).unwrap_or(0);
+                                        let mut client_lock = server_client.lock().await;
+                                        if let Err(_) = client_lock.join_world(server.hardcore, server.status.players.max, &server.dimension_codec, world_names, world, biome_id, server.game_rules.spawn_radius).await {
+                                            server.entity_ids.release(entity_id);
+                                            server.logging_in.remove(&(login.0.clone(), login.1));
                                             return;
                                         }
+                                        let mut packets = vec![server.recipes.declare_recipes(), server.recipes.unlock_recipes()];
+                                        packets.push(world.time_packet(server.game_rules.do_daylight_cycle));
+                                        packets.extend(world.weather_packets());
+                                        let _ = client_lock.send_packets(packets).await;
+                                        server.send_permissions(&mut client_lock).await;
                                     }
                                     let server_client_arc = server_client.clone();
                                     let self_loop_arc = self_join_arc.clone();
                                     let packet_loop = async move {
-                                        let client_arc = server_client_arc.clone();
+                                        let mut connection = server_client_arc.lock().await.connection.clone();
                                         let server_arc = self_loop_arc.clone();
                                         loop {
                                             let packet_read: Result<Option<Packet>>;
                                             {
-                                                packet_read = client_arc
+                                                packet_read = connection.read_next_packet().await;
+                                            }
+                                            if let Ok(Some(packet)) = packet_read {
+                                                let mut server = server_arc.lock().await;
+                                                let sender = server_client_arc.lock().await;
+                                                // A session replaced by a newer login is ignored until its connection closes.
+                                                if sender.disconnected {
+                                                    break;
+                                                }
+                                                server.handle_packet(packet, sender).await;
+                                            } else {
+                                                server_arc
                                                     .lock()
                                                     .await
-                                                    .connection
-                                                    .read_next_packet()
+                                                    .handle_disconnect(&mut *server_client_arc.lock().await)
                                                     .await;
+                                                break;
                                             }
-                                            if let Ok(packet_ok) = packet_read {
-                                                if let Some(packet) = packet_ok {
-                                                    server_arc
-                                                        .lock()
-                                                        .await
-                                                        .handle_packet(
-                                                            packet,
-                                                            server_client_arc.clone().lock().await,
-                                                        ).await;
-                                                }
-                                            };
                                         }
                                     };
                                     runtime_arc.lock().await.spawn(packet_loop);
-                                    connections
-                                        .lock()
-                                        .await
-                                        .insert(Arc::new((login.0, login.1)), server_client);
+                                    let keep_alive_arc = server_client.clone();
+                                    let keep_alive_loop = async move {
+                                        loop {
+                                            tokio::time::sleep(KEEP_ALIVE_INTERVAL).await;
+                                            let mut client = keep_alive_arc.lock().await;
+                                            if client.disconnected {
+                                                break;
+                                            }
+                                            if let Err(_) = client.keep_alive().await {
+                                                break;
+                                            }
+                                        }
+                                    };
+                                    runtime_arc.lock().await.spawn(keep_alive_loop);
+                                    {
+                                        let mut self_lock = self_join_arc.lock().await;
+                                        connections
+                                            .lock()
+                                            .await
+                                            .insert(Arc::new((login.0.clone(), login.1)), server_client.clone());
+                                        self_lock.logging_in.remove(&(login.0, login.1));
+                                        let mut client_lock = server_client.lock().await;
+                                        self_lock.add_to_tab_list(&mut client_lock).await;
+                                        self_lock.update_tracking(&mut client_lock).await;
+                                    }
                                     println!("{} successfully logged in.", address.to_string());
                                 } else {
                                     println!(
@@ -219,7 +1005,7 @@ impl Server {
         Ok(())
     }
 
-    pub async fn handle_login(&mut self, client: &mut Client, compression_threhold: i32) -> Result<(String, UUID4)> {
+    pub async fn handle_login(&mut self, client: &mut Client, compression_threhold: i32, address: SocketAddr) -> Result<(String, UUID4, Vec<ProfileProperty>)> {
         use Packet::{LoginEncryptionRequest, LoginEncryptionResponse, LoginSetCompression, LoginStart, LoginSuccess};
 
         let second = &mut client.read_next_packet().await;
@@ -227,7 +1013,7 @@ impl Server {
             let response = LoginSetCompressionSpec {
                 threshold: mcproto_rs::types::VarInt::from(compression_threhold),
             };
-            let mut result = (body.name.clone(), UUID4::random());
+            let mut result = (body.name.clone(), UUID4::random(), vec![]);
 
             if self.online {
                 let server_id = "                ".to_string();
@@ -282,6 +1068,33 @@ impl Server {
                 }
             };
 
+            if let Some(reason) = self.check_access(&result.0, result.1, address) {
+                Self::login_kick(client, Chat::from_text(&reason)).await?;
+                return Err(anyhow!("{} was refused: {}", result.0, reason.replace('\n', " ")));
+            }
+
+            // A player still joining can not be kicked yet, so whoever logs in second is refused.
+            if self.logging_in.iter().any(|(name, uuid)| *name == result.0 || *uuid == result.1) {
+                Self::login_kick(client, Chat::from_text("Someone with the same name or UUID as you is already connected.")).await?;
+                return Err(anyhow!("{} is already logging in", result.0));
+            }
+            let existing = self.clients.lock().await.iter()
+                .find(|(key, _)| key.0 == result.0 || key.1 == result.1)
+                .map(|(_, client)| client.clone());
+            if let Some(existing) = existing {
+                match self.duplicate_login_policy {
+                    DuplicateLoginPolicy::KickExisting => {
+                        let mut existing = existing.lock().await;
+                        let _ = existing.kick(text::translate("multiplayer.disconnect.duplicate_login", vec![]).build()).await;
+                        self.handle_disconnect(&mut existing).await;
+                    }
+                    DuplicateLoginPolicy::RejectNew => {
+                        Self::login_kick(client, Chat::from_text("Someone with the same name or UUID as you is already connected.")).await?;
+                        return Err(anyhow!("{} is already connected", result.0));
+                    }
+                }
+            }
+
             if let Err(error) = client.write_packet(LoginSetCompression(response)).await {
                 return Err(error);
             } else {
@@ -298,14 +1111,15 @@ impl Server {
 
             client.set_state(Play).await;
 
-
+            // Reserved under the same lock as the check above, until the player is added to `clients`.
+            self.logging_in.insert((result.0.clone(), result.1));
             return Ok(result);
         } else {
             return Err(anyhow!("Client did not follow up with Login start"));
         }
     }
 
-    pub async fn login_kick(mut client: Client, message: Chat) -> Result<()> {
+    pub async fn login_kick(client: &mut Client, message: Chat) -> Result<()> {
         use mcproto_rs::v1_16_3::LoginDisconnectSpec;
         use Packet::LoginDisconnect;
 
@@ -313,34 +1127,832 @@ impl Server {
         client.write_packet(LoginDisconnect(spec)).await
     }
 
-    #[allow(unused_must_use)]
-    pub async fn broadcast_chat(&mut self, message: Chat) {
+    /// Sends a chat message to everyone reading the channel who is not ignoring the sender. None
+    /// of the players may be locked by the caller.
+    pub async fn broadcast_chat(&self, channel: &str, sender: Option<&ChatMember>, message: Chat) {
+        println!("{}", text::plain_text(&message));
+        let channel = self.channels.get_or_global(channel);
         for player in self.clients.clone().lock().await.values() {
-            player.clone().lock().await.send_message(
+            let mut player = player.lock().await;
+            let ignored = sender.map_or(false, |sender| player.data.is_ignoring(sender.uuid));
+            if ignored || !channel.filter.receives(self, sender, &player.chat_member()) {
+                continue;
+            }
+            let _ = player.send_message(
                 message.clone(),
                 ChatPosition::ChatBox,
-                None,
-            );
+                sender.map(|sender| sender.uuid),
+            ).await;
         }
     }
 
-    async fn handle_packet(&mut self, packet: Packet, sender: MutexGuard<'_, ServerClient>) {
-        match packet {
-            Packet::PlayClientChatMessage(body) => {
-                self.broadcast_chat(Chat::from_traditional(
-                    &("<".to_owned() + sender.name.as_str() + "> " + body.message.as_str()),
-                    true,
-                ))
-                    .await;
+    /// Validates and rate limits a chat message or command from a player, then runs or delivers
+    /// it. The sender is unlocked before anything is sent to other players.
+    async fn handle_chat(&mut self, mut sender: MutexGuard<'_, ServerClient>, message: &str) {
+        let message = match chat::validate_message(message) {
+            Ok(message) => message,
+            Err(_) => {
+                if sender.chat.record_invalid() {
+                    let _ = sender.kick(text::translate("multiplayer.disconnect.illegal_characters", vec![]).build()).await;
+                } else {
+                    let error = text::text("Invalid chat message").color(ColorCode::Red).build();
+                    let _ = sender.send_message(error, ChatPosition::SystemMessage, None).await;
+                }
+                return;
             }
-            _ => {}
+        };
+        if !self.has_permission(&sender.name, Some(sender.uuid), SPAM_BYPASS_PERMISSION) && !sender.chat.take_token() {
+            let _ = sender.kick(text::translate("disconnect.spam", vec![]).build()).await;
+            return;
+        }
+
+        if let Some(command) = message.strip_prefix('/') {
+            let (name, uuid) = (sender.name.clone(), sender.uuid);
+            drop(sender);
+            println!("{} issued server command: {}", name, message);
+            if let Some(client) = self.client(uuid).await {
+                let mut source = CommandSource::Player { client, name, uuid };
+                self.execute_command(&mut source, command).await;
+            }
+            return;
+        }
+
+        if let ClientChatMode::Hidden = sender.chat_mode {
+            let error = text::translate("chat.disabled.options", vec![]).color(ColorCode::Red).build();
+            let _ = sender.send_message(error, ChatPosition::SystemMessage, None).await;
+            return;
+        }
+        let member = sender.chat_member();
+        // Players who lost access to their channel, or whose channel was removed, are moved back
+        // to the global one.
+        if !self.channels.get_or_global(&sender.channel).filter.can_join(self, &member) || self.channels.get(&sender.channel).is_none() {
+            sender.channel = chat::GLOBAL_CHANNEL.to_string();
         }
+        let channel = self.channels.get_or_global(&sender.channel);
+        let formatted = self.format_chat(&sender, channel, &message);
+        let channel = channel.name.clone();
+        drop(sender);
+        self.broadcast_chat(&channel, Some(&member), formatted).await;
     }
 
-    async fn handle_status(&mut self, mut client: Client) -> anyhow::Result<()> {
-        use Packet::{StatusPing, StatusPong, StatusRequest};
-        use mcproto_rs::status::StatusPlayerSampleSpec;
-        use proto::{StatusPongSpec};
+    /// A chat message from a player, rendered with the channel's template or the chat template.
+    /// Only players with `endstone.chat.color` may use `&` codes.
+    fn format_chat(&self, sender: &ServerClient, channel: &Channel, message: &str) -> Chat {
+        let message = match self.has_permission(&sender.name, Some(sender.uuid), CHAT_COLOR_PERMISSION) {
+            true => text::legacy(message, true).build(),
+            false => Chat::from_text(message),
+        };
+        let template = channel.template.as_ref().unwrap_or(&self.chat_templates.chat);
+        text::render_template(template, &[("name", sender.name_component()), ("message", message)])
+    }
+
+    /// The online player with the given UUID.
+    pub async fn client(&self, uuid: UUID4) -> Option<Arc<Mutex<ServerClient>>> {
+        self.clients.lock().await.iter().find(|(key, _)| key.1 == uuid).map(|(_, client)| client.clone())
+    }
+
+    /// The online player with the given name, ignoring case.
+    pub async fn client_by_name(&self, name: &str) -> Option<Arc<Mutex<ServerClient>>> {
+        self.clients.lock().await.iter().find(|(key, _)| key.0.eq_ignore_ascii_case(name)).map(|(_, client)| client.clone())
+    }
+
+    /// Rereads the player lists and permissions from disk, for when they were edited by hand.
+    pub fn reload_lists(&mut self) -> Result<()> {
+        self.ops.load()?;
+        self.whitelist.load()?;
+        self.banned_players.load()?;
+        self.banned_ips.load()?;
+        self.permissions.load()?;
+        Ok(())
+    }
+
+    /// Writes the player lists to disk. Worlds only live in memory, so there is nothing else to
+    /// save yet.
+    pub fn save_all(&self) -> Result<()> {
+        self.ops.save()?;
+        self.permissions.save()?;
+        self.banned_players.save()?;
+        self.banned_ips.save()?;
+        self.whitelist.save()?;
+        Ok(())
+    }
+
+    /// Saves, disconnects everyone and stops accepting connections. None of the players may be
+    /// locked by the caller.
+    pub async fn stop(&mut self) {
+        println!("Stopping the server");
+        if let Err(e) = self.save_all() {
+            println!("Failed to save: {}", e);
+        }
+        for client in self.clients.lock().await.values() {
+            let _ = client.lock().await.kick(text::translate("multiplayer.disconnect.server_shutdown", vec![]).build()).await;
+        }
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Whether a player may join, or the reason they are turned away.
+    fn check_access(&self, name: &str, uuid: UUID4, address: SocketAddr) -> Option<String> {
+        if let Some(ban) = self.banned_players.get(name, Some(uuid)) {
+            return Some(ban.kick_message());
+        }
+        if let Some(ban) = self.banned_ips.get(&address.ip().to_string(), None) {
+            return Some(ban.kick_message());
+        }
+        if self.whitelist_enabled && !self.whitelist.contains(name, Some(uuid)) && !self.ops.contains(name, Some(uuid)) {
+            return Some("You are not white-listed on this server!".to_string());
+        }
+        None
+    }
+
+    async fn handle_packet(&mut self, packet: Packet, mut sender: MutexGuard<'_, ServerClient>) {
+        match packet {
+            Packet::PlayClientChatMessage(body) => {
+                self.handle_chat(sender, &body.message).await;
+            }
+            Packet::PlayClientSettings(body) => {
+                self.handle_client_settings(&mut sender, body).await;
+            }
+            Packet::PlayClientTabComplete(body) => {
+                self.handle_tab_complete(&mut sender, body).await;
+            }
+            Packet::PlayClientKeepAlive(body) => {
+                if let Some((id, sent)) = sender.keep_alive {
+                    if id == body.id {
+                        sender.keep_alive = None;
+                        sender.player.ping = sent.elapsed().as_millis() as i32;
+                        let packet = tablist::update_latency(sender.uuid, sender.player.ping);
+                        self.broadcast_packet(packet, &mut sender).await;
+                    }
+                }
+            }
+            Packet::PlayTeleportConfirm(body) => {
+                let movement = &mut sender.player.movement;
+                if movement.pending_teleport == Some(body.teleport_id.0) {
+                    movement.pending_teleport = None;
+                    movement.last_update = std::time::Instant::now();
+                }
+            }
+            Packet::PlayPlayerPosition(body) => {
+                self.handle_movement(&mut sender, Some(body.feet_position), None, body.on_ground).await;
+            }
+            Packet::PlayClientPlayerPositionAndRotation(body) => {
+                let location = body.feet_location;
+                self.handle_movement(&mut sender, Some(location.position), Some(location.rotation), body.on_ground).await;
+            }
+            Packet::PlayPlayerRotation(body) => {
+                self.handle_movement(&mut sender, None, Some(body.rotation), body.on_ground).await;
+            }
+            Packet::PlayPlayerMovement(body) => {
+                self.handle_movement(&mut sender, None, None, body.on_ground).await;
+            }
+            Packet::PlayClientPlayerAbilities(body) => {
+                sender.player.flying = body.flags.is_flying() && sender.player.may_fly();
+            }
+            Packet::PlayClientHeldItemChange(body) => {
+                if body.slot >= 0 && body.slot < 9 {
+                    sender.player.selected_slot = body.slot as u8;
+                } else {
+                    let slot = sender.player.selected_slot as i8;
+                    let _ = sender.connection.write_packet(Packet::PlayServerHeldItemChange(PlayServerHeldItemChangeSpec { slot })).await;
+                }
+            }
+            Packet::PlayClickWindow(body) => {
+                self.handle_click_window(&mut sender, body).await;
+            }
+            Packet::PlayClientWindowConfirmation(body) => {
+                if sender.window.pending_confirmation == Some(body.action_num) {
+                    sender.window.pending_confirmation = None;
+                }
+            }
+            Packet::PlayCreativeInventoryAction(body) => {
+                self.handle_creative_inventory_action(&mut sender, body).await;
+            }
+            Packet::PlayClientCloseWindow(body) => {
+                let open_container = sender.container.as_ref().map(|container| container.id);
+                if body.window_id == inventory::PLAYER_WINDOW_ID || Some(body.window_id) == open_container {
+                    sender.return_window_items();
+                    sender.container = None;
+                }
+            }
+            Packet::PlayClientStatus(body) => {
+                if body.action == ClientStatusAction::PerformRespawn && sender.player.health.is_dead() {
+                    if let Err(e) = self.respawn(&mut sender).await {
+                        println!("{} could not respawn: {}", sender.name, e);
+                    }
+                }
+            }
+            Packet::PlayPlayerDigging(body) => {
+                self.handle_digging(&mut sender, body).await;
+            }
+            Packet::PlayBlockPlacement(body) => {
+                self.handle_block_placement(&mut sender, body).await;
+            }
+            Packet::PlayEntityAction(body) => {
+                match body.action {
+                    EntityActionKind::StartSprinting => sender.player.sprinting = true,
+                    EntityActionKind::StopSprinting => sender.player.sprinting = false,
+                    EntityActionKind::StartSneaking => sender.player.sneaking = true,
+                    EntityActionKind::StopSneaking => sender.player.sneaking = false,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies a client's settings: its view distance up to the server's, the language and chat
+    /// mode messages are sent with, and the skin parts and main hand other players see.
+    async fn handle_client_settings(&mut self, sender: &mut ServerClient, body: PlayClientSettingsSpec) {
+        sender.locale = body.locale;
+        sender.chat_mode = body.chat_mode;
+
+        let view_distance = (body.view_distance as i32).clamp(MIN_VIEW_DISTANCE, self.view_distance);
+        if view_distance != sender.view_distance {
+            sender.view_distance = view_distance;
+            let biome_id = self.worlds.get(&sender.player.position.world)
+                .and_then(|world| self.dimension_codec.biomes.id_of(&world.biome))
+                .unwrap_or(0);
+            if let Some(world) = self.worlds.get_mut(&sender.player.position.world) {
+                let _ = sender.update_chunks(world, biome_id).await;
+            }
+        }
+
+        if sender.player.skin_parts != body.displayed_skin_parts || sender.player.main_hand != body.main_hand {
+            sender.player.skin_parts = body.displayed_skin_parts;
+            sender.player.main_hand = body.main_hand;
+            let packet = sender.player.metadata();
+            // Only players that have the sender spawned can apply metadata to them.
+            for (key, client) in self.clients.lock().await.iter() {
+                if key.1 == sender.uuid {
+                    continue;
+                }
+                let mut client = client.lock().await;
+                if client.tracker.is_tracking(sender.entity_id) {
+                    let _ = client.connection.write_packet(packet.clone()).await;
+                }
+            }
+            let _ = sender.connection.write_packet(packet).await;
+        }
+    }
+
+    /// Handles a player starting, cancelling or finishing digging a block. Survival players have
+    /// to dig for roughly as long as the block takes to break with the tool they are holding.
+    async fn handle_digging(&mut self, sender: &mut ServerClient, body: PlayPlayerDiggingSpec) {
+        let status = match body.status {
+            PlayerDiggingStatus::Started => DiggingStatus::Started,
+            PlayerDiggingStatus::Cancelled => DiggingStatus::Cancelled,
+            PlayerDiggingStatus::Finished => DiggingStatus::Finished,
+            PlayerDiggingStatus::DropItem | PlayerDiggingStatus::DropStack => {
+                // Item entities do not exist yet, so the drop is refused and the client, which
+                // already removed the item, is told it is still there.
+                let slot = sender.player.held_slot(&Hand::MainHand);
+                let _ = sender.send_slot(slot as i16).await;
+                return;
+            }
+            PlayerDiggingStatus::SwapItemInHand => {
+                let main_hand = sender.player.held_slot(&Hand::MainHand);
+                let off_hand = sender.player.held_slot(&Hand::OffHand);
+                sender.player.inventory.items.swap(main_hand, off_hand);
+                // The client does not predict the swap, so both slots are sent.
+                let _ = sender.send_slot(main_hand as i16).await;
+                let _ = sender.send_slot(off_hand as i16).await;
+                return;
+            }
+            _ => return,
+        };
+        let (x, y, z) = (body.location.x, body.location.y as i32, body.location.z);
+        let world_name = sender.player.position.world.clone();
+        let can_build = can_build(sender, x, y, z);
+        let world = match self.worlds.get_mut(&world_name) {
+            Some(world) => world,
+            None => return,
+        };
+        let state = world.get_block(x, y, z);
+
+        // Whether the block breaks, and whether the client's view of the dig was accepted.
+        let (breaks, successful) = match status {
+            _ if !can_build => (false, false),
+            DiggingStatus::Started if sender.player.gamemode == GameMode::Creative => (true, true),
+            DiggingStatus::Started => {
+                let held = sender.player.held_item(&Hand::MainHand);
+                let tool = held.as_ref().and_then(|item| digging::get_tool(item.item_id.0));
+                let efficiency = held.as_ref().map(|item| item::enchantment_level(item, "efficiency") as i32).unwrap_or(0);
+                let haste = sender.player.effect_level(&EntityEffectKind::Haste);
+                let ticks = block::get_block(state).and_then(|block| digging::break_ticks(block, tool.as_ref(), efficiency, haste, sender.player.position.on_ground));
+                match ticks {
+                    Some(0) => (true, true),
+                    Some(ticks) => {
+                        sender.player.digging = Some(DigState::new(x, y, z, ticks));
+                        (false, true)
+                    }
+                    None => (false, false),
+                }
+            }
+            DiggingStatus::Cancelled => {
+                sender.player.digging = None;
+                (false, true)
+            }
+            DiggingStatus::Finished => match sender.player.digging.take() {
+                Some(dig) if dig.x == x && dig.y == y && dig.z == z && dig.is_done() => (true, true),
+                _ => (false, false),
+            },
+        };
+
+        // Item entities do not exist yet, so containers can only be broken once emptied.
+        let (breaks, successful) = if breaks && container::holds_items(world, x, y, z) {
+            sender.player.digging = None;
+            sender.send_feedback("Empty this container before breaking it").await;
+            (false, false)
+        } else {
+            (breaks, successful)
+        };
+
+        let mut changes = vec![];
+        let block = if breaks {
+            changes.extend(container::disconnect_chest(world, x, y, z));
+            if let Some(((dx, dy, dz), other_state)) = placement::bed_other_half(state) {
+                let (other_x, other_y, other_z) = (x + dx, y + dy, z + dz);
+                if world.get_block(other_x, other_y, other_z) == other_state {
+                    world.set_block(other_x, other_y, other_z, block::AIR);
+                    changes.push(BlockChange { x: other_x, y: other_y, z: other_z, state: block::AIR });
+                }
+            }
+            world.set_block(x, y, z, block::AIR);
+            changes.push(BlockChange { x, y, z, state: block::AIR });
+            if sender.player.gamemode != GameMode::Creative {
+                damage_held_tool(sender);
+                sender.player.health.add_exhaustion(health::BREAK_EXHAUSTION);
+            }
+            block::AIR
+        } else {
+            state
+        };
+
+        let _ = sender.connection.write_packet(Packet::PlayAcknowledgePlayerDigging(PlayAcknowledgePlayerDiggingSpec {
+            location: body.location,
+            block: VarInt::from(block as i32),
+            status,
+            successful,
+        })).await;
+
+        if breaks {
+            self.close_container_viewers(&world_name, (x, y, z), sender).await;
+            self.send_block_changes(&world_name, &changes, sender).await;
+        }
+    }
+
+    /// Applies a click in the player's inventory window. Clicks the server disagrees with are
+    /// rejected and the whole window is resent.
+    async fn handle_click_window(&mut self, sender: &mut ServerClient, body: PlayClickWindowSpec) {
+        if sender.window.pending_confirmation.is_some() {
+            return;
+        }
+        if body.window_id != inventory::PLAYER_WINDOW_ID {
+            self.handle_container_click(sender, body).await;
+            return;
+        }
+
+        let before = if body.slot >= 0 {
+            sender.player.inventory.get(body.slot as usize)
+        } else {
+            None
+        };
+        let click = Click {
+            slot: body.slot,
+            button: body.button,
+            mode: body.mode.clone(),
+        };
+        let creative = sender.player.gamemode == GameMode::Creative;
+        let result = recipe::crafting_click(&self.recipes, &recipe::PLAYER_CRAFTING, &PlayerWindow, &mut sender.player.inventory.items, &mut sender.window, &click, creative);
+
+        // The client sends what it thinks was in the clicked slot, which only means something for
+        // clicks that pick up or move that slot.
+        let compares = body.mode == InventoryOperationMode::MouseClick || body.mode == InventoryOperationMode::ShiftClick;
+        let accepted = match result {
+            Ok(()) => !compares || before == body.clicked_item,
+            Err(_) => false,
+        };
+
+        let _ = sender.connection.write_packet(Packet::PlayServerWindowConfirmation(PlayServerWindowConfirmationSpec {
+            window_id: body.window_id,
+            action_number: body.action_number,
+            accepted,
+        })).await;
+        if !accepted || body.slot == player::CRAFTING_OUTPUT_SLOT as i16 {
+            if !accepted {
+                sender.window.pending_confirmation = Some(body.action_number);
+            }
+            let _ = sender.send_inventory().await;
+        } else {
+            let _ = sender.send_slot(player::CRAFTING_OUTPUT_SLOT as i16).await;
+        }
+    }
+
+    /// Applies a click in an open container window, then shows the new contents to everyone else
+    /// looking into the same blocks.
+    async fn handle_container_click(&mut self, sender: &mut ServerClient, body: PlayClickWindowSpec) {
+        let mut open = match &sender.container {
+            Some(open) if open.id == body.window_id => open.clone(),
+            _ => return,
+        };
+        let world = match self.worlds.get_mut(&open.world) {
+            Some(world) => world,
+            None => return,
+        };
+
+        let size = open.kind.size();
+        let player_slots = player::MAIN_SLOTS.start..player::HOTBAR_SLOTS.end;
+        let mut slots = open.slots(world);
+        slots.extend_from_slice(&sender.player.inventory.items[player_slots.clone()]);
+        let before = if body.slot >= 0 {
+            slots.get(body.slot as usize).cloned().flatten()
+        } else {
+            None
+        };
+        let click = Click {
+            slot: body.slot,
+            button: body.button,
+            mode: body.mode.clone(),
+        };
+        let creative = sender.player.gamemode == GameMode::Creative;
+        let layout = ContainerWindow { kind: open.kind };
+        let result = if open.kind == ContainerKind::CraftingTable {
+            recipe::crafting_click(&self.recipes, &recipe::TABLE_CRAFTING, &layout, &mut slots, &mut sender.window, &click, creative)
+        } else {
+            inventory::click(&layout, &mut slots, &mut sender.window, &click, creative)
+        };
+        open.set_slots(world, &slots[..size]);
+        sender.player.inventory.items[player_slots].clone_from_slice(&slots[size..]);
+        sender.container = Some(open.clone());
+
+        let compares = body.mode == InventoryOperationMode::MouseClick || body.mode == InventoryOperationMode::ShiftClick;
+        let accepted = match result {
+            Ok(()) => !compares || before == body.clicked_item,
+            Err(_) => false,
+        };
+        let _ = sender.connection.write_packet(Packet::PlayServerWindowConfirmation(PlayServerWindowConfirmationSpec {
+            window_id: body.window_id,
+            action_number: body.action_number,
+            accepted,
+        })).await;
+        // Crafting outputs are only ever computed by the server.
+        let crafted = open.kind == ContainerKind::CraftingTable;
+        if !accepted || (crafted && body.slot == recipe::TABLE_CRAFTING.output as i16) {
+            if !accepted {
+                sender.window.pending_confirmation = Some(body.action_number);
+            }
+            let _ = sender.send_container(slots[..size].to_vec()).await;
+        } else if crafted {
+            let _ = sender.connection.write_packet(Packet::PlaySetSlot(PlaySetSlotSpec {
+                window_id: open.id,
+                slow: recipe::TABLE_CRAFTING.output as i16,
+                slot_data: slots[recipe::TABLE_CRAFTING.output].clone(),
+            })).await;
+        }
+
+        for (key, other) in self.clients.lock().await.iter() {
+            if key.1 == sender.uuid {
+                continue;
+            }
+            let mut other = other.lock().await;
+            let viewing = match &other.container {
+                Some(other_open) if open.positions.iter().any(|position| other_open.shows(&open.world, *position)) => other_open.clone(),
+                _ => continue,
+            };
+            let _ = other.send_container(viewing.slots(world)).await;
+        }
+    }
+
+    /// Sets a player's spawn point when they use a bed or charged respawn anchor, returning whether
+    /// the block was one.
+    async fn use_spawn_block(&mut self, sender: &mut ServerClient, x: i32, y: i32, z: i32) -> bool {
+        let world = match self.worlds.get_mut(&sender.player.position.world) {
+            Some(world) => world,
+            None => return false,
+        };
+        let point = match spawn::spawn_point_at(world, x, y, z) {
+            Some(point) => point,
+            None => return false,
+        };
+        let is_anchor = spawn::anchor_charges(world.get_block(point.x, point.y, point.z)).is_some();
+        let works = if is_anchor { world.dimension_type.respawn_anchor_works } else { world.dimension_type.bed_works };
+        // Vanilla makes beds and anchors explode in the wrong dimension, explosions do not exist yet.
+        let message = if !works {
+            "You can't set your spawn point here"
+        } else if sender.player.spawn_point.as_ref() == Some(&point) {
+            return true;
+        } else {
+            sender.player.spawn_point = Some(point);
+            "Respawn point set"
+        };
+        let _ = sender.send_message(Chat::from_text(message), ChatPosition::SystemMessage, None).await;
+        true
+    }
+
+    /// Opens the container at the given block for a player, returning whether there is one.
+    async fn open_container(&mut self, sender: &mut ServerClient, x: i32, y: i32, z: i32) -> bool {
+        let world_name = sender.player.position.world.clone();
+        let world = match self.worlds.get_mut(&world_name) {
+            Some(world) => world,
+            None => return false,
+        };
+        let (kind, positions) = match container::container_at(world, x, y, z) {
+            Some(container) => container,
+            None => return false,
+        };
+
+        // Opening a window closes whichever one was open before on the client.
+        sender.return_window_items();
+        sender.last_window_id = container::next_window_id(sender.last_window_id);
+        let open = OpenContainer::new(sender.last_window_id, kind, &world_name, positions);
+        let _ = sender.connection.write_packet(Packet::PlayOpenWindow(PlayOpenWindowSpec {
+            id: VarInt::from(open.id as i32),
+            kind: kind.window_type(),
+            title: kind.title(),
+        })).await;
+        let furnace = open.positions.first()
+            .and_then(|&(x, y, z)| world.get_block_entity(x, y, z))
+            .and_then(|entity| entity.furnace.clone());
+        let slots = open.slots(world);
+        sender.container = Some(open);
+        let _ = sender.send_container(slots).await;
+        if let Some(furnace) = furnace {
+            let _ = sender.send_furnace_progress(&furnace).await;
+        }
+        true
+    }
+
+    /// Closes the windows of everyone looking into a block that was just removed.
+    async fn close_container_viewers(&self, world: &str, position: (i32, i32, i32), sender: &mut ServerClient) {
+        for (key, other) in self.clients.lock().await.iter() {
+            if key.1 != sender.uuid {
+                other.lock().await.close_container_at(world, position).await;
+            }
+        }
+        sender.close_container_at(world, position).await;
+    }
+
+    /// Creative players set slots directly. A slot of -1 drops the item instead.
+    async fn handle_creative_inventory_action(&mut self, sender: &mut ServerClient, body: PlayCreativeInventoryActionSpec) {
+        let valid_item = body.clicked_item.as_ref().map(|item| item.item_count > 0 && item.item_count <= inventory::max_stack_size(item)).unwrap_or(true);
+        let slot = body.slot as usize;
+        if sender.player.gamemode != GameMode::Creative || !valid_item || body.slot < 1 || slot >= sender.player.inventory.items.len() {
+            if body.slot >= 0 {
+                let _ = sender.send_slot(body.slot).await;
+            }
+            return;
+        }
+        sender.player.inventory.set(slot, body.clicked_item);
+    }
+
+    /// Places the held block against the clicked face, or lights a fire or portal when holding
+    /// flint and steel. Rejected placements are undone by resending the real blocks.
+    async fn handle_block_placement(&mut self, sender: &mut ServerClient, body: PlayBlockPlacementSpec) {
+        let (x, y, z) = (body.location.x, body.location.y as i32, body.location.z);
+        let world_name = sender.player.position.world.clone();
+        let held = sender.player.held_item(&body.hand);
+        let can_build = can_build(sender, x, y, z);
+
+        // Sneaking with something in hand places it against the container instead of opening it.
+        let opens = body.hand == Hand::MainHand && !(sender.player.sneaking && held.is_some());
+        if in_reach(sender, x, y, z) && opens && (self.use_spawn_block(sender, x, y, z).await || self.open_container(sender, x, y, z).await) {
+            return;
+        }
+
+        let world = match self.worlds.get_mut(&world_name) {
+            Some(world) => world,
+            None => return,
+        };
+        let clicked = world.get_block(x, y, z);
+        let (target_x, target_y, target_z) = if placement::is_replaceable(clicked) {
+            (x, y, z)
+        } else {
+            let (dx, dy, dz) = placement::face_offset(&body.face);
+            (x + dx, y + dy, z + dz)
+        };
+        let target = world.get_block(target_x, target_y, target_z);
+        let in_world = target_y >= 0 && target_y < world::WORLD_HEIGHT;
+
+        let placing = match &held {
+            Some(item) if can_build && in_world && placement::is_replaceable(target) => {
+                if item.item_id.0 == item::FLINT_AND_STEEL {
+                    Some(block::FIRE)
+                } else {
+                    item::get_item(item.item_id.0).and_then(|item| item.get_block()).map(|block| {
+                        placement::placement_state(block, &body.face, body.cursor_position.y, sender.player.position.yaw, sender.player.position.pitch)
+                    })
+                }
+            }
+            _ => None,
+        };
+        let placing = match placing {
+            Some(state) if block::is_solid(state) && self.is_obstructed(&world_name, target_x, target_y, target_z, sender).await => None,
+            placing => placing,
+        };
+
+        let world = match self.worlds.get_mut(&world_name) {
+            Some(world) => world,
+            None => return,
+        };
+        // Beds take up a second block, which has to be free as well.
+        let other_half = placing.and_then(placement::bed_other_half)
+            .map(|((dx, dy, dz), state)| BlockChange { x: target_x + dx, y: target_y + dy, z: target_z + dz, state });
+        let placing = match &other_half {
+            Some(half) if !placement::is_replaceable(world.get_block(half.x, half.y, half.z)) => None,
+            _ => placing,
+        };
+        let changes = match placing {
+            Some(block::FIRE) => match portal::ignite(world, target_x, target_y, target_z) {
+                Some(changes) => changes,
+                None if target == block::AIR => {
+                    world.set_block(target_x, target_y, target_z, block::FIRE);
+                    vec![BlockChange { x: target_x, y: target_y, z: target_z, state: block::FIRE }]
+                }
+                None => vec![],
+            },
+            Some(state) => {
+                world.set_block(target_x, target_y, target_z, state);
+                let mut changes = vec![BlockChange { x: target_x, y: target_y, z: target_z, state }];
+                if let Some(half) = other_half {
+                    world.set_block(half.x, half.y, half.z, half.state);
+                    changes.push(half);
+                }
+                if let Some(entity) = block::get_block(state).and_then(BlockEntity::for_block) {
+                    world.set_block_entity(target_x, target_y, target_z, entity);
+                    // Replaces the single chest state when it joins a neighbour.
+                    changes.extend(container::connect_chest(world, target_x, target_y, target_z));
+                }
+                if sender.player.gamemode != GameMode::Creative {
+                    let slot = sender.player.held_slot(&body.hand);
+                    if let Some(Some(item)) = sender.player.inventory.items.get_mut(slot) {
+                        item.item_count -= 1;
+                        if item.item_count <= 0 {
+                            sender.player.inventory.items[slot] = None;
+                        }
+                    }
+                }
+                changes
+            }
+            None => vec![],
+        };
+
+        if changes.is_empty() {
+            let resync = [
+                BlockChange { x, y, z, state: clicked },
+                BlockChange { x: target_x, y: target_y, z: target_z, state: target },
+            ];
+            let _ = sender.send_block_changes(&resync).await;
+        } else {
+            self.send_block_changes(&world_name, &changes, sender).await;
+        }
+    }
+
+    /// Whether any player in the world is standing where a block would be placed.
+    async fn is_obstructed(&self, world: &str, x: i32, y: i32, z: i32, sender: &ServerClient) -> bool {
+        if movement::intersects_block(&sender.player.position, x, y, z) {
+            return true;
+        }
+        for (key, other) in self.clients.lock().await.iter() {
+            if key.1 == sender.uuid {
+                continue;
+            }
+            let other = other.lock().await;
+            if other.player.position.world == world && movement::intersects_block(&other.player.position, x, y, z) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Applies a movement packet after validating it, rolling the player back to their last
+    /// valid position if the move is rejected.
+    async fn handle_movement(&mut self, sender: &mut ServerClient, position: Option<Vec3<f64>>, rotation: Option<EntityRotation<f32>>, on_ground: bool) {
+        if sender.player.movement.pending_teleport.is_some() || sender.player.health.is_dead() {
+            return;
+        }
+
+        let world = match self.worlds.get_mut(&sender.player.position.world) {
+            Some(world) => world,
+            None => return,
+        };
+
+        let mut fall_damage = 0.0;
+        if let Some(position) = position {
+            if let Err(violation) = movement::check_move(&self.movement_config, &mut sender.player, world, &position, on_ground) {
+                sender.player.movement.violations += 1;
+                println!("{} {}, moving them back.", sender.name, violation);
+                let last_valid = sender.player.movement.last_valid.clone();
+                let _ = sender.teleport(last_valid).await;
+                return;
+            }
+            let from = sender.player.position.clone();
+            let (dx, dy, dz) = (position.x - from.x, position.y - from.y, position.z - from.z);
+            sender.player.position.x = position.x;
+            sender.player.position.y = position.y;
+            sender.player.position.z = position.z;
+
+            let survival = sender.player.gamemode == GameMode::Survival || sender.player.gamemode == GameMode::Adventure;
+            if survival && !sender.player.flying {
+                if sender.player.sprinting {
+                    sender.player.health.add_exhaustion(health::SPRINT_EXHAUSTION * (dx * dx + dz * dz).sqrt() as f32);
+                }
+                if dy > 0.0 && from.on_ground && !on_ground {
+                    sender.player.health.add_exhaustion(if sender.player.sprinting { health::SPRINT_JUMP_EXHAUSTION } else { health::JUMP_EXHAUSTION });
+                }
+                let in_water = health::in_water(world, &sender.player.position);
+                fall_damage = sender.player.health.update_fall(dy, on_ground, in_water);
+            } else {
+                sender.player.health.fall_distance = 0.0;
+            }
+        }
+
+        if let Some(rotation) = rotation {
+            sender.player.position.yaw = rotation.yaw;
+            sender.player.position.pitch = rotation.pitch;
+        }
+
+        sender.player.position.on_ground = on_ground;
+        sender.player.movement.last_valid = sender.player.position.clone();
+
+        let biome_id = self.dimension_codec.biomes.id_of(&world.biome).unwrap_or(0);
+        let _ = sender.update_chunks(world, biome_id).await;
+
+        let portal = portal::portal_at(world, &sender.player.position);
+        let instant = sender.player.gamemode == GameMode::Creative;
+        if let Some(kind) = sender.player.portal.update(portal, instant) {
+            if let Err(e) = self.travel(sender, kind).await {
+                println!("{} could not travel through a portal: {}", sender.name, e);
+            }
+            return;
+        }
+        if fall_damage > 0.0 {
+            self.damage_player(sender, fall_damage, DamageCause::Fall).await;
+        }
+        self.update_tracking(sender).await;
+    }
+
+    /// Spawns, moves or destroys `client` for every other player in range, and the other
+    /// players for `client`.
+    async fn update_tracking(&self, client: &mut ServerClient) {
+        for (key, other) in self.clients.lock().await.iter() {
+            if key.1 == client.uuid {
+                continue;
+            }
+            let mut other = other.lock().await;
+            let in_range = tracker::in_tracking_range(&client.player.position, &other.player.position, self.tracking_range);
+
+            let packets = if !in_range {
+                other.tracker.destroy(&[client.entity_id]).into_iter().collect()
+            } else if other.tracker.is_tracking(client.entity_id) {
+                other.tracker.move_player(&client.player)
+            } else {
+                other.tracker.spawn_player(&client.player)
+            };
+            let _ = other.send_packets(packets).await;
+
+            let packets = if !in_range {
+                client.tracker.destroy(&[other.entity_id]).into_iter().collect()
+            } else if !client.tracker.is_tracking(other.entity_id) {
+                client.tracker.spawn_player(&other.player)
+            } else {
+                vec![]
+            };
+            let _ = client.send_packets(packets).await;
+        }
+    }
+
+    /// Kicks a player and removes them right away, instead of once their packet loop notices the
+    /// closed connection.
+    async fn kick_player(&mut self, client: &Arc<Mutex<ServerClient>>, reason: Chat) {
+        let mut client = client.lock().await;
+        let _ = client.kick(reason).await;
+        self.handle_disconnect(&mut client).await;
+    }
+
+    /// Removes a player from the server. Does nothing if they were already removed, which happens
+    /// when their session was replaced by a newer one.
+    async fn handle_disconnect(&mut self, client: &mut ServerClient) {
+        if client.disconnected {
+            return;
+        }
+        client.disconnected = true;
+        let mut clients = self.clients.lock().await;
+        clients.retain(|key, _| key.1 != client.uuid);
+        for other in clients.values() {
+            let mut other = other.lock().await;
+            if let Some(packet) = other.tracker.destroy(&[client.entity_id]) {
+                let _ = other.connection.write_packet(packet).await;
+            }
+            let _ = other.connection.write_packet(tablist::remove_players(vec![client.uuid])).await;
+        }
+        self.entity_ids.release(client.entity_id);
+        println!("{} disconnected.", client.name);
+    }
+
+    async fn handle_status(&mut self, mut client: Client) -> anyhow::Result<()> {
+        use Packet::{StatusPing, StatusPong, StatusRequest};
+        use mcproto_rs::status::StatusPlayerSampleSpec;
+        use proto::{StatusPongSpec};
         println!("before next read");
         let second = &mut client.read_next_packet().await;
         println!("Read next");
@@ -392,19 +2004,53 @@ impl Server {
 pub struct ServerClient {
     name: String,
     uuid: UUID4,
+    address: SocketAddr,
     entity_id: i32,
     player: Player,
     view_distance: i32,
+    /// Chunks the client has been sent and not yet told to unload.
+    loaded_chunks: HashSet<(i32, i32)>,
+    window: WindowState,
+    /// Container window the client has open on top of its inventory.
+    container: Option<OpenContainer>,
+    last_window_id: u8,
     connection: Client,
+    tracker: EntityTracker,
+    /// Id and send time of the keep alive the client has yet to answer.
+    keep_alive: Option<(i64, Instant)>,
+    /// Set once the player has been removed from the server, after which the connection is only
+    /// waited on to close.
+    disconnected: bool,
+    chat: ChatState,
+    /// Which chat messages the client wants to see, from its settings.
+    chat_mode: ClientChatMode,
+    /// Language the client runs in, such as `en_us`.
+    locale: String,
+    translations: Arc<Translations>,
+    data: PlayerData,
+    /// Chat channel the player's messages go to.
+    channel: String,
+    /// Player `/r` replies to, the last one they exchanged a private message with.
+    reply_to: Option<UUID4>,
 }
 
 impl ServerClient {
+    /// Sends plain text to the player's chat as a system message, such as command output.
+    pub async fn send_feedback(&mut self, text: &str) {
+        let _ = self.send_message(Chat::from_text(text), ChatPosition::SystemMessage, None).await;
+    }
+
+    /// Sends a message, unless the client has hidden chat messages of its kind.
     pub async fn send_message(&mut self, message: Chat, position: ChatPosition, sender: Option<UUID4>) -> Result<()> {
         use Packet::PlayServerChatMessage;
         use proto::PlayServerChatMessageSpec;
 
+        if !chat::is_visible(&self.chat_mode, &position) {
+            return Ok(());
+        }
+
         let spec = PlayServerChatMessageSpec {
-            message,
+            message: self.translations.localize(&message, &self.locale),
             sender: sender.unwrap_or(UUID4::from(0)),
             position,
         };
@@ -413,285 +2059,400 @@ impl ServerClient {
         self.connection.write_packet(packet).await
     }
 
+    /// The player's name as shown in chat: their display name if they have one, revealing their
+    /// name and UUID on hover and suggesting a private message when clicked.
+    pub fn name_component(&self) -> Chat {
+        let name = self.player.display_name.clone().unwrap_or_else(|| Chat::from_text(&self.name));
+        text::text("")
+            .append(name)
+            .hover_text(text::text(&self.name).append(text::text(&format!("\n{}", self.uuid)).color(ColorCode::Gray)))
+            .suggest_command(&format!("/tell {} ", self.name))
+            .insertion(&self.name)
+            .build()
+    }
+
+    pub fn chat_member(&self) -> ChatMember {
+        ChatMember { name: self.name.clone(), uuid: self.uuid, position: self.player.position.clone() }
+    }
+
+    /// Tells the client why it is disconnected and closes the connection, which ends its packet
+    /// loop and so removes the player.
     pub async fn kick(&mut self, reason: Chat) -> Result<()> {
         use proto::PlayDisconnectSpec;
         use Packet::PlayDisconnect;
 
         let spec = PlayDisconnectSpec { reason };
-        self.connection.write_packet(PlayDisconnect(spec)).await
-    }
-
-    /*
-    1. Implement NBTMap (see NBTMap in MCHPRS)
-    2. Possibly implement temporary local wrapper for codecs
-    3. Abandon mc-proto and go to MCHPRS protocol implementation
-     */
-    pub async fn join_world(&mut self, is_hardcore: bool, max_players: i32) -> Result<()> {
-        let tag = Tag::Compound(vec![
-            NamedTag {
-                name: String::from("piglin_safe"),
-                payload: Tag::Byte(1),
-            },
-            NamedTag {
-                name: String::from("natural"),
-                payload: Tag::Byte(1),
-            },
-            NamedTag {
-                name: String::from("ambient_light"),
-                payload: Tag::Float(1.0),
-            },
-            NamedTag {
-                name: String::from("fixed_time"),
-                payload: Tag::Long(1),
-            },
-            NamedTag {
-                name: String::from("infiniburn"),
-                payload: Tag::String(String::from("minecraft:infiniburn_overworld")),
-            },
-            NamedTag {
-                name: String::from("respawn_anchor_works"),
-                payload: Tag::Byte(0),
-            },
-            NamedTag {
-                name: String::from("has_skylight"),
-                payload: Tag::Byte(1),
-            },
-            NamedTag {
-                name: String::from("bed_works"),
-                payload: Tag::Byte(1),
-            },
-            NamedTag {
-                name: String::from("effects"),
-                payload: Tag::String(String::from("minecraft:overworld"))
-            },
-            NamedTag {
-                name: String::from("has_raids"),
-                payload: Tag::Byte(0),
-            },
-            NamedTag {
-                name: String::from("min_y"),
-                payload: Tag::Int(0),
-            },
-            NamedTag {
-                name: String::from("height"),
-                payload: Tag::Int(256),
-            },
-            NamedTag {
-                name: String::from("logical_height"),
-                payload: Tag::Int(256),
-            },
-            NamedTag {
-                name: String::from("coordinate_scale"),
-                payload: Tag::Float(1.0),
-            },
-            NamedTag {
-                name: String::from("ultrawarm"),
-                payload: Tag::Byte(0),
-            },
-            NamedTag {
-                name: String::from("has_ceiling"),
-                payload: Tag::Byte(0),
-            },
-        ]);
-
-        let dimension_type = NamedTag {
-            name: String::from(""),
-            payload: Tag::Compound(vec![
-                NamedTag {
-                    name: String::from("minecraft:dimension_type"),
-                    payload: Tag::Compound(vec![
-                        NamedTag {
-                            name: String::from("type"),
-                            payload: Tag::String(String::from("minecraft:dimension_type")),
-                        },
-                        NamedTag {
-                            name: String::from("value"),
-                            payload: Tag::List(
-                                vec![Tag::Compound(vec![
-                                    NamedTag {
-                                        name: String::from("name"),
-                                        payload: Tag::String(String::from("minecraft:overworld")),
-                                    },
-                                    NamedTag {
-                                        name: String::from("id"),
-                                        payload: Tag::Int(0),
-                                    },
-                                    NamedTag {
-                                        name: String::from("element"),
-                                        payload: tag
-                                    },
-                                ])]
-                            ),
-                        },
-                    ]),
-                },
-            ]),
-        };
+        let result = self.connection.write_packet(PlayDisconnect(spec)).await;
+        self.connection.close();
+        result
+    }
 
-        let biome = NamedTag {
-            name: String::from(""),
-            payload: Tag::Compound(vec![
-                NamedTag {
-                    name: String::from("precipitation"),
-                    payload: Tag::String(String::from("rain")),
-                },
-                NamedTag {
-                    name: String::from("effects"),
-                    payload: Tag::String(String::from("minecraft:overworld")),
-                },
-                NamedTag {
-                    name: String::from("depth"),
-                    payload: Tag::Float(-1.0),
-                },
-                NamedTag {
-                    name: String::from("temperature"),
-                    payload: Tag::Float(0.5),
-                },
-                NamedTag {
-                    name: String::from("scale"),
-                    payload: Tag::Float(0.1),
-                },
-                NamedTag {
-                    name: String::from("downfall"),
-                    payload: Tag::Float(0.5),
-                },
-                NamedTag {
-                    name: String::from("category"),
-                    payload: Tag::String(String::from("none")),
-                },
-                NamedTag {
-                    name: String::from("infiniburn"),
-                    payload: Tag::String(String::from("minecraft:infiniburn_overworld"))
-                },
-                NamedTag {
-                    name: String::from("logical_height"),
-                    payload: Tag::Int(256),
-                },
-                NamedTag {
-                    name: String::from("ambient_light"),
-                    payload: Tag::Float(9.0),
-                },
-                NamedTag {
-                    name: String::from("has_raids"),
-                    payload: Tag::Byte(1),
-                },
-                NamedTag {
-                    name: String::from("respawn_anchor_works"),
-                    payload: Tag::Byte(0),
-                },
-                NamedTag {
-                    name: String::from("bed_works"),
-                    payload: Tag::Byte(1),
-                },
-                NamedTag {
-                    name: String::from("piglin_safe"),
-                    payload: Tag::Byte(0),
-                },
-                NamedTag {
-                    name: String::from("coordinate_scale"),
-                    payload: Tag::Float(1.0),
-                },
-                NamedTag {
-                    name: String::from("ultrawarm"),
-                    payload: Tag::Byte(0),
-                },
-                NamedTag {
-                    name: String::from("has_ceiling"),
-                    payload: Tag::Byte(0),
+    /// Sends a new keep alive, or kicks the client if the previous one went unanswered for too
+    /// long.
+    pub async fn keep_alive(&mut self) -> Result<()> {
+        if let Some((_, sent)) = self.keep_alive {
+            if sent.elapsed() > KEEP_ALIVE_TIMEOUT {
+                let _ = self.kick(Chat::from_text("Timed out")).await;
+                return Err(anyhow!("{} did not respond to keep alive.", self.name));
+            }
+            return Ok(());
+        }
+
+        let id = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        self.keep_alive = Some((id, Instant::now()));
+        self.connection.write_packet(Packet::PlayServerKeepAlive(PlayServerKeepAliveSpec { id })).await
+    }
+
+    pub async fn send_packets(&mut self, packets: Vec<Packet>) -> Result<()> {
+        for packet in packets {
+            self.connection.write_packet(packet).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the player to an absolute position. Movement from the client is ignored until it
+    /// confirms the teleport.
+    pub async fn teleport(&mut self, position: Position) -> Result<()> {
+        let teleport_id = self.player.movement.next_teleport_id();
+        let spec = PlayServerPlayerPositionAndLookSpec {
+            teleport_id: VarInt::from(teleport_id),
+            location: EntityLocation {
+                position: Vec3 {
+                    x: position.x,
+                    y: position.y,
+                    z: position.z,
                 },
-                NamedTag {
-                    name: String::from("has_skylight"),
-                    payload: Tag::Byte(1),
+                rotation: EntityRotation {
+                    yaw: position.yaw,
+                    pitch: position.pitch,
                 },
-                NamedTag {
-                    name: String::from("natural"),
-                    payload: Tag::Byte(1),
-                }
-            ]),
+            },
+            flags: PositionAndLookFlags(0),
         };
 
+        self.player.movement.last_valid = position.clone();
+        self.player.position = position;
+        self.connection.write_packet(PlayServerPlayerPositionAndLook(spec)).await
+    }
+
+    pub async fn join_world(&mut self, is_hardcore: bool, max_players: i32, codec: &DimensionCodec, world_names: Vec<String>, world: &mut World, biome_id: i32, spawn_radius: i32) -> Result<()> {
         let spec = proto::PlayJoinGameSpec {
             gamemode: self.player.gamemode.clone(),
-            previous_gamemode: PreviousGameMode::NoPrevious,
+            previous_gamemode: match &self.player.previous_gamemode {
+                Some(gamemode) => PreviousGameMode::Previous(gamemode.clone()),
+                None => PreviousGameMode::NoPrevious,
+            },
             entity_id: self.player.entity_id,
             is_hardcore,
-            worlds: CountedArray::from(vec![String::from("world")]),
-            dimension_codec: NamedNbtTag {
-                root: dimension_type.clone()
-            },
-            dimension: NamedNbtTag {
-                root: biome.clone()
-            },
-            world_name: String::from("world"),
+            worlds: CountedArray::from(world_names),
+            dimension_codec: codec.to_named_nbt(),
+            dimension: world.dimension_type.to_named_nbt(),
+            world_name: world.name.clone(),
             hashed_seed: 0,
             max_players: VarInt::from(max_players),
             view_distance: VarInt::from(self.view_distance),
-            enable_respawn_screen: false,
-            is_flat: false,
+            enable_respawn_screen: true,
+            is_flat: world.is_flat,
             is_debug: false,
             reduced_debug_info: true,
         };
 
         println!("{:?}", Packet::PlayJoinGame(spec.clone()));
 
-        self.connection.write_packet(Packet::PlayJoinGame(spec)).await;
+        self.connection.write_packet(Packet::PlayJoinGame(spec)).await?;
 
         let brand = PlayClientPluginMessageSpec {
             channel: String::from("minecraft:brand"),
             data: RemainingBytes {
                 data: {
                     let mut data = Vec::new();
-                    data.write("Endstone 1.16.3".as_bytes()).await;
+                    data.write("Endstone 1.16.3".as_bytes()).await?;
                     data
                 }
             },
         };
 
-        self.connection.write_packet(PlayClientPluginMessage(brand)).await;
+        self.connection.write_packet(PlayClientPluginMessage(brand)).await?;
+        self.connection.write_packet(self.player.abilities()).await?;
 
-        let pos_and_look = PlayServerPlayerPositionAndLookSpec {
-            teleport_id: VarInt::from(0),
-            location: EntityLocation {
-                position: Vec3 {
-                    x: 0.0,
-                    y: 60.0,
-                    z: 0.0,
-                },
-                rotation: EntityRotation {
-                    pitch: 0.0,
-                    yaw: 0.0,
-                },
-            },
-            flags: PositionAndLookFlags(0x01),
+        self.connection.write_packet(Packet::PlaySpawnPosition(PlaySpawnPositionSpec { location: world.spawn.clone() })).await?;
+        let spawn = spawn_position(world, spawn_radius, &self.player.position);
+        self.player.position = spawn.clone();
+        self.update_chunks(world, biome_id).await?;
+        self.teleport(spawn).await?;
+
+        self.send_inventory().await?;
+        self.send_health().await?;
+        let slot = self.player.selected_slot as i8;
+        self.connection.write_packet(Packet::PlayServerHeldItemChange(PlayServerHeldItemChangeSpec { slot })).await
+    }
+
+    /// Resends what the client forgets on Respawn: abilities, inventory, held slot and health.
+    pub async fn send_player_state(&mut self) -> Result<()> {
+        self.connection.write_packet(self.player.abilities()).await?;
+        self.send_inventory().await?;
+        let slot = self.player.selected_slot as i8;
+        self.connection.write_packet(Packet::PlayServerHeldItemChange(PlayServerHeldItemChangeSpec { slot })).await?;
+        self.send_health().await
+    }
+
+    /// Sends the whole player inventory and the item on the cursor.
+    pub async fn send_inventory(&mut self) -> Result<()> {
+        self.connection.write_packet(Packet::PlayWindowItems(PlayWindowItemsSpec {
+            window_id: inventory::PLAYER_WINDOW_ID,
+            slots: CountedArray::from(self.player.inventory.items.to_vec()),
+        })).await?;
+        self.connection.write_packet(Packet::PlaySetSlot(PlaySetSlotSpec {
+            window_id: inventory::CURSOR_WINDOW_ID,
+            slow: inventory::CURSOR_SLOT,
+            slot_data: self.window.cursor.clone(),
+        })).await
+    }
+
+    /// Sends the slots of the open container followed by the player's main inventory and hotbar.
+    pub async fn send_container(&mut self, slots: Vec<Slot>) -> Result<()> {
+        let window_id = match &self.container {
+            Some(open) => open.id,
+            None => return Ok(()),
+        };
+        let mut slots = slots;
+        slots.extend_from_slice(&self.player.inventory.items[player::MAIN_SLOTS.start..player::HOTBAR_SLOTS.end]);
+        self.connection.write_packet(Packet::PlayWindowItems(PlayWindowItemsSpec {
+            window_id,
+            slots: CountedArray::from(slots),
+        })).await?;
+        self.connection.write_packet(Packet::PlaySetSlot(PlaySetSlotSpec {
+            window_id: inventory::CURSOR_WINDOW_ID,
+            slow: inventory::CURSOR_SLOT,
+            slot_data: self.window.cursor.clone(),
+        })).await
+    }
+
+    /// Sends the fuel and smelting progress bars of an open furnace.
+    pub async fn send_furnace_progress(&mut self, furnace: &FurnaceState) -> Result<()> {
+        let window_id = match &self.container {
+            Some(open) => open.id,
+            None => return Ok(()),
+        };
+        for (property, value) in furnace.properties().iter() {
+            self.connection.write_packet(Packet::PlayWindowProperty(PlayWindowPropertySpec {
+                window_id,
+                property: *property,
+                value: *value,
+            })).await?;
+        }
+        Ok(())
+    }
+
+    /// Puts the item on the cursor and anything left in a crafting grid back into the player's
+    /// inventory. Item entities do not exist yet, so whatever does not fit stays on the cursor or
+    /// in the player's crafting grid, and a crafting table's grid spills into the player's.
+    pub fn return_window_items(&mut self) {
+        let main = player::MAIN_SLOTS.start..player::HOTBAR_SLOTS.end;
+        let mut items = std::mem::take(&mut self.window.overflow);
+        if let Some(open) = &mut self.container {
+            items.extend(open.crafting.drain(..).skip(container::CRAFTING_TABLE_OUTPUT_SLOT + 1).flatten());
+        }
+        let slots = &mut self.player.inventory.items;
+        for item in items {
+            let left = inventory::add_item(slots, main.clone(), item)
+                .and_then(|item| inventory::add_item(slots, player::CRAFTING_INPUT_SLOTS, item));
+            self.window.overflow.extend(left);
+        }
+        for slot in player::CRAFTING_INPUT_SLOTS {
+            if let Some(item) = slots[slot].take() {
+                slots[slot] = inventory::add_item(slots, main.clone(), item);
+            }
+        }
+        if let Some(item) = self.window.cursor.take() {
+            self.window.cursor = inventory::add_item(slots, main, item);
+        }
+        slots[player::CRAFTING_OUTPUT_SLOT] = None;
+    }
+
+    /// Closes the open container window if it shows the block at the given position.
+    pub async fn close_container_at(&mut self, world: &str, position: (i32, i32, i32)) {
+        let window_id = match &self.container {
+            Some(open) if open.shows(world, position) => open.id,
+            _ => return,
         };
+        self.return_window_items();
+        self.container = None;
+        let _ = self.connection.write_packet(Packet::PlayServerCloseWindow(PlayServerCloseWindowSpec { window_id })).await;
+        let _ = self.send_inventory().await;
+    }
 
-        self.connection.write_packet(PlayServerPlayerPositionAndLook(pos_and_look)).await;
+    pub async fn send_health(&mut self) -> Result<()> {
+        self.connection.write_packet(Packet::PlayUpdatehealth(PlayUpdateHealthSpec {
+            health: self.player.health.health,
+            food: VarInt::from(self.player.health.hunger),
+            saturation: self.player.health.saturation,
+        })).await
+    }
 
-        let mut inventory: Vec<Option<ItemStack>> = vec![None; 46];
+    /// Sends a single slot of the player inventory.
+    pub async fn send_slot(&mut self, slot: i16) -> Result<()> {
+        let slot_data = self.player.inventory.get(slot as usize);
+        self.connection.write_packet(Packet::PlaySetSlot(PlaySetSlotSpec {
+            window_id: inventory::PLAYER_WINDOW_ID,
+            slow: slot,
+            slot_data,
+        })).await
+    }
 
-        /*
-        let chunk_data = ChunkData {
-            position: ChunkPosition {
-                x: 0,
-                z: 0,
-            },
-            primary_bit_mask: VarInt::from(1),
-            block_entities: vec![],
-            heightmaps: NamedNbtTag {
-                root: NamedTag {
-                    name: String::from("MOTION_BLOCKING"),
-                    payload: Tag::LongArray(vec![
+    /// Sends the chunks within view distance of the player that the client does not have yet and
+    /// unloads the ones that fell out of range.
+    pub async fn update_chunks(&mut self, world: &mut World, biome_id: i32) -> Result<()> {
+        let center_x = (self.player.position.x.floor() as i32) >> 4;
+        let center_z = (self.player.position.z.floor() as i32) >> 4;
+        let distance = self.view_distance;
+        let in_view = |x: i32, z: i32| (x - center_x).abs() <= distance && (z - center_z).abs() <= distance;
 
-                    ]),
+        let mut missing = vec![];
+        for x in center_x - distance..=center_x + distance {
+            for z in center_z - distance..=center_z + distance {
+                if !self.loaded_chunks.contains(&(x, z)) {
+                    missing.push((x, z));
                 }
+            }
+        }
+        let unloading: Vec<(i32, i32)> = self.loaded_chunks.iter().filter(|(x, z)| !in_view(*x, *z)).cloned().collect();
+        if missing.is_empty() && unloading.is_empty() {
+            return Ok(());
+        }
+
+        self.connection.write_packet(Packet::PlayUpdateViewPosition(PlayUpdateViewPositionSpec {
+            chunk: ChunkPosition {
+                x: VarInt::from(center_x),
+                z: VarInt::from(center_z),
             },
-        };
-         */
+        })).await?;
+
+        for (x, z) in unloading {
+            self.loaded_chunks.remove(&(x, z));
+            self.connection.write_packet(Packet::PlayUnloadChunk(PlayUnloadChunkSpec {
+                position: ChunkPosition { x, z },
+            })).await?;
+        }
+
+        missing.sort_by_key(|(x, z)| (x - center_x).abs().max((z - center_z).abs()));
+        let has_skylight = world.dimension_type.has_skylight;
+        for (x, z) in missing {
+            let packets = world.get_chunk(x, z).to_packets(biome_id, has_skylight);
+            self.send_packets(packets).await?;
+            self.loaded_chunks.insert((x, z));
+        }
+        Ok(())
+    }
 
+    /// Sends the changes to blocks in chunks the client has loaded, batching changes to the same
+    /// chunk section into a single packet.
+    pub async fn send_block_changes(&mut self, changes: &[BlockChange]) -> Result<()> {
+        let mut sections: HashMap<(i32, i32, i32), Vec<BlockChange>> = HashMap::new();
+        for change in changes {
+            if self.loaded_chunks.contains(&(change.x >> 4, change.z >> 4)) {
+                sections.entry((change.x >> 4, change.y >> 4, change.z >> 4)).or_insert_with(Vec::new).push(*change);
+            }
+        }
+
+        for ((x, y, z), changes) in sections {
+            let packet = if changes.len() == 1 {
+                let change = changes[0];
+                Packet::PlayBlockChange(PlayBlockChangeSpec {
+                    location: IntPosition {
+                        x: change.x,
+                        y: change.y as i16,
+                        z: change.z,
+                    },
+                    block_id: VarInt::from(change.state as i32),
+                })
+            } else {
+                let blocks = changes
+                    .iter()
+                    .map(|change| MultiBlockChangeRecord {
+                        block_id: change.state as u64,
+                        rel_position: Vec3 {
+                            x: (change.x & 15) as i8,
+                            y: (change.y & 15) as i8,
+                            z: (change.z & 15) as i8,
+                        },
+                    })
+                    .collect::<Vec<MultiBlockChangeRecord>>();
+                Packet::PlayMultiBlockChange(PlayMultiBlockChangeSpec {
+                    chunk: ChunkSectionPosition { x, y, z },
+                    not_trust_edges: false,
+                    blocks: CountedArray::from(blocks),
+                })
+            };
+            self.connection.write_packet(packet).await?;
+        }
+        Ok(())
+    }
+
+    /// Unloads every chunk the client has, used before moving it to another world.
+    pub async fn unload_chunks(&mut self) -> Result<()> {
+        for (x, z) in self.loaded_chunks.drain().collect::<Vec<(i32, i32)>>() {
+            self.connection.write_packet(Packet::PlayUnloadChunk(PlayUnloadChunkSpec {
+                position: ChunkPosition { x, z },
+            })).await?;
+        }
         Ok(())
     }
 }
 
+/// Uses up one durability of the tool in the player's main hand, breaking it when none is left.
+fn damage_held_tool(client: &mut ServerClient) {
+    let slot = client.player.held_slot(&Hand::MainHand);
+    let held = match client.player.inventory.get(slot) {
+        Some(held) => held,
+        None => return,
+    };
+    let registered = match item::get_item(held.item_id.0) {
+        Some(registered) if registered.max_damage > 0 && digging::get_tool(registered.id).is_some() => registered,
+        _ => return,
+    };
+    if item::is_unbreakable(&held) {
+        return;
+    }
+
+    let damage = item::damage(&held) + 1;
+    let damaged = if damage >= registered.max_damage {
+        None
+    } else {
+        Some(item::set_damage(held, damage))
+    };
+    client.player.inventory.set(slot, damaged);
+}
+
+/// Whether the given block is within the player's reach, which is all it takes to use it.
+fn in_reach(client: &ServerClient, x: i32, y: i32, z: i32) -> bool {
+    let position = &client.player.position;
+    let dx = position.x - (x as f64 + 0.5);
+    let dy = position.y + PLAYER_EYE_HEIGHT - (y as f64 + 0.5);
+    let dz = position.z - (z as f64 + 0.5);
+    dx * dx + dy * dy + dz * dz <= digging::MAX_REACH * digging::MAX_REACH
+}
+
+/// Whether the player may change the given block: they have to be in survival or creative and
+/// within reach of it.
+fn can_build(client: &ServerClient, x: i32, y: i32, z: i32) -> bool {
+    let gamemode = &client.player.gamemode;
+    (*gamemode == GameMode::Survival || *gamemode == GameMode::Creative) && in_reach(client, x, y, z)
+}
+
+/// A safe position near the world's spawn, keeping the rotation of `current`.
+fn spawn_position(world: &mut World, radius: i32, current: &Position) -> Position {
+    let (x, y, z) = spawn::world_spawn(world, radius);
+    Position {
+        x: x as f64 + 0.5,
+        y: y as f64,
+        z: z as f64 + 0.5,
+        on_ground: true,
+        world: world.name.clone(),
+        ..current.clone()
+    }
+}
+
 pub struct ServerStatus {
     description: Chat,
     players: StatusPlayersSpec,
@@ -0,0 +1,969 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use tokio::sync::Mutex;
+use mcproto_rs::types::{Chat, ColorCode, CountedArray, IntPosition, VarInt};
+use mcproto_rs::uuid::UUID4;
+use mcproto_rs::v1_16_3::{ChatPosition, GameMode, Packet753 as Packet, PlayClientTabCompleteSpec, PlayTabCompleteSpec, TabCompleteMatch};
+use crate::server::chat;
+use crate::server::command::{argument, literal, ArgumentType, CommandBuilder, CommandContext, CommandDispatcher, EntitySelector, SelectorCandidate, SelectorTarget, Suggestions};
+use crate::server::inventory;
+use crate::server::item::ItemBuilder;
+use crate::server::permission::Permissions;
+use crate::server::player::{self, gamemode_name, Position};
+use crate::server::text;
+use crate::server::userlist::{self, BanEntry, IpBanEntry, OpEntry, UserEntry};
+use crate::server::world::{Weather, DAY_LENGTH};
+use super::{Server, ServerClient};
+
+/// How long `/weather` lasts without a duration, in ticks.
+const DEFAULT_WEATHER_TICKS: i32 = 6000;
+
+/// Whoever runs a command, which decides where its output goes.
+pub enum CommandSource<'a> {
+    /// The player is not kept locked while the command runs, since it may act on them like on
+    /// any other player.
+    Player {
+        client: Arc<Mutex<ServerClient>>,
+        name: String,
+        uuid: UUID4,
+    },
+    Console,
+    /// Output is collected to be sent back over RCON.
+    Rcon(&'a mut Vec<String>),
+}
+
+impl CommandSource<'_> {
+    pub fn name(&self) -> &str {
+        match self {
+            CommandSource::Player { name, .. } => name,
+            CommandSource::Console => "Server",
+            CommandSource::Rcon(_) => "Rcon",
+        }
+    }
+
+    pub fn uuid(&self) -> Option<UUID4> {
+        match self {
+            CommandSource::Player { uuid, .. } => Some(*uuid),
+            _ => None,
+        }
+    }
+
+    /// The player running the command, for commands that need one.
+    pub fn player(&self) -> Result<Arc<Mutex<ServerClient>>> {
+        match self {
+            CommandSource::Player { client, .. } => Ok(client.clone()),
+            _ => Err(anyhow!("A player is required to run this command here")),
+        }
+    }
+
+    /// The source's name as shown in chat.
+    pub async fn name_component(&self) -> Chat {
+        match self {
+            CommandSource::Player { client, .. } => client.lock().await.name_component(),
+            _ => Chat::from_text(self.name()),
+        }
+    }
+
+    pub async fn send_chat(&mut self, message: Chat) {
+        match self {
+            CommandSource::Player { client, .. } => {
+                let _ = client.lock().await.send_message(message, ChatPosition::SystemMessage, None).await;
+            }
+            CommandSource::Console => println!("{}", text::plain_text(&message)),
+            CommandSource::Rcon(output) => output.push(text::plain_text(&message)),
+        }
+    }
+
+    pub async fn send_feedback(&mut self, text: &str) {
+        self.send_chat(Chat::from_text(text)).await;
+    }
+
+    pub async fn send_error(&mut self, text: &str) {
+        match self {
+            CommandSource::Player { .. } => self.send_chat(text::text(text).color(ColorCode::Red).build()).await,
+            _ => self.send_feedback(text).await,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TimeQuery {
+    DayTime,
+    GameTime,
+    Day,
+}
+
+/// What each registered command does, see `Server::run_command`.
+#[derive(Clone, Debug)]
+pub enum CommandAction {
+    GameMode(GameMode),
+    Teleport,
+    Give,
+    Kick,
+    /// Temporary bans take their length from the `duration` argument.
+    Ban { temporary: bool },
+    Pardon,
+    BanIp,
+    PardonIp,
+    BanList { players: bool, ips: bool },
+    Reload,
+    Op,
+    Deop,
+    WhitelistOn,
+    WhitelistOff,
+    WhitelistList,
+    WhitelistAdd,
+    WhitelistRemove,
+    WhitelistReload,
+    List { uuids: bool },
+    Say,
+    Message,
+    Reply,
+    Ignore,
+    Unignore,
+    IgnoreList,
+    /// Lists channels, or switches to the one in the `channel` argument.
+    Channel { switch: bool },
+    /// `None` takes the time from the `time` argument.
+    TimeSet(Option<i64>),
+    TimeAdd,
+    TimeQuery(TimeQuery),
+    Weather(Weather),
+    Stop,
+    SaveAll,
+    SetWorldSpawn,
+    Permission(PermissionAction),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum PermissionAction {
+    Check,
+    PlayerSet,
+    PlayerUnset,
+    PlayerAddGroup,
+    PlayerRemoveGroup,
+    GroupSet,
+    GroupUnset,
+    GroupInherit,
+    GroupDelete,
+    Reload,
+}
+
+/// Operator level each command needs unless permissions say otherwise, as in vanilla.
+pub const COMMAND_LEVELS: &[(&str, u8)] = &[
+    ("gamemode", 2), ("teleport", 2), ("give", 2), ("kick", 3), ("ban", 3), ("tempban", 3), ("pardon", 3),
+    ("ban-ip", 3), ("pardon-ip", 3), ("banlist", 3), ("reload", 3), ("op", 3), ("deop", 3), ("whitelist", 3),
+    ("list", 0), ("say", 2), ("msg", 0), ("time", 2), ("weather", 2), ("stop", 4), ("save-all", 4),
+    ("setworldspawn", 2), ("permission", 4), ("ignore", 0), ("channel", 0),
+];
+
+/// The permission node guarding a command.
+pub fn command_permission(name: &str) -> String {
+    format!("endstone.command.{}", name)
+}
+
+fn command(name: &str, permission: &str) -> CommandBuilder<CommandAction> {
+    literal(name).requires(&command_permission(permission))
+}
+
+fn teleport_command(name: &str) -> CommandBuilder<CommandAction> {
+    command(name, "teleport")
+        .then(argument("location", ArgumentType::Vec3).executes(CommandAction::Teleport))
+        .then(argument("destination", ArgumentType::player()).executes(CommandAction::Teleport))
+        .then(argument("targets", ArgumentType::players())
+            .then(argument("location", ArgumentType::Vec3).executes(CommandAction::Teleport))
+            .then(argument("destination", ArgumentType::player()).executes(CommandAction::Teleport)))
+}
+
+fn message_command(name: &str) -> CommandBuilder<CommandAction> {
+    command(name, "msg").then(argument("targets", ArgumentType::players())
+        .then(argument("message", ArgumentType::GreedyString).executes(CommandAction::Message)))
+}
+
+/// The built-in administration commands.
+pub fn default_commands() -> CommandDispatcher<CommandAction> {
+    let mut dispatcher = CommandDispatcher::new();
+
+    let mut gamemode = command("gamemode", "gamemode");
+    for (name, mode) in player::GAMEMODES.iter() {
+        gamemode = gamemode.then(literal(name)
+            .executes(CommandAction::GameMode(mode.clone()))
+            .then(argument("target", ArgumentType::players()).executes(CommandAction::GameMode(mode.clone()))));
+    }
+    dispatcher.register(gamemode);
+    dispatcher.register(teleport_command("teleport"));
+    dispatcher.register(teleport_command("tp"));
+    dispatcher.register(command("give", "give").then(argument("targets", ArgumentType::players())
+        .then(argument("item", ArgumentType::ItemStack)
+            .executes(CommandAction::Give)
+            .then(argument("count", ArgumentType::Integer { min: Some(1), max: None }).executes(CommandAction::Give)))));
+    dispatcher.register(command("kick", "kick").then(argument("targets", ArgumentType::players())
+        .executes(CommandAction::Kick)
+        .then(argument("reason", ArgumentType::GreedyString).executes(CommandAction::Kick))));
+    dispatcher.register(command("ban", "ban").then(argument("targets", ArgumentType::GameProfile)
+        .executes(CommandAction::Ban { temporary: false })
+        .then(argument("reason", ArgumentType::GreedyString).executes(CommandAction::Ban { temporary: false }))));
+    dispatcher.register(command("tempban", "tempban").then(argument("targets", ArgumentType::GameProfile)
+        .then(argument("duration", ArgumentType::Word)
+            .executes(CommandAction::Ban { temporary: true })
+            .then(argument("reason", ArgumentType::GreedyString).executes(CommandAction::Ban { temporary: true })))));
+    dispatcher.register(command("ban-ip", "ban-ip").then(argument("target", ArgumentType::Word)
+        .suggests(Suggestions::Players)
+        .executes(CommandAction::BanIp)
+        .then(argument("reason", ArgumentType::GreedyString).executes(CommandAction::BanIp))));
+    dispatcher.register(command("pardon-ip", "pardon-ip").then(argument("target", ArgumentType::Word).executes(CommandAction::PardonIp)));
+    dispatcher.register(command("banlist", "banlist")
+        .executes(CommandAction::BanList { players: true, ips: true })
+        .then(literal("players").executes(CommandAction::BanList { players: true, ips: false }))
+        .then(literal("ips").executes(CommandAction::BanList { players: false, ips: true })));
+    dispatcher.register(command("reload", "reload").executes(CommandAction::Reload));
+    dispatcher.register(command("pardon", "pardon").then(argument("targets", ArgumentType::GameProfile).executes(CommandAction::Pardon)));
+    dispatcher.register(command("op", "op").then(argument("targets", ArgumentType::GameProfile).executes(CommandAction::Op)));
+    dispatcher.register(command("deop", "deop").then(argument("targets", ArgumentType::GameProfile).executes(CommandAction::Deop)));
+    dispatcher.register(command("whitelist", "whitelist")
+        .then(literal("on").executes(CommandAction::WhitelistOn))
+        .then(literal("off").executes(CommandAction::WhitelistOff))
+        .then(literal("list").executes(CommandAction::WhitelistList))
+        .then(literal("add").then(argument("targets", ArgumentType::GameProfile).executes(CommandAction::WhitelistAdd)))
+        .then(literal("remove").then(argument("targets", ArgumentType::GameProfile).executes(CommandAction::WhitelistRemove)))
+        .then(literal("reload").executes(CommandAction::WhitelistReload)));
+    dispatcher.register(command("list", "list")
+        .executes(CommandAction::List { uuids: false })
+        .then(literal("uuids").executes(CommandAction::List { uuids: true })));
+    dispatcher.register(command("say", "say").then(argument("message", ArgumentType::GreedyString).executes(CommandAction::Say)));
+    dispatcher.register(message_command("msg"));
+    dispatcher.register(message_command("tell"));
+    dispatcher.register(message_command("w"));
+    dispatcher.register(command("r", "msg").then(argument("message", ArgumentType::GreedyString).executes(CommandAction::Reply)));
+    dispatcher.register(command("ignore", "ignore")
+        .executes(CommandAction::IgnoreList)
+        .then(argument("target", ArgumentType::player()).executes(CommandAction::Ignore)));
+    dispatcher.register(command("unignore", "ignore").then(argument("target", ArgumentType::Word).executes(CommandAction::Unignore)));
+    dispatcher.register(command("channel", "channel")
+        .executes(CommandAction::Channel { switch: false })
+        .then(argument("channel", ArgumentType::Word).executes(CommandAction::Channel { switch: true })));
+    dispatcher.register(command("time", "time")
+        .then(literal("set")
+            .then(literal("day").executes(CommandAction::TimeSet(Some(1000))))
+            .then(literal("noon").executes(CommandAction::TimeSet(Some(6000))))
+            .then(literal("night").executes(CommandAction::TimeSet(Some(13000))))
+            .then(literal("midnight").executes(CommandAction::TimeSet(Some(18000))))
+            .then(argument("time", ArgumentType::Time).executes(CommandAction::TimeSet(None))))
+        .then(literal("add").then(argument("time", ArgumentType::Time).executes(CommandAction::TimeAdd)))
+        .then(literal("query")
+            .then(literal("daytime").executes(CommandAction::TimeQuery(TimeQuery::DayTime)))
+            .then(literal("gametime").executes(CommandAction::TimeQuery(TimeQuery::GameTime)))
+            .then(literal("day").executes(CommandAction::TimeQuery(TimeQuery::Day)))));
+    let mut weather = command("weather", "weather");
+    for (name, kind) in [("clear", Weather::Clear), ("rain", Weather::Rain), ("thunder", Weather::Thunder)].iter() {
+        weather = weather.then(literal(name)
+            .executes(CommandAction::Weather(*kind))
+            .then(argument("duration", ArgumentType::integer(0, 1000000)).executes(CommandAction::Weather(*kind))));
+    }
+    dispatcher.register(weather);
+    dispatcher.register(command("stop", "stop").executes(CommandAction::Stop));
+    dispatcher.register(command("save-all", "save-all")
+        .executes(CommandAction::SaveAll)
+        .then(literal("flush").executes(CommandAction::SaveAll)));
+    let node = || argument("node", ArgumentType::String);
+    dispatcher.register(command("permission", "permission")
+        .then(literal("check").then(argument("targets", ArgumentType::GameProfile)
+            .then(node().executes(CommandAction::Permission(PermissionAction::Check)))))
+        .then(literal("player").then(argument("targets", ArgumentType::GameProfile)
+            .then(literal("set").then(node().then(argument("value", ArgumentType::Bool).executes(CommandAction::Permission(PermissionAction::PlayerSet)))))
+            .then(literal("unset").then(node().executes(CommandAction::Permission(PermissionAction::PlayerUnset))))
+            .then(literal("addgroup").then(argument("group", ArgumentType::Word).executes(CommandAction::Permission(PermissionAction::PlayerAddGroup))))
+            .then(literal("removegroup").then(argument("group", ArgumentType::Word).executes(CommandAction::Permission(PermissionAction::PlayerRemoveGroup))))))
+        .then(literal("group").then(argument("group", ArgumentType::Word)
+            .then(literal("set").then(node().then(argument("value", ArgumentType::Bool).executes(CommandAction::Permission(PermissionAction::GroupSet)))))
+            .then(literal("unset").then(node().executes(CommandAction::Permission(PermissionAction::GroupUnset))))
+            .then(literal("inherit").then(argument("parent", ArgumentType::Word).executes(CommandAction::Permission(PermissionAction::GroupInherit))))
+            .then(literal("delete").executes(CommandAction::Permission(PermissionAction::GroupDelete)))))
+        .then(literal("reload").executes(CommandAction::Permission(PermissionAction::Reload))));
+    dispatcher.register(command("setworldspawn", "setworldspawn")
+        .executes(CommandAction::SetWorldSpawn)
+        .then(argument("pos", ArgumentType::BlockPos).executes(CommandAction::SetWorldSpawn)));
+    dispatcher
+}
+
+/// Permissions with the command permissions registered at their vanilla operator levels.
+pub fn default_permissions(online: bool) -> Permissions {
+    let mut permissions = Permissions::new("permissions.json", online);
+    for (name, level) in COMMAND_LEVELS.iter() {
+        permissions.register(&command_permission(name), *level);
+    }
+    permissions
+}
+
+/// Names a group of players in command output, like vanilla does.
+fn describe(names: &[String]) -> String {
+    match names {
+        [name] => name.clone(),
+        _ => format!("{} players", names.len()),
+    }
+}
+
+impl Server {
+    /// Runs a command typed into the server console, printing its output.
+    pub async fn execute_console_command(&mut self, command: &str) {
+        self.execute_command(&mut CommandSource::Console, command).await;
+    }
+
+    /// Runs a command received over RCON, returning its output.
+    pub async fn execute_rcon_command(&mut self, command: &str) -> String {
+        let mut output = vec![];
+        self.execute_command(&mut CommandSource::Rcon(&mut output), command).await;
+        output.join("\n")
+    }
+
+    /// Parses and runs a command, without the leading `/`, reporting errors to the source.
+    pub async fn execute_command(&mut self, source: &mut CommandSource<'_>, command: &str) {
+        let parsed = self.commands.parse(command, &|permission| self.source_allows(source, permission));
+        let result = match parsed {
+            Ok(context) => self.run_command(source, context).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            source.send_error(&error.to_string()).await;
+        }
+    }
+
+    async fn run_command(&mut self, source: &mut CommandSource<'_>, context: CommandContext<CommandAction>) -> Result<()> {
+        match context.action.clone() {
+            CommandAction::GameMode(gamemode) => self.gamemode_command(source, &context, gamemode).await,
+            CommandAction::Teleport => self.teleport_command(source, &context).await,
+            CommandAction::Give => self.give_command(source, &context).await,
+            CommandAction::Kick => {
+                let reason = match context.get_string("reason") {
+                    Ok(reason) => Chat::from_text(reason),
+                    Err(_) => text::translate("multiplayer.disconnect.kicked", vec![]).build(),
+                };
+                for target in self.select_players(source, context.get_entity("targets")?).await? {
+                    let name = target.lock().await.name.clone();
+                    self.kick_player(&target, reason.clone()).await;
+                    source.send_feedback(&format!("Kicked {}: {}", name, text::plain_text(&reason))).await;
+                }
+                Ok(())
+            }
+            CommandAction::Ban { temporary } => {
+                let reason = context.get_string("reason").unwrap_or("Banned by an operator.");
+                let expires = match temporary {
+                    true => Some(userlist::parse_expiry(context.get_string("duration")?)?),
+                    false => None,
+                };
+                let mut changed = false;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    if self.banned_players.contains(&name, uuid) && !temporary {
+                        continue;
+                    }
+                    let entry = BanEntry::new(&name, uuid, source.name(), reason, expires);
+                    match temporary {
+                        true => source.send_feedback(&format!("Banned {} until {}: {}", name, entry.expires, reason)).await,
+                        false => source.send_feedback(&format!("Banned {}: {}", name, reason)).await,
+                    }
+                    if let Some(client) = self.client_by_name(&name).await {
+                        self.kick_player(&client, Chat::from_text(&entry.kick_message())).await;
+                    }
+                    self.banned_players.add(entry);
+                    changed = true;
+                }
+                if !changed {
+                    return Err(anyhow!("Nothing changed. The player is already banned"));
+                }
+                self.banned_players.save()
+            }
+            CommandAction::Pardon => {
+                let mut changed = false;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    if let Some(entry) = self.banned_players.remove(&name, uuid) {
+                        changed = true;
+                        source.send_feedback(&format!("Unbanned {}", entry.name)).await;
+                    }
+                }
+                if !changed {
+                    return Err(anyhow!("Nothing changed. The player isn't banned"));
+                }
+                self.banned_players.save()
+            }
+            CommandAction::BanIp => {
+                let reason = context.get_string("reason").unwrap_or("Banned by an operator.");
+                let target = context.get_string("target")?;
+                let ip = match target.parse::<IpAddr>() {
+                    Ok(ip) => ip,
+                    Err(_) => match self.client_by_name(target).await {
+                        Some(client) => client.lock().await.address.ip(),
+                        None => return Err(anyhow!("Invalid IP address or unknown player")),
+                    },
+                };
+                if self.banned_ips.contains(&ip.to_string(), None) {
+                    return Err(anyhow!("Nothing changed. That IP is already banned"));
+                }
+                let entry = IpBanEntry::new(&ip.to_string(), source.name(), reason, None);
+                let mut targets = vec![];
+                for client in self.clients.lock().await.values() {
+                    let locked = client.lock().await;
+                    if locked.address.ip() == ip {
+                        targets.push((locked.name.clone(), client.clone()));
+                    }
+                }
+                let mut names = vec![];
+                for (name, client) in targets {
+                    self.kick_player(&client, Chat::from_text(&entry.kick_message())).await;
+                    names.push(name);
+                }
+                self.banned_ips.add(entry);
+                source.send_feedback(&format!("Banned IP {}: {}", ip, reason)).await;
+                if !names.is_empty() {
+                    source.send_feedback(&format!("This ban affects {} player(s): {}", names.len(), names.join(", "))).await;
+                }
+                self.banned_ips.save()
+            }
+            CommandAction::PardonIp => {
+                let target = context.get_string("target")?;
+                let ip = target.parse::<IpAddr>().map_err(|_| anyhow!("Invalid IP address"))?;
+                if self.banned_ips.remove(&ip.to_string(), None).is_none() {
+                    return Err(anyhow!("Nothing changed. That IP isn't banned"));
+                }
+                source.send_feedback(&format!("Unbanned IP {}", ip)).await;
+                self.banned_ips.save()
+            }
+            CommandAction::BanList { players, ips } => {
+                let mut bans = vec![];
+                if players {
+                    self.banned_players.remove_expired();
+                    bans.extend(self.banned_players.entries().iter().map(|ban| (ban.name.clone(), ban.source.clone(), ban.reason.clone())));
+                }
+                if ips {
+                    self.banned_ips.remove_expired();
+                    bans.extend(self.banned_ips.entries().iter().map(|ban| (ban.ip.clone(), ban.source.clone(), ban.reason.clone())));
+                }
+                if bans.is_empty() {
+                    source.send_feedback("There are no bans").await;
+                } else {
+                    source.send_feedback(&format!("There are {} ban(s):", bans.len())).await;
+                    for (target, by, reason) in bans {
+                        source.send_feedback(&format!("{} was banned by {}: {}", target, by, reason)).await;
+                    }
+                }
+                Ok(())
+            }
+            CommandAction::Reload => {
+                self.reload_lists()?;
+                self.refresh_permissions().await;
+                source.send_feedback("Reloaded the operator, whitelist, ban and permission lists").await;
+                Ok(())
+            }
+            CommandAction::Op => {
+                let mut changed = false;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    if self.ops.contains(&name, uuid) {
+                        continue;
+                    }
+                    self.ops.add(OpEntry { name: name.clone(), uuid, level: self.op_permission_level, bypasses_player_limit: false });
+                    changed = true;
+                    source.send_feedback(&format!("Made {} a server operator", name)).await;
+                }
+                if !changed {
+                    return Err(anyhow!("Nothing changed. The player already is an operator"));
+                }
+                self.ops.save()?;
+                self.refresh_permissions().await;
+                Ok(())
+            }
+            CommandAction::Deop => {
+                let mut changed = false;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    if let Some(entry) = self.ops.remove(&name, uuid) {
+                        changed = true;
+                        source.send_feedback(&format!("Made {} no longer a server operator", entry.name)).await;
+                    }
+                }
+                if !changed {
+                    return Err(anyhow!("Nothing changed. The player is not an operator"));
+                }
+                self.ops.save()?;
+                self.refresh_permissions().await;
+                Ok(())
+            }
+            CommandAction::WhitelistOn | CommandAction::WhitelistOff => {
+                let enable = matches!(context.action, CommandAction::WhitelistOn);
+                if self.whitelist_enabled == enable {
+                    return Err(anyhow!("Whitelist is already turned {}", if enable { "on" } else { "off" }));
+                }
+                self.whitelist_enabled = enable;
+                source.send_feedback(&format!("Whitelist is now turned {}", if enable { "on" } else { "off" })).await;
+                Ok(())
+            }
+            CommandAction::WhitelistList => {
+                let names = self.whitelist.entries().iter().map(|entry| entry.name.clone()).collect::<Vec<String>>();
+                if names.is_empty() {
+                    source.send_feedback("There are no whitelisted players").await;
+                } else {
+                    source.send_feedback(&format!("There are {} whitelisted players: {}", names.len(), names.join(", "))).await;
+                }
+                Ok(())
+            }
+            CommandAction::WhitelistAdd => {
+                let mut changed = false;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    if self.whitelist.contains(&name, uuid) {
+                        continue;
+                    }
+                    self.whitelist.add(UserEntry { name: name.clone(), uuid });
+                    changed = true;
+                    source.send_feedback(&format!("Added {} to the whitelist", name)).await;
+                }
+                if !changed {
+                    return Err(anyhow!("Player is already whitelisted"));
+                }
+                self.whitelist.save()
+            }
+            CommandAction::WhitelistRemove => {
+                let mut changed = false;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    if let Some(entry) = self.whitelist.remove(&name, uuid) {
+                        changed = true;
+                        source.send_feedback(&format!("Removed {} from the whitelist", entry.name)).await;
+                    }
+                }
+                if !changed {
+                    return Err(anyhow!("Player is not whitelisted"));
+                }
+                self.whitelist.save()
+            }
+            CommandAction::WhitelistReload => {
+                self.whitelist.load()?;
+                source.send_feedback("Reloaded the whitelist").await;
+                Ok(())
+            }
+            CommandAction::List { uuids } => {
+                let mut names = self.clients.lock().await.keys()
+                    .map(|key| if uuids { format!("{} ({})", key.0, key.1) } else { key.0.clone() })
+                    .collect::<Vec<String>>();
+                names.sort();
+                source.send_feedback(&format!("There are {} of a max of {} players online: {}", names.len(), self.status.players.max, names.join(", "))).await;
+                Ok(())
+            }
+            CommandAction::Say => {
+                let message = Chat::from_text(context.get_string("message")?);
+                let name = source.name_component().await;
+                self.broadcast_chat(chat::GLOBAL_CHANNEL, None, text::render_template(&self.chat_templates.announcement, &[("name", name), ("message", message)])).await;
+                Ok(())
+            }
+            CommandAction::Message => {
+                let message = Chat::from_text(context.get_string("message")?);
+                for target in self.select_players(source, context.get_entity("targets")?).await? {
+                    self.send_private_message(source, target, &message).await;
+                }
+                Ok(())
+            }
+            CommandAction::Reply => {
+                let message = Chat::from_text(context.get_string("message")?);
+                let reply_to = source.player()?.lock().await.reply_to;
+                let uuid = reply_to.ok_or(anyhow!("There is nobody to reply to"))?;
+                let target = self.client(uuid).await.ok_or(anyhow!("That player is no longer online"))?;
+                self.send_private_message(source, target, &message).await;
+                Ok(())
+            }
+            CommandAction::Ignore => {
+                let player = source.player()?;
+                let mut targets = vec![];
+                for target in self.select_players(source, context.get_entity("target")?).await? {
+                    let target = target.lock().await;
+                    targets.push((target.name.clone(), target.uuid));
+                }
+                let mut client = player.lock().await;
+                for (name, uuid) in targets {
+                    if uuid == client.uuid {
+                        return Err(anyhow!("You can't ignore yourself"));
+                    }
+                    if !client.data.ignore(&name, uuid) {
+                        return Err(anyhow!("You are already ignoring {}", name));
+                    }
+                    client.send_feedback(&format!("You are now ignoring {}", name)).await;
+                }
+                self.save_player_data(&client)
+            }
+            CommandAction::Unignore => {
+                let player = source.player()?;
+                let mut client = player.lock().await;
+                let name = client.data.unignore(context.get_string("target")?).ok_or(anyhow!("You aren't ignoring that player"))?;
+                client.send_feedback(&format!("You are no longer ignoring {}", name)).await;
+                self.save_player_data(&client)
+            }
+            CommandAction::IgnoreList => {
+                let player = source.player()?;
+                let mut client = player.lock().await;
+                let names = client.data.ignored.iter().map(|(name, _)| name.clone()).collect::<Vec<String>>();
+                match names.is_empty() {
+                    true => client.send_feedback("You aren't ignoring anyone").await,
+                    false => client.send_feedback(&format!("You are ignoring {} player(s): {}", names.len(), names.join(", "))).await,
+                }
+                Ok(())
+            }
+            CommandAction::Channel { switch } => {
+                let player = source.player()?;
+                let mut client = player.lock().await;
+                if switch {
+                    self.join_channel(&mut client, context.get_string("channel")?)?;
+                    let feedback = format!("You are now talking in {}", client.channel);
+                    client.send_feedback(&feedback).await;
+                    return Ok(());
+                }
+                let member = client.chat_member();
+                let names = self.channels.iter()
+                    .filter(|channel| channel.filter.can_join(self, &member))
+                    .map(|channel| match channel.name == client.channel {
+                        true => format!("{} (current)", channel.name),
+                        false => channel.name.clone(),
+                    })
+                    .collect::<Vec<String>>();
+                client.send_feedback(&format!("Channels: {}", names.join(", "))).await;
+                Ok(())
+            }
+            CommandAction::TimeSet(time) => {
+                let time = match time {
+                    Some(time) => time,
+                    None => context.get_int("time")? as i64,
+                };
+                for world in self.worlds.iter_mut() {
+                    world.time = time;
+                }
+                self.send_time_updates().await;
+                source.send_feedback(&format!("Set the time to {}", time)).await;
+                Ok(())
+            }
+            CommandAction::TimeAdd => {
+                let ticks = context.get_int("time")? as i64;
+                for world in self.worlds.iter_mut() {
+                    world.time += ticks;
+                }
+                self.send_time_updates().await;
+                let time = self.worlds.default_world_mut().time % DAY_LENGTH;
+                source.send_feedback(&format!("Set the time to {}", time)).await;
+                Ok(())
+            }
+            CommandAction::TimeQuery(query) => {
+                let world = self.worlds.default_world_mut();
+                let value = match query {
+                    TimeQuery::DayTime => world.time % DAY_LENGTH,
+                    TimeQuery::GameTime => world.age % i32::MAX as i64,
+                    TimeQuery::Day => world.time / DAY_LENGTH % i32::MAX as i64,
+                };
+                source.send_feedback(&format!("The time is {}", value)).await;
+                Ok(())
+            }
+            CommandAction::Weather(weather) => {
+                let duration = context.get_int("duration").map(|seconds| seconds * 20).unwrap_or(DEFAULT_WEATHER_TICKS);
+                let world = self.worlds.default_world_mut();
+                world.set_weather(weather, if weather == Weather::Clear { None } else { Some(duration) });
+                let name = world.name.clone();
+                self.send_weather(&name).await;
+                let description = match weather {
+                    Weather::Clear => "clear",
+                    Weather::Rain => "rain",
+                    Weather::Thunder => "rain & thunder",
+                };
+                source.send_feedback(&format!("Set the weather to {}", description)).await;
+                Ok(())
+            }
+            CommandAction::Stop => {
+                source.send_feedback("Stopping the server").await;
+                self.stop().await;
+                Ok(())
+            }
+            CommandAction::SaveAll => {
+                source.send_feedback("Saving the game (this may take a moment!)").await;
+                self.save_all()?;
+                source.send_feedback("Saved the game").await;
+                Ok(())
+            }
+            CommandAction::Permission(action) => self.permission_command(source, &context, action).await,
+            CommandAction::SetWorldSpawn => {
+                let origin = self.command_origin(source).await;
+                let (x, y, z) = match context.get_position("pos") {
+                    Ok(position) => position.resolve_block(&origin),
+                    Err(_) => (origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32),
+                };
+                self.set_world_spawn(&origin.world, IntPosition { x, y: y as i16, z }).await?;
+                source.send_feedback(&format!("Set the world spawn point to {}, {}, {}", x, y, z)).await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Delivers a private message and lets both sides answer each other with `/r`. Players who
+    /// ignore the sender do not receive it, without the sender being told.
+    async fn send_private_message(&self, source: &mut CommandSource<'_>, target: Arc<Mutex<ServerClient>>, message: &Chat) {
+        let sender_name = source.name_component().await;
+        let mut client = target.lock().await;
+        if !source.uuid().map_or(false, |uuid| client.data.is_ignoring(uuid)) {
+            let incoming = text::render_template(&self.chat_templates.whisper_incoming, &[("name", sender_name), ("message", message.clone())]);
+            let _ = client.send_message(incoming, ChatPosition::SystemMessage, None).await;
+            if let Some(uuid) = source.uuid() {
+                client.reply_to = Some(uuid);
+            }
+        }
+        let (target_name, target_uuid) = (client.name_component(), client.uuid);
+        drop(client);
+        if let CommandSource::Player { client, .. } = source {
+            client.lock().await.reply_to = Some(target_uuid);
+        }
+        source.send_chat(text::render_template(&self.chat_templates.whisper_outgoing, &[("name", target_name), ("message", message.clone())])).await;
+    }
+
+    /// Whether the source may use something guarded by the permission. The console and RCON may
+    /// use everything.
+    fn source_allows(&self, source: &CommandSource<'_>, permission: &str) -> bool {
+        match source {
+            CommandSource::Player { name, uuid, .. } => self.has_permission(name, Some(*uuid), permission),
+            _ => true,
+        }
+    }
+
+    async fn permission_command(&mut self, source: &mut CommandSource<'_>, context: &CommandContext<CommandAction>, action: PermissionAction) -> Result<()> {
+        let mut feedback = vec![];
+        match action {
+            PermissionAction::Check => {
+                let node = context.get_string("node")?;
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    let verb = if self.has_permission(&name, uuid, node) { "has" } else { "does not have" };
+                    source.send_feedback(&format!("{} {} permission {}", name, verb, node)).await;
+                }
+                return Ok(());
+            }
+            PermissionAction::PlayerSet | PermissionAction::PlayerUnset | PermissionAction::PlayerAddGroup | PermissionAction::PlayerRemoveGroup => {
+                for (name, uuid) in self.resolve_profiles(source, context.get_entity("targets")?).await? {
+                    let player = self.permissions.player_mut(&name, uuid);
+                    match action {
+                        PermissionAction::PlayerSet => {
+                            let (node, value) = (context.get_string("node")?, context.get_bool("value")?);
+                            player.permissions.set(node, value);
+                            feedback.push(format!("Set permission {} to {} for {}", node, value, name));
+                        }
+                        PermissionAction::PlayerUnset => {
+                            let node = context.get_string("node")?;
+                            if !player.permissions.unset(node) {
+                                return Err(anyhow!("{} has no permission {} set", name, node));
+                            }
+                            feedback.push(format!("Unset permission {} for {}", node, name));
+                        }
+                        PermissionAction::PlayerAddGroup => {
+                            let group = context.get_string("group")?;
+                            if player.groups.iter().any(|existing| existing == group) {
+                                return Err(anyhow!("{} is already in group {}", name, group));
+                            }
+                            player.groups.push(group.to_string());
+                            feedback.push(format!("Added {} to group {}", name, group));
+                        }
+                        _ => {
+                            let group = context.get_string("group")?;
+                            if !player.groups.iter().any(|existing| existing == group) {
+                                return Err(anyhow!("{} is not in group {}", name, group));
+                            }
+                            player.groups.retain(|existing| existing != group);
+                            feedback.push(format!("Removed {} from group {}", name, group));
+                        }
+                    }
+                }
+            }
+            PermissionAction::GroupSet => {
+                let (group, node, value) = (context.get_string("group")?, context.get_string("node")?, context.get_bool("value")?);
+                self.permissions.group_mut(group).permissions.set(node, value);
+                feedback.push(format!("Set permission {} to {} for group {}", node, value, group));
+            }
+            PermissionAction::GroupUnset => {
+                let (group, node) = (context.get_string("group")?, context.get_string("node")?);
+                let unset = self.permissions.group_mut(group).permissions.unset(node);
+                if !unset {
+                    return Err(anyhow!("Group {} has no permission {} set", group, node));
+                }
+                feedback.push(format!("Unset permission {} for group {}", node, group));
+            }
+            PermissionAction::GroupInherit => {
+                let (group, parent) = (context.get_string("group")?, context.get_string("parent")?);
+                let inherits = &mut self.permissions.group_mut(group).inherits;
+                if group == parent || inherits.iter().any(|existing| existing == parent) {
+                    return Err(anyhow!("Group {} already inherits from {}", group, parent));
+                }
+                inherits.push(parent.to_string());
+                feedback.push(format!("Group {} now inherits from {}", group, parent));
+            }
+            PermissionAction::GroupDelete => {
+                let group = context.get_string("group")?;
+                if self.permissions.remove_group(group).is_none() {
+                    return Err(anyhow!("There is no group named {}", group));
+                }
+                feedback.push(format!("Deleted group {}", group));
+            }
+            PermissionAction::Reload => {
+                self.permissions.load()?;
+                self.refresh_permissions().await;
+                source.send_feedback("Reloaded permissions").await;
+                return Ok(());
+            }
+        }
+        self.permissions.save()?;
+        self.refresh_permissions().await;
+        for message in feedback {
+            source.send_feedback(&message).await;
+        }
+        Ok(())
+    }
+
+    async fn gamemode_command(&mut self, source: &mut CommandSource<'_>, context: &CommandContext<CommandAction>, gamemode: GameMode) -> Result<()> {
+        let targets = match context.get_entity("target") {
+            Ok(selector) => self.select_players(source, selector).await?,
+            Err(_) => vec![source.player()?],
+        };
+        let name = gamemode_name(&gamemode);
+        for target in targets {
+            let mut client = target.lock().await;
+            self.set_gamemode(&mut client, gamemode.clone()).await?;
+            if Some(client.uuid) == source.uuid() {
+                drop(client);
+                source.send_feedback(&format!("Set own game mode to {}", name)).await;
+            } else {
+                client.send_feedback(&format!("Your game mode has been updated to {}", name)).await;
+                let target_name = client.name.clone();
+                drop(client);
+                source.send_feedback(&format!("Set {}'s game mode to {}", target_name, name)).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn teleport_command(&mut self, source: &mut CommandSource<'_>, context: &CommandContext<CommandAction>) -> Result<()> {
+        let targets = match context.get_entity("targets") {
+            Ok(selector) => self.select_players(source, selector).await?,
+            Err(_) => vec![source.player()?],
+        };
+        // Entity destinations carry their rotation and world, locations keep the target's rotation.
+        let (destination, keep_rotation, description) = match context.get_entity("destination") {
+            Ok(selector) => {
+                let destination = self.select_players(source, selector).await?.remove(0);
+                let destination = destination.lock().await;
+                (destination.player.position.clone(), false, destination.name.clone())
+            }
+            Err(_) => {
+                let origin = self.command_origin(source).await;
+                let (x, y, z) = context.get_position("location")?.resolve(&origin);
+                (Position { x, y, z, ..origin }, true, format!("{:.2}, {:.2}, {:.2}", x, y, z))
+            }
+        };
+
+        let mut names = vec![];
+        for target in targets {
+            let mut client = target.lock().await;
+            let mut position = destination.clone();
+            if keep_rotation {
+                position.yaw = client.player.position.yaw;
+                position.pitch = client.player.position.pitch;
+            }
+            self.teleport_player(&mut client, position).await?;
+            names.push(client.name.clone());
+        }
+        source.send_feedback(&format!("Teleported {} to {}", describe(&names), description)).await;
+        Ok(())
+    }
+
+    async fn give_command(&mut self, source: &mut CommandSource<'_>, context: &CommandContext<CommandAction>) -> Result<()> {
+        let item = context.get_item("item")?;
+        let count = context.get_int("count").unwrap_or(1);
+        let mut names = vec![];
+        for target in self.select_players(source, context.get_entity("targets")?).await? {
+            let mut client = target.lock().await;
+            let mut remaining = count;
+            while remaining > 0 {
+                let stack = ItemBuilder::new(item).count(remaining.min(item.max_stack as i32) as i8).build();
+                remaining -= stack.item_count as i32;
+                // Item entities do not exist yet, so whatever does not fit is lost.
+                let range = player::MAIN_SLOTS.start..player::HOTBAR_SLOTS.end;
+                if inventory::add_item(&mut client.player.inventory.items, range, stack).is_some() {
+                    break;
+                }
+            }
+            let _ = client.send_inventory().await;
+            names.push(client.name.clone());
+        }
+        source.send_feedback(&format!("Gave {} [{}] to {}", count, item.name, describe(&names))).await;
+        Ok(())
+    }
+
+    /// Where relative coordinates and selectors are resolved from: the player running the
+    /// command, or the spawn of the default world.
+    async fn command_origin(&mut self, source: &CommandSource<'_>) -> Position {
+        if let CommandSource::Player { client, .. } = source {
+            return client.lock().await.player.position.clone();
+        }
+        let world = self.worlds.default_world_mut();
+        Position {
+            x: world.spawn.x as f64 + 0.5,
+            y: world.spawn.y as f64,
+            z: world.spawn.z as f64 + 0.5,
+            yaw: 0.0,
+            pitch: 0.0,
+            on_ground: true,
+            dimension: 0,
+            world: world.name.clone(),
+        }
+    }
+
+    /// The online players an entity argument picks. None of them are locked by the caller.
+    async fn select_players(&mut self, source: &CommandSource<'_>, selector: &EntitySelector) -> Result<Vec<Arc<Mutex<ServerClient>>>> {
+        let origin = self.command_origin(source).await;
+        let mut candidates = vec![];
+        let mut sender = None;
+        for (key, client) in self.clients.lock().await.iter() {
+            if Some(key.1) == source.uuid() {
+                sender = Some(candidates.len());
+            }
+            let locked = client.lock().await;
+            candidates.push(SelectorCandidate {
+                name: locked.name.clone(),
+                gamemode: locked.player.gamemode.clone(),
+                position: locked.player.position.clone(),
+                value: client.clone(),
+            });
+        }
+        let selected = selector.select(candidates, sender, &origin);
+        if selected.is_empty() {
+            return Err(anyhow!("No player was found"));
+        }
+        Ok(selected)
+    }
+
+    /// The names and UUIDs a game profile argument stands for. Plain names may belong to players
+    /// that are offline, whose UUID is then unknown.
+    async fn resolve_profiles(&mut self, source: &CommandSource<'_>, selector: &EntitySelector) -> Result<Vec<(String, Option<UUID4>)>> {
+        if let SelectorTarget::Name(name) = &selector.target {
+            let online = self.clients.lock().await.keys()
+                .find(|key| key.0.eq_ignore_ascii_case(name))
+                .map(|key| (key.0.clone(), Some(key.1)));
+            return Ok(vec![online.unwrap_or((name.clone(), None))]);
+        }
+        let mut profiles = vec![];
+        for client in self.select_players(source, selector).await? {
+            let client = client.lock().await;
+            profiles.push((client.name.clone(), Some(client.uuid)));
+        }
+        Ok(profiles)
+    }
+
+    /// Answers a client asking for suggestions for the command it is typing.
+    pub(super) async fn handle_tab_complete(&self, sender: &mut ServerClient, body: PlayClientTabCompleteSpec) {
+        let command = body.text.strip_prefix('/').unwrap_or(&body.text);
+        let offset = body.text.len() - command.len();
+        let players = self.clients.lock().await.keys().map(|key| key.0.clone()).collect::<Vec<String>>();
+        let (name, uuid) = (sender.name.clone(), sender.uuid);
+        let (start, suggestions) = self.commands.suggest(command, &players, &|permission| self.has_permission(&name, Some(uuid), permission));
+        let matches = suggestions.into_iter().map(|suggestion| TabCompleteMatch { match_: suggestion, tooltip: None }).collect::<Vec<TabCompleteMatch>>();
+        let _ = sender.connection.write_packet(Packet::PlayTabComplete(PlayTabCompleteSpec {
+            id: body.transaction_id,
+            start: VarInt::from((offset + start) as i32),
+            length: VarInt::from((command.len() - start) as i32),
+            matches: CountedArray::from(matches),
+        })).await;
+    }
+}
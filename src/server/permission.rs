@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+use mcproto_rs::uuid::UUID4;
+
+/// Highest operator level, which bypasses every permission check.
+pub const MAX_OP_LEVEL: u8 = 4;
+/// Level needed for permissions that were never registered.
+pub const DEFAULT_LEVEL: u8 = MAX_OP_LEVEL;
+/// Group every player belongs to.
+pub const DEFAULT_GROUP: &str = "default";
+
+/// Entity status that tells a client its operator level is 0, followed by levels 1 to 4.
+const OP_LEVEL_STATUS: u8 = 24;
+
+/// The Entity Status code that tells a client its operator level, which decides what the client
+/// offers in F3 shortcuts and the gamemode switcher.
+pub fn op_level_status(level: u8) -> u8 {
+    OP_LEVEL_STATUS + level.min(MAX_OP_LEVEL)
+}
+
+/// Permissions explicitly granted or denied, by node. A node ending in `*` covers every node
+/// below it, so `endstone.command.*` covers `endstone.command.kick`.
+#[derive(Clone, Debug, Default)]
+pub struct PermissionSet {
+    nodes: HashMap<String, bool>,
+}
+
+impl PermissionSet {
+    pub fn set(&mut self, node: &str, value: bool) {
+        self.nodes.insert(node.to_string(), value);
+    }
+
+    pub fn unset(&mut self, node: &str) -> bool {
+        self.nodes.remove(node).is_some()
+    }
+
+    /// The value of the most specific entry covering the node.
+    pub fn get(&self, node: &str) -> Option<bool> {
+        if let Some(value) = self.nodes.get(node) {
+            return Some(*value);
+        }
+        let mut prefix = node;
+        while let Some(index) = prefix.rfind('.') {
+            prefix = &prefix[..index];
+            if let Some(value) = self.nodes.get(&format!("{}.*", prefix)) {
+                return Some(*value);
+            }
+        }
+        self.nodes.get("*").copied()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (&String, &bool)> {
+        self.nodes.iter()
+    }
+
+    fn to_json(&self) -> Value {
+        Value::Object(self.nodes.iter().map(|(node, value)| (node.clone(), Value::Bool(*value))).collect::<Map<String, Value>>())
+    }
+
+    fn from_json(json: &Value) -> Self {
+        let nodes = json.as_object()
+            .map(|nodes| nodes.iter().filter_map(|(node, value)| Some((node.clone(), value.as_bool()?))).collect())
+            .unwrap_or_default();
+        Self { nodes }
+    }
+}
+
+/// A named set of permissions players can be put in.
+#[derive(Clone, Debug, Default)]
+pub struct Group {
+    pub permissions: PermissionSet,
+    /// Groups this one takes permissions from when it does not set them itself.
+    pub inherits: Vec<String>,
+}
+
+/// The groups and overrides of one player.
+#[derive(Clone, Debug, Default)]
+pub struct PlayerPermissions {
+    pub name: String,
+    pub groups: Vec<String>,
+    pub permissions: PermissionSet,
+}
+
+/// Node-based permissions on top of operator levels, stored in `permissions.json`. A player has a
+/// permission if their own overrides say so, otherwise if one of their groups or the default group
+/// does, and otherwise if their operator level is at least the one the permission was registered
+/// with.
+pub struct Permissions {
+    path: PathBuf,
+    /// Operator level each known permission requires by default.
+    levels: HashMap<String, u8>,
+    groups: HashMap<String, Group>,
+    /// Keyed by UUID, or lowercase name for players whose UUID is unknown.
+    players: HashMap<String, PlayerPermissions>,
+    /// Whether the server authenticates players, making their UUIDs authoritative.
+    online: bool,
+}
+
+fn player_key(name: &str, uuid: Option<UUID4>) -> String {
+    match uuid {
+        Some(uuid) => uuid.to_string(),
+        None => name.to_lowercase(),
+    }
+}
+
+impl Permissions {
+    pub fn new<P: AsRef<Path>>(path: P, online: bool) -> Self {
+        Self { path: path.as_ref().to_path_buf(), levels: HashMap::new(), groups: HashMap::new(), players: HashMap::new(), online }
+    }
+
+    /// A player's entry looked up by name. In online mode entries stored under another UUID
+    /// are skipped when the player's own UUID is known, since names can change hands.
+    fn find_by_name(&self, name: &str, uuid: Option<UUID4>) -> Option<(&String, &PlayerPermissions)> {
+        let any_key = !self.online || uuid.is_none();
+        self.players.iter().find(|(key, player)| player.name.eq_ignore_ascii_case(name) && (any_key || UUID4::parse(key).is_none()))
+    }
+
+    /// Declares a permission and the operator level that grants it when nothing else decides.
+    pub fn register(&mut self, node: &str, level: u8) {
+        self.levels.insert(node.to_string(), level.min(MAX_OP_LEVEL));
+    }
+
+    pub fn level(&self, node: &str) -> u8 {
+        self.levels.get(node).copied().unwrap_or(DEFAULT_LEVEL)
+    }
+
+    pub fn has(&self, name: &str, uuid: Option<UUID4>, op_level: u8, node: &str) -> bool {
+        let player = self.player(name, uuid);
+        if let Some(value) = player.and_then(|player| player.permissions.get(node)) {
+            return value;
+        }
+        let groups = player.map(|player| player.groups.as_slice()).unwrap_or(&[]);
+        for group in groups.iter().map(String::as_str).chain(std::iter::once(DEFAULT_GROUP)) {
+            if let Some(value) = self.group_value(group, node, &mut vec![]) {
+                return value;
+            }
+        }
+        op_level >= self.level(node)
+    }
+
+    /// A group's value for a node, looking through the groups it inherits from. `seen` guards
+    /// against groups inheriting from each other.
+    fn group_value<'a>(&'a self, name: &'a str, node: &str, seen: &mut Vec<&'a str>) -> Option<bool> {
+        if seen.contains(&name) {
+            return None;
+        }
+        seen.push(name);
+        let group = self.groups.get(name)?;
+        if let Some(value) = group.permissions.get(node) {
+            return Some(value);
+        }
+        group.inherits.iter().find_map(|parent| self.group_value(parent, node, seen))
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.get(name)
+    }
+
+    /// The group with the given name, created if it does not exist yet.
+    pub fn group_mut(&mut self, name: &str) -> &mut Group {
+        self.groups.entry(name.to_string()).or_default()
+    }
+
+    pub fn remove_group(&mut self, name: &str) -> Option<Group> {
+        let group = self.groups.remove(name)?;
+        for player in self.players.values_mut() {
+            player.groups.retain(|group| group != name);
+        }
+        Some(group)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &Group)> {
+        self.groups.iter()
+    }
+
+    pub fn player(&self, name: &str, uuid: Option<UUID4>) -> Option<&PlayerPermissions> {
+        self.players.get(&player_key(name, uuid))
+            .or_else(|| self.find_by_name(name, uuid).map(|(_, player)| player))
+    }
+
+    /// The permissions of a player, created if they have none yet. Entries stored by name are
+    /// moved under the UUID once it is known.
+    pub fn player_mut(&mut self, name: &str, uuid: Option<UUID4>) -> &mut PlayerPermissions {
+        let key = player_key(name, uuid);
+        if !self.players.contains_key(&key) {
+            let existing = self.find_by_name(name, uuid).map(|(key, _)| key.clone());
+            let player = existing.and_then(|key| self.players.remove(&key)).unwrap_or_default();
+            self.players.insert(key.clone(), player);
+        }
+        let player = self.players.get_mut(&key).unwrap();
+        player.name = name.to_string();
+        player
+    }
+
+    /// Replaces everything with what is on disk, keeping registered levels. A missing file means
+    /// no groups or overrides.
+    pub fn load(&mut self) -> Result<()> {
+        self.groups.clear();
+        self.players.clear();
+        if !self.path.exists() {
+            return Ok(());
+        }
+        let json: Value = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        let root = json.as_object().ok_or(anyhow!("{} is not an object", self.path.display()))?;
+        for (name, group) in root.get("groups").and_then(Value::as_object).into_iter().flatten() {
+            let inherits = group["inherits"].as_array()
+                .map(|parents| parents.iter().filter_map(|parent| parent.as_str().map(String::from)).collect())
+                .unwrap_or_default();
+            self.groups.insert(name.clone(), Group { permissions: PermissionSet::from_json(&group["permissions"]), inherits });
+        }
+        for (key, player) in root.get("players").and_then(Value::as_object).into_iter().flatten() {
+            let groups = player["groups"].as_array()
+                .map(|groups| groups.iter().filter_map(|group| group.as_str().map(String::from)).collect())
+                .unwrap_or_default();
+            self.players.insert(key.clone(), PlayerPermissions {
+                name: player["name"].as_str().unwrap_or(key).to_string(),
+                groups,
+                permissions: PermissionSet::from_json(&player["permissions"]),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let groups = self.groups.iter().map(|(name, group)| (name.clone(), json!({
+            "inherits": group.inherits,
+            "permissions": group.permissions.to_json(),
+        }))).collect::<Map<String, Value>>();
+        let players = self.players.iter().map(|(key, player)| (key.clone(), json!({
+            "name": player.name,
+            "groups": player.groups,
+            "permissions": player.permissions.to_json(),
+        }))).collect::<Map<String, Value>>();
+        let json = json!({ "groups": groups, "players": players });
+        fs::write(&self.path, serde_json::to_string_pretty(&json)?)?;
+        Ok(())
+    }
+}
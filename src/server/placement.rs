@@ -0,0 +1,175 @@
+use mcproto_rs::v1_16_3::DiggingFace;
+use crate::server::block::{self, Block};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Facing {
+    North,
+    South,
+    West,
+    East,
+    Up,
+    Down,
+}
+
+impl Facing {
+    /// Horizontal direction a player with the given yaw is looking in.
+    pub fn from_yaw(yaw: f32) -> Self {
+        match ((yaw / 90.0 + 0.5).floor() as i32).rem_euclid(4) {
+            0 => Facing::South,
+            1 => Facing::West,
+            2 => Facing::North,
+            _ => Facing::East,
+        }
+    }
+
+    /// Direction a player is looking in, including straight up or down.
+    pub fn from_rotation(yaw: f32, pitch: f32) -> Self {
+        if pitch > 45.0 {
+            Facing::Down
+        } else if pitch < -45.0 {
+            Facing::Up
+        } else {
+            Self::from_yaw(yaw)
+        }
+    }
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Facing::North => Facing::South,
+            Facing::South => Facing::North,
+            Facing::West => Facing::East,
+            Facing::East => Facing::West,
+            Facing::Up => Facing::Down,
+            Facing::Down => Facing::Up,
+        }
+    }
+
+    /// Facing from its index among north, south, west and east.
+    pub fn from_horizontal_index(index: u32) -> Self {
+        match index {
+            0 => Facing::North,
+            1 => Facing::South,
+            2 => Facing::West,
+            _ => Facing::East,
+        }
+    }
+
+    /// The next horizontal facing turning clockwise when seen from above.
+    pub fn clockwise(&self) -> Self {
+        match self {
+            Facing::North => Facing::East,
+            Facing::East => Facing::South,
+            Facing::South => Facing::West,
+            Facing::West => Facing::North,
+            vertical => *vertical,
+        }
+    }
+
+    pub fn offset(&self) -> (i32, i32, i32) {
+        match self {
+            Facing::North => (0, 0, -1),
+            Facing::South => (0, 0, 1),
+            Facing::West => (-1, 0, 0),
+            Facing::East => (1, 0, 0),
+            Facing::Up => (0, 1, 0),
+            Facing::Down => (0, -1, 0),
+        }
+    }
+
+    /// Index among north, south, west and east, the order horizontal facings take in block states.
+    pub fn horizontal_index(&self) -> u32 {
+        match self {
+            Facing::North | Facing::Up | Facing::Down => 0,
+            Facing::South => 1,
+            Facing::West => 2,
+            Facing::East => 3,
+        }
+    }
+}
+
+/// Offset to the block on the other side of a clicked face.
+pub fn face_offset(face: &DiggingFace) -> (i32, i32, i32) {
+    match face {
+        DiggingFace::Bottom => (0, -1, 0),
+        DiggingFace::Top => (0, 1, 0),
+        DiggingFace::North => (0, 0, -1),
+        DiggingFace::South => (0, 0, 1),
+        DiggingFace::West => (-1, 0, 0),
+        DiggingFace::East => (1, 0, 0),
+    }
+}
+
+/// Blocks that a placed block simply replaces instead of being placed next to.
+pub fn is_replaceable(state: u32) -> bool {
+    if state == block::AIR {
+        return true;
+    }
+    match block::get_block(state) {
+        Some(block) => block.name == "minecraft:water" || block.name == "minecraft:lava" || block.name == "minecraft:fire",
+        None => false,
+    }
+}
+
+/// The state a block is placed with, given the face it was placed against, the height on that
+/// face that was clicked and the rotation of the player placing it.
+pub fn placement_state(block: &Block, face: &DiggingFace, cursor_y: f32, yaw: f32, pitch: f32) -> u32 {
+    let name = block.name.trim_start_matches("minecraft:");
+    let facing = Facing::from_yaw(yaw);
+
+    if name.ends_with("_log") {
+        let axis = match face {
+            DiggingFace::West | DiggingFace::East => 0,
+            DiggingFace::Bottom | DiggingFace::Top => 1,
+            DiggingFace::North | DiggingFace::South => 2,
+        };
+        return block.min_state + axis;
+    }
+
+    if name.ends_with("_bed") {
+        // Foot half, not occupied, with the head further along the player's facing.
+        return block.min_state + facing.horizontal_index() * 4 + 3;
+    }
+
+    if name.ends_with("_stairs") {
+        let top = *face == DiggingFace::Bottom || (*face != DiggingFace::Top && cursor_y > 0.5);
+        let half = if top { 0 } else { 1 };
+        // Straight and not waterlogged.
+        return block.min_state + facing.horizontal_index() * 20 + half * 10 + 1;
+    }
+
+    match name {
+        // Single and not waterlogged.
+        "chest" => block.min_state + facing.opposite().horizontal_index() * 6 + 1,
+        // Not lit.
+        "furnace" => block.min_state + facing.opposite().horizontal_index() * 2 + 1,
+        "barrel" => {
+            let index = match Facing::from_rotation(yaw, pitch).opposite() {
+                Facing::North => 0,
+                Facing::East => 1,
+                Facing::South => 2,
+                Facing::West => 3,
+                Facing::Up => 4,
+                Facing::Down => 5,
+            };
+            // Closed.
+            block.min_state + index * 2 + 1
+        }
+        _ => block.default_state,
+    }
+}
+
+/// The other half of a bed given the state of one half, as its offset and state.
+pub fn bed_other_half(state: u32) -> Option<((i32, i32, i32), u32)> {
+    let block = block::get_block(state)?;
+    if !block.name.ends_with("_bed") {
+        return None;
+    }
+    let offset = state - block.min_state;
+    let (dx, dy, dz) = Facing::from_horizontal_index(offset / 4).offset();
+    if offset % 2 == 1 {
+        // Foot, the head lies in the facing direction.
+        Some(((dx, dy, dz), state - 1))
+    } else {
+        Some(((-dx, -dy, -dz), state + 1))
+    }
+}
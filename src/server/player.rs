@@ -1,4 +1,6 @@
 use std::borrow::Borrow;
+use std::fmt::Debug;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use mcproto_rs::protocol::{PacketDirection};
@@ -7,18 +9,23 @@ use mcproto_rs::v1_16_3::{GameMode, Packet753 as Packet, RawPacket753 as RawPack
 use anyhow::Result;
 use mcproto_rs::uuid::UUID4;
 use std::thread;
-use mcproto_rs::types::{ItemStack, Slot, VarInt};
-
-pub struct Health {
-    pub health: i32,
-    pub hunger: i32,
-    pub saturation: i32,
-}
+use mcproto_rs::types::{Chat, ItemStack, Slot, VarInt};
+use mcproto_rs::v1_16_3::{ClientDisplayedSkinParts, ClientMainHand, EntityEffectKind, EntityMetadata, EntityMetadataField, EntityMetadataFieldData, Hand, PlayerAbilityFlags, PlayEntityMetadataSpec, PlayServerPlayerAbilitiesSpec};
+use crate::server::movement::MovementState;
+use crate::server::portal::PortalState;
+use crate::server::digging::DigState;
+use crate::server::auth::ProfileProperty;
+use crate::server::health::Health;
+use crate::server::spawn::SpawnPoint;
 
+#[derive(Clone, Debug)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
     pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
     pub dimension: i32,
     pub world: String,
 }
@@ -26,87 +33,394 @@ pub struct Position {
 pub struct Player {
     pub name: String,
     pub uuid: UUID4,
+    pub properties: Vec<ProfileProperty>,
+    /// Name shown in the tab list instead of `name` when set.
+    pub display_name: Option<Chat>,
+    /// Round trip time of the last keep alive, in milliseconds.
+    pub ping: i32,
     pub entity_id: i32,
     pub position: Position,
     pub health: Health,
     pub inventory: PlayerInventory,
     pub gamemode: GameMode,
+    /// Gamemode before the last switch, shown by the client's F3+F4 menu.
+    pub previous_gamemode: Option<GameMode>,
+    pub flying: bool,
+    pub allow_flight: bool,
+    pub sprinting: bool,
+    pub sneaking: bool,
+    pub effects: Vec<ActiveEffect>,
+    pub movement: MovementState,
+    pub portal: PortalState,
+    /// Hotbar slot currently held, from 0 to 8.
+    pub selected_slot: u8,
+    /// Block being dug in survival, if any.
+    pub digging: Option<DigState>,
+    /// Bed or respawn anchor the player last used.
+    pub spawn_point: Option<SpawnPoint>,
+    /// Outer skin layers the player shows, from their client settings.
+    pub skin_parts: ClientDisplayedSkinParts,
+    pub main_hand: ClientMainHand,
+}
 
+/// A potion effect currently applied to a player.
+#[derive(Clone, Debug)]
+pub struct ActiveEffect {
+    pub kind: EntityEffectKind,
+    /// Zero based, so Speed II has an amplifier of 1.
+    pub amplifier: i8,
+    pub duration_ticks: i32,
 }
 
 impl Player {
-    pub fn new(name: String, uuid: UUID4, entity_id: i32) -> Self {
+    /// A player joining in the given gamemode, flying only if it is spectator.
+    pub fn new(name: String, uuid: UUID4, entity_id: i32, gamemode: GameMode) -> Self {
+        let position = Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            on_ground: false,
+            dimension: 0,
+            world: "world".to_string(),
+        };
+
         Self {
             name,
             uuid,
+            properties: vec![],
+            display_name: None,
+            ping: 0,
             entity_id,
-            position: Position {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-                dimension: 0,
-                world: "world".to_string(),
-            },
-            health: Health {
-                health: 20,
-                hunger: 20,
-                saturation: 20,
-            },
+            position: position.clone(),
+            health: Health::default(),
             inventory: PlayerInventory::new_empty(),
-            gamemode: GameMode::Spectator,
+            flying: gamemode == GameMode::Spectator,
+            gamemode,
+            previous_gamemode: None,
+            allow_flight: false,
+            sprinting: false,
+            sneaking: false,
+            effects: vec![],
+            movement: MovementState::new(position),
+            portal: PortalState::default(),
+            selected_slot: 0,
+            digging: None,
+            spawn_point: None,
+            skin_parts: ClientDisplayedSkinParts::default(),
+            main_hand: ClientMainHand::Right,
         }
     }
+
+    /// Inventory slot of the item held in the given hand.
+    pub fn held_slot(&self, hand: &Hand) -> usize {
+        match hand {
+            Hand::MainHand => HOTBAR_SLOTS.start + self.selected_slot as usize,
+            Hand::OffHand => OFFHAND_SLOT,
+        }
+    }
+
+    pub fn held_item(&self, hand: &Hand) -> Slot {
+        self.inventory.get(self.held_slot(hand))
+    }
+
+    /// Switches gamemode, remembering the old one. Players that may no longer fly are dropped.
+    pub fn set_gamemode(&mut self, gamemode: GameMode) {
+        self.previous_gamemode = Some(self.gamemode.clone());
+        self.gamemode = gamemode;
+        self.flying = self.gamemode == GameMode::Spectator || (self.flying && self.may_fly());
+        self.digging = None;
+    }
+
+    /// Whether the player is allowed to fly, either by gamemode or by `allow_flight`.
+    pub fn may_fly(&self) -> bool {
+        self.allow_flight || self.gamemode == GameMode::Creative || self.gamemode == GameMode::Spectator
+    }
+
+    /// The abilities packet matching the player's gamemode.
+    pub fn abilities(&self) -> Packet {
+        let mut flags = PlayerAbilityFlags::default();
+        let creative = self.gamemode == GameMode::Creative;
+        flags.set_invulnerable(creative || self.gamemode == GameMode::Spectator);
+        flags.set_flying(self.flying);
+        flags.set_flight_allowed(self.may_fly());
+        flags.set_instant_break(creative);
+        Packet::PlayServerPlayerAbilities(PlayServerPlayerAbilitiesSpec {
+            flags,
+            flying_speed: FLYING_SPEED,
+            field_of_view_modifier: WALKING_SPEED,
+        })
+    }
+
+    /// The Entity Metadata packet carrying the player's skin parts and main hand, which other
+    /// clients need to render them.
+    pub fn metadata(&self) -> Packet {
+        let main_hand = match self.main_hand {
+            ClientMainHand::Left => 0,
+            ClientMainHand::Right => 1,
+        };
+        Packet::PlayEntityMetadata(PlayEntityMetadataSpec {
+            entity_id: VarInt::from(self.entity_id),
+            metadata: EntityMetadata {
+                fields: vec![
+                    EntityMetadataField { index: SKIN_PARTS_METADATA, data: EntityMetadataFieldData::Byte(self.skin_parts.0 as i8) },
+                    EntityMetadataField { index: MAIN_HAND_METADATA, data: EntityMetadataFieldData::Byte(main_hand) },
+                ],
+            },
+        })
+    }
+
+    /// Level of the given effect starting at 1, or 0 if the effect is not active.
+    pub fn effect_level(&self, kind: &EntityEffectKind) -> i32 {
+        self.effects
+            .iter()
+            .find(|effect| &effect.kind == kind)
+            .map(|effect| effect.amplifier as i32 + 1)
+            .unwrap_or(0)
+    }
+}
+
+/// Entity metadata indices of player fields.
+const SKIN_PARTS_METADATA: u8 = 16;
+const MAIN_HAND_METADATA: u8 = 17;
+
+/// Default speeds sent with the player's abilities.
+const FLYING_SPEED: f32 = 0.05;
+const WALKING_SPEED: f32 = 0.1;
+
+/// Every gamemode under the name commands use for it.
+pub const GAMEMODES: [(&str, GameMode); 4] = [
+    ("survival", GameMode::Survival),
+    ("creative", GameMode::Creative),
+    ("adventure", GameMode::Adventure),
+    ("spectator", GameMode::Spectator),
+];
+
+/// Name of a gamemode as the vanilla client shows it.
+pub fn gamemode_name(gamemode: &GameMode) -> &'static str {
+    match gamemode {
+        GameMode::Survival => "Survival Mode",
+        GameMode::Creative => "Creative Mode",
+        GameMode::Adventure => "Adventure Mode",
+        GameMode::Spectator => "Spectator Mode",
+    }
 }
 
+pub const INVENTORY_SIZE: usize = 46;
+pub const CRAFTING_OUTPUT_SLOT: usize = 0;
+pub const CRAFTING_INPUT_SLOTS: Range<usize> = 1..5;
+pub const ARMOR_SLOTS: Range<usize> = 5..9;
+pub const MAIN_SLOTS: Range<usize> = 9..36;
+pub const HOTBAR_SLOTS: Range<usize> = 36..45;
+pub const OFFHAND_SLOT: usize = 45;
+
+const EMPTY: Slot = None;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArmorSlot {
+    Head,
+    Chest,
+    Legs,
+    Feet,
+}
+
+impl ArmorSlot {
+    pub fn index(&self) -> usize {
+        ARMOR_SLOTS.start + *self as usize
+    }
+}
+
+/// The player's inventory, laid out exactly like the protocol's player window: crafting output,
+/// 2x2 crafting input, armor from head to feet, the main inventory, the hotbar and the offhand.
 pub struct PlayerInventory {
-    pub items: [Slot; 44],
+    pub items: [Slot; INVENTORY_SIZE],
 }
 
 impl PlayerInventory {
-    pub fn new(items: [Slot; 44]) -> Self {
+    pub fn new(items: [Slot; INVENTORY_SIZE]) -> Self {
         Self { items }
     }
 
     pub fn new_empty() -> Self {
-        //         let items: [Option<ItemStack>; 44] = [stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone(), stack.clone()];
-        //         let items: [Option<ItemStack>; 44] = [None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None];
-        let items: [Option<ItemStack>; 44] = [None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None];
-        Self { items }
+        Self {
+            items: [EMPTY; INVENTORY_SIZE],
+        }
+    }
+
+    pub fn get(&self, slot: usize) -> Slot {
+        self.items.get(slot).cloned().flatten()
+    }
+
+    pub fn set(&mut self, slot: usize, item: Slot) {
+        if let Some(existing) = self.items.get_mut(slot) {
+            *existing = item;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items = [EMPTY; INVENTORY_SIZE];
     }
 
     pub fn get_armor(&self) -> [Slot; 4] {
-        self.items.clone()[5..8]
-            .to_owned()
-            .try_into()
-            .expect("Inventory did not have enough slots.")
+        self.range(ARMOR_SLOTS)
+    }
+
+    pub fn get_armor_piece(&self, piece: ArmorSlot) -> Slot {
+        self.get(piece.index())
+    }
+
+    pub fn set_armor_piece(&mut self, piece: ArmorSlot, item: Slot) {
+        self.set(piece.index(), item);
     }
 
     pub fn get_inventory(&self) -> [Slot; 27] {
-        self.items.clone()[9..35]
-            .to_owned()
-            .try_into()
-            .expect("Inventory did not have enough slots.")
+        self.range(MAIN_SLOTS)
     }
 
     pub fn get_hotbar(&self) -> [Slot; 9] {
-        self.items.clone()[36..44]
-            .to_owned()
-            .try_into()
-            .expect("Inventory did not have enough slots.")
+        self.range(HOTBAR_SLOTS)
+    }
+
+    /// Item in the given hotbar slot, from 0 to 8.
+    pub fn get_hotbar_slot(&self, index: usize) -> Slot {
+        if index >= HOTBAR_SLOTS.len() {
+            return None;
+        }
+        self.get(HOTBAR_SLOTS.start + index)
+    }
+
+    pub fn set_hotbar_slot(&mut self, index: usize, item: Slot) {
+        if index < HOTBAR_SLOTS.len() {
+            self.set(HOTBAR_SLOTS.start + index, item);
+        }
     }
 
     pub fn get_offhand(&self) -> Slot {
-        self.items[45].clone()
+        self.get(OFFHAND_SLOT)
+    }
+
+    pub fn set_offhand(&mut self, item: Slot) {
+        self.set(OFFHAND_SLOT, item);
     }
 
     pub fn get_crafting_input(&self) -> [Slot; 4] {
-        self.items.clone()[1..4]
-            .to_owned()
+        self.range(CRAFTING_INPUT_SLOTS)
+    }
+
+    /// Sets one of the four crafting input slots, numbered left to right, top to bottom.
+    pub fn set_crafting_input(&mut self, index: usize, item: Slot) {
+        if index < CRAFTING_INPUT_SLOTS.len() {
+            self.set(CRAFTING_INPUT_SLOTS.start + index, item);
+        }
+    }
+
+    pub fn get_crafting_output(&self) -> Slot {
+        self.get(CRAFTING_OUTPUT_SLOT)
+    }
+
+    pub fn set_crafting_output(&mut self, item: Slot) {
+        self.set(CRAFTING_OUTPUT_SLOT, item);
+    }
+
+    fn range<T: TryFrom<Vec<Slot>>>(&self, range: Range<usize>) -> T where T::Error: Debug {
+        self.items[range]
+            .to_vec()
             .try_into()
             .expect("Inventory did not have enough slots.")
     }
+}
 
-    pub fn get_crafting_output(&self) -> Slot {
-        self.items[0].clone()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: i32) -> Slot {
+        Some(ItemStack { item_id: VarInt::from(id), item_count: 1, nbt: None })
     }
-}
\ No newline at end of file
+
+    /// An inventory whose slot `n` holds item `n`, so accessors can be checked against the layout.
+    fn numbered() -> PlayerInventory {
+        let mut inventory = PlayerInventory::new_empty();
+        for slot in 0..INVENTORY_SIZE {
+            inventory.set(slot, item(slot as i32));
+        }
+        inventory
+    }
+
+    fn numbered_range(range: Range<usize>) -> Vec<Slot> {
+        range.map(|slot| item(slot as i32)).collect()
+    }
+
+    #[test]
+    fn getters_read_their_ranges() {
+        let inventory = numbered();
+        assert_eq!(inventory.get_crafting_output(), item(CRAFTING_OUTPUT_SLOT as i32));
+        assert_eq!(inventory.get_crafting_input().to_vec(), numbered_range(CRAFTING_INPUT_SLOTS));
+        assert_eq!(inventory.get_armor().to_vec(), numbered_range(ARMOR_SLOTS));
+        assert_eq!(inventory.get_inventory().to_vec(), numbered_range(MAIN_SLOTS));
+        assert_eq!(inventory.get_hotbar().to_vec(), numbered_range(HOTBAR_SLOTS));
+        assert_eq!(inventory.get_offhand(), item(OFFHAND_SLOT as i32));
+        for (index, slot) in HOTBAR_SLOTS.enumerate() {
+            assert_eq!(inventory.get_hotbar_slot(index), item(slot as i32));
+        }
+    }
+
+    #[test]
+    fn armor_pieces_go_from_head_to_feet() {
+        let mut inventory = PlayerInventory::new_empty();
+        let pieces = [ArmorSlot::Head, ArmorSlot::Chest, ArmorSlot::Legs, ArmorSlot::Feet];
+        for (index, piece) in pieces.iter().enumerate() {
+            inventory.set_armor_piece(*piece, item(100 + index as i32));
+        }
+        for (index, piece) in pieces.iter().enumerate() {
+            assert_eq!(piece.index(), ARMOR_SLOTS.start + index);
+            assert_eq!(inventory.get_armor_piece(*piece), item(100 + index as i32));
+            assert_eq!(inventory.items[ARMOR_SLOTS.start + index], item(100 + index as i32));
+        }
+    }
+
+    #[test]
+    fn setters_write_their_slots() {
+        let mut inventory = PlayerInventory::new_empty();
+        inventory.set_crafting_output(item(1));
+        assert_eq!(inventory.items[CRAFTING_OUTPUT_SLOT], item(1));
+
+        for index in 0..CRAFTING_INPUT_SLOTS.len() {
+            inventory.set_crafting_input(index, item(10 + index as i32));
+            assert_eq!(inventory.items[CRAFTING_INPUT_SLOTS.start + index], item(10 + index as i32));
+        }
+
+        for slot in MAIN_SLOTS {
+            inventory.set(slot, item(slot as i32));
+        }
+        assert_eq!(inventory.get_inventory().to_vec(), numbered_range(MAIN_SLOTS));
+
+        for index in 0..HOTBAR_SLOTS.len() {
+            inventory.set_hotbar_slot(index, item(50 + index as i32));
+            assert_eq!(inventory.items[HOTBAR_SLOTS.start + index], item(50 + index as i32));
+        }
+
+        inventory.set_offhand(item(2));
+        assert_eq!(inventory.items[OFFHAND_SLOT], item(2));
+    }
+
+    #[test]
+    fn out_of_range_indices_do_nothing() {
+        let mut inventory = numbered();
+        inventory.set(INVENTORY_SIZE, item(999));
+        inventory.set_crafting_input(CRAFTING_INPUT_SLOTS.len(), item(999));
+        inventory.set_hotbar_slot(HOTBAR_SLOTS.len(), item(999));
+        assert_eq!(inventory.items.to_vec(), numbered().items.to_vec());
+
+        assert_eq!(inventory.get(INVENTORY_SIZE), None);
+        assert_eq!(inventory.get_hotbar_slot(HOTBAR_SLOTS.len()), None);
+    }
+
+    #[test]
+    fn clear_empties_every_slot() {
+        let mut inventory = numbered();
+        inventory.clear();
+        assert!(inventory.items.iter().all(Option::is_none));
+    }
+}
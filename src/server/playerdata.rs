@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde_json::{json, Value};
+use mcproto_rs::uuid::UUID4;
+
+/// What the server remembers about a player between sessions.
+#[derive(Clone, Debug, Default)]
+pub struct PlayerData {
+    /// Players whose chat and private messages are hidden from this one, by name and UUID.
+    pub ignored: Vec<(String, UUID4)>,
+}
+
+impl PlayerData {
+    pub fn is_ignoring(&self, uuid: UUID4) -> bool {
+        self.ignored.iter().any(|(_, ignored)| *ignored == uuid)
+    }
+
+    /// Returns false if the player was already ignored.
+    pub fn ignore(&mut self, name: &str, uuid: UUID4) -> bool {
+        if self.is_ignoring(uuid) {
+            return false;
+        }
+        self.ignored.push((name.to_string(), uuid));
+        true
+    }
+
+    /// Stops ignoring a player by name, ignoring case, returning the name as it was stored.
+    pub fn unignore(&mut self, name: &str) -> Option<String> {
+        let index = self.ignored.iter().position(|(ignored, _)| ignored.eq_ignore_ascii_case(name))?;
+        Some(self.ignored.remove(index).0)
+    }
+
+    fn to_json(&self) -> Value {
+        let ignored = self.ignored.iter().map(|(name, uuid)| json!({ "name": name, "uuid": uuid.to_string() })).collect::<Vec<Value>>();
+        json!({ "ignored": ignored })
+    }
+
+    fn from_json(json: &Value) -> Self {
+        let ignored = json["ignored"].as_array()
+            .map(|ignored| ignored.iter().filter_map(|entry| Some((entry["name"].as_str()?.to_string(), entry["uuid"].as_str().and_then(UUID4::parse)?))).collect())
+            .unwrap_or_default();
+        Self { ignored }
+    }
+}
+
+/// Player data kept as one `<uuid>.json` file per player in a directory.
+pub struct PlayerDataStore {
+    dir: PathBuf,
+}
+
+impl PlayerDataStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    fn path(&self, uuid: UUID4) -> PathBuf {
+        self.dir.join(format!("{}.json", uuid))
+    }
+
+    /// The saved data of a player, or empty data for players that have none yet.
+    pub fn load(&self, uuid: UUID4) -> Result<PlayerData> {
+        let path = self.path(uuid);
+        if !path.exists() {
+            return Ok(PlayerData::default());
+        }
+        let json: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(PlayerData::from_json(&json))
+    }
+
+    pub fn save(&self, uuid: UUID4, data: &PlayerData) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(uuid), serde_json::to_string_pretty(&data.to_json())?)?;
+        Ok(())
+    }
+}
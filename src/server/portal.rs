@@ -0,0 +1,351 @@
+use std::time::{Duration, Instant};
+use crate::server::block;
+use crate::server::dimension::DimensionType;
+use crate::server::player::Position;
+use crate::server::world::{BlockChange, World};
+
+/// Time a player has to stand in a nether portal before travelling, unless they are in creative.
+pub const NETHER_PORTAL_DELAY: Duration = Duration::from_secs(4);
+/// Radius searched for an existing portal in an overworld-scaled dimension. Dimensions with a
+/// larger coordinate scale search proportionally less, 16 blocks in the nether.
+pub const PORTAL_SEARCH_RADIUS: i32 = 128;
+/// Block players arrive on in the end, in the middle of the obsidian platform.
+pub const END_SPAWN: (i32, i32, i32) = (100, 49, 0);
+
+const MIN_PORTAL_WIDTH: i32 = 2;
+const MAX_PORTAL_WIDTH: i32 = 21;
+const MIN_PORTAL_HEIGHT: i32 = 3;
+const MAX_PORTAL_HEIGHT: i32 = 21;
+/// How far from the centre of the end a gateway throws players.
+const GATEWAY_EXIT_DISTANCE: f64 = 1024.0;
+/// Height of the island built when a gateway exit lands in the void.
+const GATEWAY_ISLAND_HEIGHT: i32 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PortalKind {
+    Nether,
+    End,
+    Gateway,
+}
+
+/// Horizontal axis a nether portal's plane runs along.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Z,
+}
+
+impl Axis {
+    fn offset(&self) -> (i32, i32) {
+        match self {
+            Axis::X => (1, 0),
+            Axis::Z => (0, 1),
+        }
+    }
+
+    fn portal_state(&self) -> u32 {
+        match self {
+            Axis::X => block::NETHER_PORTAL_X,
+            Axis::Z => block::NETHER_PORTAL_Z,
+        }
+    }
+}
+
+/// The inside of a nether portal frame. `x`, `y` and `z` is the lowest interior block closest to
+/// the negative end of the axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortalFrame {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub axis: Axis,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl PortalFrame {
+    pub fn interior(&self) -> Vec<(i32, i32, i32)> {
+        let (dx, dz) = self.axis.offset();
+        let mut blocks = vec![];
+        for i in 0..self.width {
+            for j in 0..self.height {
+                blocks.push((self.x + dx * i, self.y + j, self.z + dz * i));
+            }
+        }
+        blocks
+    }
+}
+
+/// How long a player has been standing in a portal. Players have to step out of a portal before
+/// it can send them anywhere again, so they do not bounce straight back on arrival.
+#[derive(Clone, Debug, Default)]
+pub struct PortalState {
+    entered: Option<Instant>,
+    travelled: bool,
+}
+
+impl PortalState {
+    /// Updates the state with the portal the player is currently in, returning the portal to
+    /// travel through once the player is allowed to.
+    pub fn update(&mut self, portal: Option<PortalKind>, instant: bool) -> Option<PortalKind> {
+        let portal = match portal {
+            Some(portal) => portal,
+            None => {
+                self.entered = None;
+                self.travelled = false;
+                return None;
+            }
+        };
+
+        if self.travelled {
+            return None;
+        }
+
+        let entered = *self.entered.get_or_insert_with(Instant::now);
+        if portal != PortalKind::Nether || instant || entered.elapsed() >= NETHER_PORTAL_DELAY {
+            self.entered = None;
+            self.travelled = true;
+            Some(portal)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the portal at a player's feet or head, if any.
+pub fn portal_at(world: &mut World, position: &Position) -> Option<PortalKind> {
+    let x = position.x.floor() as i32;
+    let y = position.y.floor() as i32;
+    let z = position.z.floor() as i32;
+
+    for block_y in y..=y + 1 {
+        match world.get_block(x, block_y, z) {
+            block::NETHER_PORTAL_X | block::NETHER_PORTAL_Z => return Some(PortalKind::Nether),
+            block::END_PORTAL => return Some(PortalKind::End),
+            block::END_GATEWAY => return Some(PortalKind::Gateway),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Finds the obsidian frame around the given block, trying both axes.
+pub fn find_frame(world: &mut World, x: i32, y: i32, z: i32) -> Option<PortalFrame> {
+    find_frame_along(world, x, y, z, Axis::X).or_else(|| find_frame_along(world, x, y, z, Axis::Z))
+}
+
+fn find_frame_along(world: &mut World, x: i32, y: i32, z: i32, axis: Axis) -> Option<PortalFrame> {
+    let (dx, dz) = axis.offset();
+    if !is_open(world.get_block(x, y, z)) {
+        return None;
+    }
+
+    let mut bottom = y;
+    while is_open(world.get_block(x, bottom - 1, z)) {
+        bottom -= 1;
+        if y - bottom > MAX_PORTAL_HEIGHT {
+            return None;
+        }
+    }
+
+    let mut start = 0;
+    while is_open(world.get_block(x - dx * (start + 1), bottom, z - dz * (start + 1))) {
+        start += 1;
+        if start > MAX_PORTAL_WIDTH {
+            return None;
+        }
+    }
+    let (start_x, start_z) = (x - dx * start, z - dz * start);
+    if world.get_block(start_x - dx, bottom, start_z - dz) != block::OBSIDIAN {
+        return None;
+    }
+
+    let mut width = 0;
+    while width <= MAX_PORTAL_WIDTH && is_open(world.get_block(start_x + dx * width, bottom, start_z + dz * width)) {
+        width += 1;
+    }
+    if width < MIN_PORTAL_WIDTH || width > MAX_PORTAL_WIDTH || world.get_block(start_x + dx * width, bottom, start_z + dz * width) != block::OBSIDIAN {
+        return None;
+    }
+
+    if !(0..width).all(|i| world.get_block(start_x + dx * i, bottom - 1, start_z + dz * i) == block::OBSIDIAN) {
+        return None;
+    }
+
+    let mut height = 0;
+    loop {
+        if height > MAX_PORTAL_HEIGHT {
+            return None;
+        }
+        let row = bottom + height;
+        if (0..width).all(|i| is_open(world.get_block(start_x + dx * i, row, start_z + dz * i))) {
+            let sides = world.get_block(start_x - dx, row, start_z - dz) == block::OBSIDIAN
+                && world.get_block(start_x + dx * width, row, start_z + dz * width) == block::OBSIDIAN;
+            if !sides {
+                return None;
+            }
+            height += 1;
+        } else if (0..width).all(|i| world.get_block(start_x + dx * i, row, start_z + dz * i) == block::OBSIDIAN) {
+            break;
+        } else {
+            return None;
+        }
+    }
+
+    if height < MIN_PORTAL_HEIGHT {
+        return None;
+    }
+
+    Some(PortalFrame {
+        x: start_x,
+        y: bottom,
+        z: start_z,
+        axis,
+        width,
+        height,
+    })
+}
+
+/// Lights the frame around the given block, filling it with portal blocks.
+pub fn ignite(world: &mut World, x: i32, y: i32, z: i32) -> Option<Vec<BlockChange>> {
+    let frame = find_frame(world, x, y, z)?;
+    let state = frame.axis.portal_state();
+    let mut changes = vec![];
+    for (x, y, z) in frame.interior() {
+        set(world, &mut changes, x, y, z, state);
+    }
+    Some(changes)
+}
+
+/// Builds a lit 2x3 portal with its lowest interior block at the given position, clearing room
+/// on either side and adding obsidian ledges to stand on.
+pub fn create_portal(world: &mut World, x: i32, y: i32, z: i32, axis: Axis) -> Vec<BlockChange> {
+    let (dx, dz) = axis.offset();
+    let (side_x, side_z) = (dz, dx);
+    let mut changes = vec![];
+
+    for i in -1..=MIN_PORTAL_WIDTH {
+        for j in -1..=MIN_PORTAL_HEIGHT {
+            let border = i == -1 || i == MIN_PORTAL_WIDTH || j == -1 || j == MIN_PORTAL_HEIGHT;
+            let state = if border { block::OBSIDIAN } else { axis.portal_state() };
+            set(world, &mut changes, x + dx * i, y + j, z + dz * i, state);
+        }
+    }
+
+    for i in 0..MIN_PORTAL_WIDTH {
+        for side in [-1, 1].iter() {
+            let (block_x, block_z) = (x + dx * i + side_x * side, z + dz * i + side_z * side);
+            for j in 0..MIN_PORTAL_HEIGHT {
+                set(world, &mut changes, block_x, y + j, block_z, block::AIR);
+            }
+            if !block::is_solid(world.get_block(block_x, y - 1, block_z)) {
+                set(world, &mut changes, block_x, y - 1, block_z, block::OBSIDIAN);
+            }
+        }
+    }
+    changes
+}
+
+/// Finds the closest lit nether portal, returning its lowest portal block.
+pub fn find_portal(world: &mut World, x: i32, z: i32, radius: i32) -> Option<(i32, i32, i32)> {
+    let (x, mut y, z) = world.find_block(x, z, radius, is_nether_portal)?;
+    while is_nether_portal(world.get_block(x, y - 1, z)) {
+        y -= 1;
+    }
+    Some((x, y, z))
+}
+
+/// Scales horizontal coordinates between dimensions, so one block in the nether covers eight in
+/// the overworld.
+pub fn scale_position(from: &DimensionType, to: &DimensionType, x: f64, z: f64) -> (f64, f64) {
+    let scale = from.coordinate_scale / to.coordinate_scale;
+    (x * scale, z * scale)
+}
+
+/// Where a player leaving through a nether portal arrives in `target`, linking to the closest
+/// portal there or building one when there is none in range.
+pub fn nether_destination(target: &mut World, from: &DimensionType, position: &Position) -> (Position, Vec<BlockChange>) {
+    let (x, z) = scale_position(from, &target.dimension_type, position.x, position.z);
+    let (x, z) = (x.floor() as i32, z.floor() as i32);
+    let radius = (PORTAL_SEARCH_RADIUS as f64 / target.dimension_type.coordinate_scale) as i32;
+
+    let ((x, y, z), changes) = match find_portal(target, x, z, radius) {
+        Some(portal) => (portal, vec![]),
+        None => {
+            let top = target.dimension_type.logical_height - MIN_PORTAL_HEIGHT - 2;
+            let y = target.highest_block(x, z).max(1).min(top);
+            ((x, y, z), create_portal(target, x, y, z, Axis::X))
+        }
+    };
+
+    (arrival(target, position, x, y, z), changes)
+}
+
+/// Rebuilds the obsidian platform players arrive on in the end, as vanilla does on every visit.
+pub fn end_platform(target: &mut World, position: &Position) -> (Position, Vec<BlockChange>) {
+    let (x, y, z) = END_SPAWN;
+    let mut changes = vec![];
+    for dx in -2..=2 {
+        for dz in -2..=2 {
+            set(target, &mut changes, x + dx, y - 1, z + dz, block::OBSIDIAN);
+            for dy in 0..3 {
+                set(target, &mut changes, x + dx, y + dy, z + dz, block::AIR);
+            }
+        }
+    }
+
+    (arrival(target, position, x, y, z), changes)
+}
+
+/// Where an end gateway throws a player: outwards from the centre of the end, onto the highest
+/// block there or a small end stone island if there is only void.
+pub fn gateway_destination(world: &mut World, position: &Position) -> (Position, Vec<BlockChange>) {
+    let length = (position.x * position.x + position.z * position.z).sqrt();
+    let (dx, dz) = if length < 1.0 {
+        (1.0, 0.0)
+    } else {
+        (position.x / length, position.z / length)
+    };
+    let x = (dx * GATEWAY_EXIT_DISTANCE).floor() as i32;
+    let z = (dz * GATEWAY_EXIT_DISTANCE).floor() as i32;
+
+    let mut changes = vec![];
+    let mut y = world.highest_block(x, z);
+    if y == 0 {
+        for island_x in -2..=2 {
+            for island_z in -2..=2 {
+                set(world, &mut changes, x + island_x, GATEWAY_ISLAND_HEIGHT, z + island_z, block::END_STONE);
+            }
+        }
+        y = GATEWAY_ISLAND_HEIGHT + 1;
+    }
+
+    (arrival(world, position, x, y, z), changes)
+}
+
+fn arrival(world: &World, position: &Position, x: i32, y: i32, z: i32) -> Position {
+    Position {
+        x: x as f64 + 0.5,
+        y: y as f64,
+        z: z as f64 + 0.5,
+        on_ground: true,
+        world: world.name.clone(),
+        ..position.clone()
+    }
+}
+
+fn set(world: &mut World, changes: &mut Vec<BlockChange>, x: i32, y: i32, z: i32, state: u32) {
+    if world.get_block(x, y, z) != state {
+        world.set_block(x, y, z, state);
+        changes.push(BlockChange { x, y, z, state });
+    }
+}
+
+fn is_nether_portal(state: u32) -> bool {
+    state == block::NETHER_PORTAL_X || state == block::NETHER_PORTAL_Z
+}
+
+/// Blocks a portal frame may contain before it is lit.
+fn is_open(state: u32) -> bool {
+    state == block::AIR || is_nether_portal(state) || block::get_block(state).map(|block| block.name == "minecraft:fire").unwrap_or(false)
+}
@@ -0,0 +1,101 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+use tokio::sync::{watch, Mutex};
+use crate::server::network::Server;
+
+const TYPE_RESPONSE: i32 = 0;
+const TYPE_COMMAND: i32 = 2;
+const TYPE_AUTH_RESPONSE: i32 = 2;
+const TYPE_AUTH: i32 = 3;
+/// Longest body sent in a single response, longer output is split over several.
+const MAX_RESPONSE_LENGTH: usize = 4096;
+/// Longest packet a client may send, as in vanilla.
+const MAX_PACKET_LENGTH: i32 = 1460;
+
+struct RconPacket {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+async fn read_packet(stream: &mut TcpStream) -> Result<RconPacket> {
+    let length = stream.read_i32_le().await?;
+    if !(10..=MAX_PACKET_LENGTH).contains(&length) {
+        return Err(anyhow!("Invalid RCON packet length {}", length));
+    }
+    let id = stream.read_i32_le().await?;
+    let kind = stream.read_i32_le().await?;
+    // The body is followed by two null bytes.
+    let mut body = vec![0; length as usize - 8];
+    stream.read_exact(&mut body).await?;
+    let end = body.iter().position(|byte| *byte == 0).unwrap_or(body.len());
+    body.truncate(end);
+    Ok(RconPacket { id, kind, body: String::from_utf8_lossy(&body).to_string() })
+}
+
+async fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &str) -> Result<()> {
+    let mut bytes = Vec::with_capacity(body.len() + 14);
+    bytes.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+    bytes.extend_from_slice(&id.to_le_bytes());
+    bytes.extend_from_slice(&kind.to_le_bytes());
+    bytes.extend_from_slice(body.as_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+/// Serves one RCON client until it disconnects. Commands are only run once it has sent the
+/// password.
+async fn handle_connection(server: Arc<Mutex<Server>>, mut stream: TcpStream, password: String) -> Result<()> {
+    let mut authenticated = false;
+    loop {
+        let packet = read_packet(&mut stream).await?;
+        match packet.kind {
+            TYPE_AUTH => {
+                authenticated = !password.is_empty() && packet.body == password;
+                let id = if authenticated { packet.id } else { -1 };
+                write_packet(&mut stream, id, TYPE_AUTH_RESPONSE, "").await?;
+            }
+            TYPE_COMMAND if authenticated => {
+                let output = server.lock().await.execute_rcon_command(&packet.body).await;
+                if output.is_empty() {
+                    write_packet(&mut stream, packet.id, TYPE_RESPONSE, "").await?;
+                }
+                let mut rest = output.as_str();
+                while !rest.is_empty() {
+                    let mut split = rest.len().min(MAX_RESPONSE_LENGTH);
+                    while !rest.is_char_boundary(split) {
+                        split -= 1;
+                    }
+                    write_packet(&mut stream, packet.id, TYPE_RESPONSE, &rest[..split]).await?;
+                    rest = &rest[split..];
+                }
+            }
+            TYPE_COMMAND => write_packet(&mut stream, -1, TYPE_AUTH_RESPONSE, "").await?,
+            kind => write_packet(&mut stream, packet.id, TYPE_RESPONSE, &format!("Unknown request {:x}", kind)).await?,
+        }
+    }
+}
+
+/// Accepts RCON connections until the server shuts down.
+pub async fn listen(server: Arc<Mutex<Server>>, address: SocketAddr, password: String, mut shutdown: watch::Receiver<bool>, runtime: Arc<Mutex<Runtime>>) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    println!("RCON running on {}", address);
+    loop {
+        let (stream, client_address) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.changed() => return Ok(()),
+        };
+        let server = server.clone();
+        let password = password.clone();
+        runtime.lock().await.spawn(async move {
+            if let Err(e) = handle_connection(server, stream, password).await {
+                println!("RCON connection from {} closed: {}", client_address, e);
+            }
+        });
+    }
+}
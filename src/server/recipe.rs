@@ -0,0 +1,809 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use mcproto_rs::Serializer;
+use mcproto_rs::types::{BytesSerializer, CountedArray, ItemStack, RemainingBytes, Slot, VarInt};
+use mcproto_rs::v1_16_3::{InventoryOperationMode, Packet753 as Packet, PlayDeclareRecipesSpec, PlayUnlockRecipesSpec, Recipe as RecipeData, RecipeCraftingShapedSpec, RecipeCraftingShapelessSpec, RecipeIngredient, RecipeSmeltingSpec, RecipeSmithingSpec, RecipeSpec, RecipeStonecuttingSpec, RecipeUnlockAction};
+use crate::server::inventory::{self, Click, WindowLayout, WindowState};
+use crate::server::item::{self, ItemBuilder};
+use crate::server::player::{CRAFTING_INPUT_SLOTS, CRAFTING_OUTPUT_SLOT};
+use crate::server::container::{CRAFTING_TABLE_OUTPUT_SLOT, CRAFTING_TABLE_SIZE};
+
+/// The vanilla data pack's namespace, extracted from the server jar, read at startup when present.
+pub const DATA_PACK_PATH: &str = "data/minecraft";
+/// Cooking time of smelting recipes that do not give one, in ticks.
+const DEFAULT_COOKING_TIME: i32 = 200;
+/// Upper bound on how many times a shift-click on a crafting output crafts.
+const MAX_SHIFT_CRAFTS: usize = 64;
+
+/// Item ids any of which satisfy a recipe slot. Empty means the slot must be empty.
+pub type Ingredient = Vec<i32>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CookingKind {
+    Smelting,
+    Blasting,
+    Smoking,
+    Campfire,
+}
+
+#[derive(Clone, Debug)]
+pub enum RecipeKind {
+    Shaped {
+        width: usize,
+        height: usize,
+        /// Row by row, `width * height` long.
+        pattern: Vec<Ingredient>,
+    },
+    Shapeless {
+        ingredients: Vec<Ingredient>,
+    },
+    Cooking {
+        kind: CookingKind,
+        ingredient: Ingredient,
+        experience: f32,
+        cooking_time: i32,
+    },
+    Stonecutting {
+        ingredient: Ingredient,
+    },
+    Smithing {
+        base: Ingredient,
+        addition: Ingredient,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Recipe {
+    /// Namespaced identifier, e.g. `minecraft:chest`.
+    pub id: String,
+    pub group: String,
+    pub kind: RecipeKind,
+    pub result: ItemStack,
+}
+
+fn namespaced(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{}", name)
+    }
+}
+
+fn item_id(name: &str) -> Result<i32> {
+    item::get_item_by_name(&namespaced(name))
+        .map(|item| item.id)
+        .ok_or(anyhow!("Unknown item {}.", name))
+}
+
+fn matches(ingredient: &Ingredient, slot: &Slot) -> bool {
+    match slot {
+        Some(item) => ingredient.contains(&item.item_id.0),
+        None => ingredient.is_empty(),
+    }
+}
+
+/// Finds an ingredient for an item, moving items assigned earlier to other ingredients they also
+/// satisfy when needed, so an item that fits several ingredients cannot block another that fits
+/// only one.
+fn assign_ingredient(item: usize, items: &[i32], ingredients: &[Ingredient], assigned: &mut [Option<usize>], visited: &mut [bool]) -> bool {
+    for (index, ingredient) in ingredients.iter().enumerate() {
+        if visited[index] || !ingredient.contains(&items[item]) {
+            continue;
+        }
+        visited[index] = true;
+        let free = match assigned[index] {
+            Some(other) => assign_ingredient(other, items, ingredients, assigned, visited),
+            None => true,
+        };
+        if free {
+            assigned[index] = Some(item);
+            return true;
+        }
+    }
+    false
+}
+
+fn ingredient_spec(ingredient: &Ingredient) -> RecipeIngredient {
+    let items: Vec<Slot> = ingredient.iter()
+        .map(|id| Some(ItemStack { item_id: VarInt::from(*id), item_count: 1, nbt: None }))
+        .collect();
+    RecipeIngredient { items: CountedArray::from(items) }
+}
+
+impl Recipe {
+    /// Parses a recipe in the format of the vanilla data pack, resolving `tag` ingredients with
+    /// the given item tags.
+    pub fn from_json(id: &str, json: &Value, tags: &HashMap<String, Vec<i32>>) -> Result<Self> {
+        let ingredient = |value: &Value| -> Result<Ingredient> {
+            let options = match value {
+                Value::Array(options) => options.clone(),
+                option => vec![option.clone()],
+            };
+            let mut items = vec![];
+            for option in options {
+                if let Some(name) = option["item"].as_str() {
+                    items.push(item_id(name)?);
+                } else if let Some(tag) = option["tag"].as_str() {
+                    let tagged = tags.get(&namespaced(tag)).ok_or(anyhow!("Unknown item tag {}.", tag))?;
+                    items.extend(tagged.iter().copied());
+                } else {
+                    return Err(anyhow!("Ingredient has neither an item nor a tag."));
+                }
+            }
+            if items.is_empty() {
+                return Err(anyhow!("Ingredient matches no items."));
+            }
+            Ok(items)
+        };
+        // Crafting results are objects, cooking and stonecutting results just a name.
+        let result = match &json["result"] {
+            Value::String(name) => ItemBuilder::from_id(item_id(name)?)?.count(json["count"].as_i64().unwrap_or(1) as i8).build(),
+            result => {
+                let name = result["item"].as_str().ok_or(anyhow!("Recipe has no result."))?;
+                ItemBuilder::from_id(item_id(name)?)?.count(result["count"].as_i64().unwrap_or(1) as i8).build()
+            }
+        };
+
+        let kind_name = json["type"].as_str().ok_or(anyhow!("Recipe has no type."))?;
+        let cooking = |kind: CookingKind| -> Result<RecipeKind> {
+            Ok(RecipeKind::Cooking {
+                kind,
+                ingredient: ingredient(&json["ingredient"])?,
+                experience: json["experience"].as_f64().unwrap_or(0.0) as f32,
+                cooking_time: json["cookingtime"].as_i64().map(|time| time as i32).unwrap_or(DEFAULT_COOKING_TIME),
+            })
+        };
+        let kind = match namespaced(kind_name).as_str() {
+            "minecraft:crafting_shaped" => {
+                let rows: Vec<&str> = json["pattern"].as_array()
+                    .ok_or(anyhow!("Shaped recipe has no pattern."))?
+                    .iter()
+                    .filter_map(|row| row.as_str())
+                    .collect();
+                let height = rows.len();
+                let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+                if width == 0 || width > 3 || height > 3 {
+                    return Err(anyhow!("Shaped recipe pattern must be between 1x1 and 3x3."));
+                }
+                let mut pattern = vec![];
+                for row in rows {
+                    let mut keys: Vec<char> = row.chars().collect();
+                    keys.resize(width, ' ');
+                    for key in keys {
+                        if key == ' ' {
+                            pattern.push(vec![]);
+                        } else {
+                            pattern.push(ingredient(&json["key"][key.to_string()])?);
+                        }
+                    }
+                }
+                RecipeKind::Shaped { width, height, pattern }
+            }
+            "minecraft:crafting_shapeless" => {
+                let ingredients = json["ingredients"].as_array()
+                    .ok_or(anyhow!("Shapeless recipe has no ingredients."))?
+                    .iter()
+                    .map(|value| ingredient(value))
+                    .collect::<Result<Vec<_>>>()?;
+                if ingredients.is_empty() || ingredients.len() > 9 {
+                    return Err(anyhow!("Shapeless recipes take between 1 and 9 ingredients."));
+                }
+                RecipeKind::Shapeless { ingredients }
+            }
+            "minecraft:smelting" => cooking(CookingKind::Smelting)?,
+            "minecraft:blasting" => cooking(CookingKind::Blasting)?,
+            "minecraft:smoking" => cooking(CookingKind::Smoking)?,
+            "minecraft:campfire_cooking" => cooking(CookingKind::Campfire)?,
+            "minecraft:stonecutting" => RecipeKind::Stonecutting { ingredient: ingredient(&json["ingredient"])? },
+            "minecraft:smithing" => RecipeKind::Smithing {
+                base: ingredient(&json["base"])?,
+                addition: ingredient(&json["addition"])?,
+            },
+            other => return Err(anyhow!("Unsupported recipe type {}.", other)),
+        };
+
+        Ok(Self {
+            id: namespaced(id),
+            group: json["group"].as_str().unwrap_or("").to_string(),
+            kind,
+            result,
+        })
+    }
+
+    /// Whether the recipe is crafted from the given grid, read row by row.
+    pub fn matches_grid(&self, grid: &[Slot], grid_width: usize) -> bool {
+        let grid_height = grid.len() / grid_width;
+        match &self.kind {
+            RecipeKind::Shaped { width, height, pattern } => {
+                if *width > grid_width || *height > grid_height {
+                    return false;
+                }
+                for offset_x in 0..=grid_width - width {
+                    for offset_y in 0..=grid_height - height {
+                        for mirrored in [false, true].iter() {
+                            let fits = (0..grid_height).all(|y| (0..grid_width).all(|x| {
+                                let inside = x >= offset_x && x < offset_x + width && y >= offset_y && y < offset_y + height;
+                                let slot = &grid[y * grid_width + x];
+                                if !inside {
+                                    return slot.is_none();
+                                }
+                                let mut pattern_x = x - offset_x;
+                                if *mirrored {
+                                    pattern_x = width - 1 - pattern_x;
+                                }
+                                matches(&pattern[(y - offset_y) * width + pattern_x], slot)
+                            }));
+                            if fits {
+                                return true;
+                            }
+                        }
+                    }
+                }
+                false
+            }
+            RecipeKind::Shapeless { ingredients } => {
+                let items: Vec<i32> = grid.iter().flatten().map(|item| item.item_id.0).collect();
+                if items.len() != ingredients.len() {
+                    return false;
+                }
+                // Each item has to satisfy a different ingredient.
+                let mut assigned = vec![None; ingredients.len()];
+                (0..items.len()).all(|item| assign_ingredient(item, &items, ingredients, &mut assigned, &mut vec![false; ingredients.len()]))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn to_spec(&self) -> RecipeSpec {
+        let result = Some(self.result.clone());
+        let group = self.group.clone();
+        let recipe = match &self.kind {
+            RecipeKind::Shaped { width, height, pattern } => RecipeData::CraftingShaped(RecipeCraftingShapedSpec {
+                width: VarInt::from(*width as i32),
+                height: VarInt::from(*height as i32),
+                group,
+                ingredients: pattern.iter().map(ingredient_spec).collect(),
+                result,
+            }),
+            RecipeKind::Shapeless { ingredients } => RecipeData::CraftingShapeless(RecipeCraftingShapelessSpec {
+                group,
+                ingredients: CountedArray::from(ingredients.iter().map(ingredient_spec).collect::<Vec<_>>()),
+                result,
+            }),
+            RecipeKind::Cooking { kind, ingredient, experience, cooking_time } => {
+                let spec = RecipeSmeltingSpec {
+                    group,
+                    ingredient: ingredient_spec(ingredient),
+                    result,
+                    experience: *experience,
+                    cooking_time: VarInt::from(*cooking_time),
+                };
+                match kind {
+                    CookingKind::Smelting => RecipeData::Smelting(spec),
+                    CookingKind::Blasting => RecipeData::Blasting(spec),
+                    CookingKind::Smoking => RecipeData::Smoking(spec),
+                    CookingKind::Campfire => RecipeData::CampfireCooking(spec),
+                }
+            }
+            RecipeKind::Stonecutting { ingredient } => RecipeData::StoneCutting(RecipeStonecuttingSpec {
+                group,
+                ingredient: ingredient_spec(ingredient),
+                result,
+            }),
+            RecipeKind::Smithing { base, addition } => RecipeData::Smithing(RecipeSmithingSpec {
+                base: ingredient_spec(base),
+                addition: ingredient_spec(addition),
+                result,
+            }),
+        };
+        RecipeSpec { recipe, id: self.id.clone() }
+    }
+}
+
+/// Item tags derived from the names in the item registry, enough for the built-in recipes.
+fn default_item_tags() -> HashMap<String, Vec<i32>> {
+    let mut tags: HashMap<String, Vec<i32>> = HashMap::new();
+    for item in item::items() {
+        let name = item.name.trim_start_matches("minecraft:");
+        let mut add = |tag: &str| tags.entry(format!("minecraft:{}", tag)).or_default().push(item.id);
+        if name.ends_with("_planks") {
+            add("planks");
+        }
+        if let Some(wood) = name.strip_suffix("_log") {
+            add("logs");
+            add("logs_that_burn");
+            add(&format!("{}_logs", wood));
+        }
+        if name == "cobblestone" || name == "blackstone" {
+            add("stone_crafting_materials");
+            add("stone_tool_materials");
+        }
+        if name == "coal" || name == "charcoal" {
+            add("coals");
+        }
+    }
+    tags
+}
+
+/// Resolves tag files, whose values are item names or `#`-prefixed names of other tags, into the
+/// ids of the items they hold. Unknown items and tags are left out, as are tags including
+/// themselves, which vanilla rejects.
+fn resolve_item_tags(files: &HashMap<String, Vec<String>>) -> HashMap<String, Vec<i32>> {
+    fn resolve(name: &str, files: &HashMap<String, Vec<String>>, resolved: &mut HashMap<String, Vec<i32>>, visiting: &mut Vec<String>) -> Vec<i32> {
+        if let Some(items) = resolved.get(name) {
+            return items.clone();
+        }
+        let values = match files.get(name) {
+            Some(values) if !visiting.iter().any(|visited| visited == name) => values,
+            _ => return vec![],
+        };
+        visiting.push(name.to_string());
+        let mut items = vec![];
+        for value in values {
+            let ids = match value.strip_prefix('#') {
+                Some(tag) => resolve(&namespaced(tag), files, resolved, visiting),
+                None => item_id(value).into_iter().collect(),
+            };
+            items.extend(ids.into_iter().filter(|id| !items.contains(id)).collect::<Vec<i32>>());
+        }
+        visiting.pop();
+        resolved.insert(name.to_string(), items.clone());
+        items
+    }
+
+    let mut resolved = HashMap::new();
+    for name in files.keys() {
+        resolve(name, files, &mut resolved, &mut vec![]);
+    }
+    resolved
+}
+
+/// Recipes used when the vanilla data pack is not available, in the data pack format.
+fn default_recipes() -> Vec<(String, Value)> {
+    let mut recipes = vec![];
+    for wood in ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"].iter() {
+        recipes.push((format!("{}_planks", wood), json!({
+            "type": "minecraft:crafting_shapeless",
+            "group": "planks",
+            "ingredients": [{ "tag": format!("minecraft:{}_logs", wood) }],
+            "result": { "item": format!("minecraft:{}_planks", wood), "count": 4 }
+        })));
+    }
+    recipes.push(("stick".to_string(), json!({
+        "type": "minecraft:crafting_shaped",
+        "group": "sticks",
+        "pattern": ["#", "#"],
+        "key": { "#": { "tag": "minecraft:planks" } },
+        "result": { "item": "minecraft:stick", "count": 4 }
+    })));
+    recipes.push(("crafting_table".to_string(), json!({
+        "type": "minecraft:crafting_shaped",
+        "pattern": ["##", "##"],
+        "key": { "#": { "tag": "minecraft:planks" } },
+        "result": { "item": "minecraft:crafting_table" }
+    })));
+    recipes.push(("chest".to_string(), json!({
+        "type": "minecraft:crafting_shaped",
+        "pattern": ["###", "# #", "###"],
+        "key": { "#": { "tag": "minecraft:planks" } },
+        "result": { "item": "minecraft:chest" }
+    })));
+    recipes.push(("furnace".to_string(), json!({
+        "type": "minecraft:crafting_shaped",
+        "pattern": ["###", "# #", "###"],
+        "key": { "#": { "tag": "minecraft:stone_crafting_materials" } },
+        "result": { "item": "minecraft:furnace" }
+    })));
+    recipes.push(("oak_stairs".to_string(), json!({
+        "type": "minecraft:crafting_shaped",
+        "group": "wooden_stairs",
+        "pattern": ["#  ", "## ", "###"],
+        "key": { "#": { "item": "minecraft:oak_planks" } },
+        "result": { "item": "minecraft:oak_stairs", "count": 4 }
+    })));
+    recipes.push(("bowl".to_string(), json!({
+        "type": "minecraft:crafting_shaped",
+        "pattern": ["# #", " # "],
+        "key": { "#": { "tag": "minecraft:planks" } },
+        "result": { "item": "minecraft:bowl", "count": 4 }
+    })));
+    recipes.push(("netherite_ingot".to_string(), json!({
+        "type": "minecraft:crafting_shapeless",
+        "group": "netherite_ingot",
+        "ingredients": [
+            { "item": "minecraft:netherite_scrap" }, { "item": "minecraft:netherite_scrap" },
+            { "item": "minecraft:netherite_scrap" }, { "item": "minecraft:netherite_scrap" },
+            { "item": "minecraft:gold_ingot" }, { "item": "minecraft:gold_ingot" },
+            { "item": "minecraft:gold_ingot" }, { "item": "minecraft:gold_ingot" }
+        ],
+        "result": { "item": "minecraft:netherite_ingot" }
+    })));
+
+    let tools = [
+        ("sword", vec!["X", "X", "#"]),
+        ("shovel", vec!["X", "#", "#"]),
+        ("pickaxe", vec!["XXX", " # ", " # "]),
+        ("axe", vec!["XX", "X#", " #"]),
+        ("hoe", vec!["XX", " #", " #"]),
+    ];
+    let materials = [
+        ("wooden", json!({ "tag": "minecraft:planks" })),
+        ("stone", json!({ "tag": "minecraft:stone_tool_materials" })),
+        ("golden", json!({ "item": "minecraft:gold_ingot" })),
+        ("iron", json!({ "item": "minecraft:iron_ingot" })),
+        ("diamond", json!({ "item": "minecraft:diamond" })),
+    ];
+    for (tier, material) in materials.iter() {
+        for (tool, pattern) in tools.iter() {
+            recipes.push((format!("{}_{}", tier, tool), json!({
+                "type": "minecraft:crafting_shaped",
+                "pattern": pattern,
+                "key": { "#": { "item": "minecraft:stick" }, "X": material },
+                "result": { "item": format!("minecraft:{}_{}", tier, tool) }
+            })));
+        }
+    }
+    for (tool, _) in tools.iter() {
+        recipes.push((format!("netherite_{}_smithing", tool), json!({
+            "type": "minecraft:smithing",
+            "base": { "item": format!("minecraft:diamond_{}", tool) },
+            "addition": { "item": "minecraft:netherite_ingot" },
+            "result": { "item": format!("minecraft:netherite_{}", tool) }
+        })));
+    }
+
+    for (ore, ingot, experience) in [("iron_ore", "iron_ingot", 0.7), ("gold_ore", "gold_ingot", 1.0)].iter() {
+        recipes.push((ingot.to_string(), json!({
+            "type": "minecraft:smelting",
+            "ingredient": { "item": format!("minecraft:{}", ore) },
+            "result": format!("minecraft:{}", ingot),
+            "experience": experience,
+            "cookingtime": 200
+        })));
+        recipes.push((format!("{}_from_blasting", ingot), json!({
+            "type": "minecraft:blasting",
+            "ingredient": { "item": format!("minecraft:{}", ore) },
+            "result": format!("minecraft:{}", ingot),
+            "experience": experience,
+            "cookingtime": 100
+        })));
+    }
+    recipes.push(("stone".to_string(), json!({
+        "type": "minecraft:smelting",
+        "ingredient": { "item": "minecraft:cobblestone" },
+        "result": "minecraft:stone",
+        "experience": 0.1,
+        "cookingtime": 200
+    })));
+    recipes.push(("charcoal".to_string(), json!({
+        "type": "minecraft:smelting",
+        "ingredient": { "tag": "minecraft:logs_that_burn" },
+        "result": "minecraft:charcoal",
+        "experience": 0.15,
+        "cookingtime": 200
+    })));
+    for stone in ["granite", "diorite", "andesite"].iter() {
+        recipes.push((format!("polished_{}_from_{}_stonecutting", stone, stone), json!({
+            "type": "minecraft:stonecutting",
+            "ingredient": { "item": format!("minecraft:{}", stone) },
+            "result": format!("minecraft:polished_{}", stone),
+            "count": 1
+        })));
+    }
+    recipes
+}
+
+/// Every recipe the server knows, in the order they are sent to clients.
+pub struct RecipeManager {
+    recipes: Vec<Recipe>,
+    item_tags: HashMap<String, Vec<i32>>,
+}
+
+impl RecipeManager {
+    pub fn new() -> Self {
+        Self {
+            recipes: vec![],
+            item_tags: default_item_tags(),
+        }
+    }
+
+    /// The built-in recipes, a small part of vanilla's used when its data pack is not available.
+    pub fn vanilla() -> Self {
+        let mut manager = Self::new();
+        for (id, json) in default_recipes() {
+            if let Err(err) = manager.add_json(&id, &json) {
+                println!("Failed to load built-in recipe {}: {}", id, err);
+            }
+        }
+        manager
+    }
+
+    /// Every recipe of a data pack namespace laid out like `data/minecraft` in the vanilla server
+    /// jar, with the item tags they use.
+    pub fn load_data_pack<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut manager = Self::new();
+        let tags = manager.load_item_tags_dir(path.as_ref().join("tags").join("items"))?;
+        let recipes = manager.load_dir(path.as_ref().join("recipes"))?;
+        println!("Loaded {} item tags and {} recipes from {}", tags, recipes, path.as_ref().display());
+        Ok(manager)
+    }
+
+    /// Loads every item tag in a directory laid out like `data/minecraft/tags/items` in the vanilla
+    /// server jar, returning how many were loaded.
+    pub fn load_item_tags_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let mut files = HashMap::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => namespaced(name),
+                None => continue,
+            };
+            let json: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            // Values are either names or objects naming an optional entry.
+            let values = json["values"].as_array().map(|values| values.iter()
+                .filter_map(|value| value.as_str().or_else(|| value["id"].as_str()).map(str::to_string))
+                .collect())
+                .unwrap_or_default();
+            files.insert(name, values);
+        }
+        let tags = resolve_item_tags(&files);
+        let count = tags.len();
+        self.item_tags.extend(tags);
+        Ok(count)
+    }
+
+    /// Makes a tag available to recipes loaded afterwards.
+    pub fn register_item_tag(&mut self, name: &str, items: Vec<i32>) {
+        self.item_tags.insert(namespaced(name), items);
+    }
+
+    /// Adds a recipe, replacing any with the same id.
+    pub fn add(&mut self, recipe: Recipe) {
+        match self.recipes.iter_mut().find(|existing| existing.id == recipe.id) {
+            Some(existing) => *existing = recipe,
+            None => self.recipes.push(recipe),
+        }
+    }
+
+    pub fn add_json(&mut self, id: &str, json: &Value) -> Result<()> {
+        let recipe = Recipe::from_json(id, json, &self.item_tags)?;
+        self.add(recipe);
+        Ok(())
+    }
+
+    /// Loads every recipe in a directory laid out like `data/minecraft/recipes` in the vanilla
+    /// server jar, returning how many were loaded. Recipes of unsupported types or for items the
+    /// registry does not have are skipped.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let mut loaded = 0;
+        let mut skipped = 0;
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let id = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            let json: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            match self.add_json(&id, &json) {
+                Ok(()) => loaded += 1,
+                Err(_) => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            println!("Skipped {} recipes that use unsupported types or unknown items.", skipped);
+        }
+        Ok(loaded)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.id == id)
+    }
+
+    pub fn recipes(&self) -> &[Recipe] {
+        &self.recipes
+    }
+
+    /// The crafting recipe made by a grid, read row by row.
+    pub fn find_crafting(&self, grid: &[Slot], width: usize) -> Option<&Recipe> {
+        if grid.iter().all(|slot| slot.is_none()) {
+            return None;
+        }
+        self.recipes.iter().find(|recipe| recipe.matches_grid(grid, width))
+    }
+
+    /// The recipe of the given cooking kind that takes the item.
+    pub fn find_cooking(&self, kind: CookingKind, item_id: i32) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| match &recipe.kind {
+            RecipeKind::Cooking { kind: recipe_kind, ingredient, .. } => *recipe_kind == kind && ingredient.contains(&item_id),
+            _ => false,
+        })
+    }
+
+    pub fn declare_recipes(&self) -> Packet {
+        Packet::PlayDeclareRecipes(PlayDeclareRecipesSpec {
+            recipes: CountedArray::from(self.recipes.iter().map(|recipe| recipe.to_spec()).collect::<Vec<_>>()),
+        })
+    }
+
+    /// Unlocks every crafting and cooking recipe in the client's recipe book.
+    pub fn unlock_recipes(&self) -> Packet {
+        let ids: Vec<String> = self.recipes.iter()
+            .filter(|recipe| matches!(recipe.kind, RecipeKind::Shaped { .. } | RecipeKind::Shapeless { .. } | RecipeKind::Cooking { .. }))
+            .map(|recipe| recipe.id.clone())
+            .collect();
+        // Init carries a second list of recipes to highlight as new, which stays empty.
+        let mut highlighted = BytesSerializer::default();
+        let _ = highlighted.serialize_other(&CountedArray::<String, VarInt>::from(vec![]));
+        Packet::PlayUnlockRecipes(PlayUnlockRecipesSpec {
+            action: RecipeUnlockAction::Init,
+            crafting_book_open: false,
+            crafting_book_active: false,
+            smelting_book_open: false,
+            smelting_book_active: false,
+            blast_furnace_recipe_book_open: false,
+            blast_furnace_recipe_book_active: false,
+            smoke_recipe_book_open: false,
+            smoke_recipe_book_active: false,
+            recipe_ids: CountedArray::from(ids),
+            other_recipe_ids: RemainingBytes { data: highlighted.into_bytes() },
+        })
+    }
+}
+
+impl Default for RecipeManager {
+    fn default() -> Self {
+        Self::vanilla()
+    }
+}
+
+/// Where a window keeps its crafting grid.
+pub struct CraftingGrid {
+    pub output: usize,
+    pub inputs: Range<usize>,
+    pub width: usize,
+}
+
+/// The 2x2 grid of the player's inventory window.
+pub const PLAYER_CRAFTING: CraftingGrid = CraftingGrid {
+    output: CRAFTING_OUTPUT_SLOT,
+    inputs: CRAFTING_INPUT_SLOTS,
+    width: 2,
+};
+
+/// The 3x3 grid of a crafting table window.
+pub const TABLE_CRAFTING: CraftingGrid = CraftingGrid {
+    output: CRAFTING_TABLE_OUTPUT_SLOT,
+    inputs: CRAFTING_TABLE_OUTPUT_SLOT + 1..CRAFTING_TABLE_SIZE,
+    width: 3,
+};
+
+/// Fills the output slot with whatever the grid currently crafts.
+pub fn update_output(recipes: &RecipeManager, grid: &CraftingGrid, slots: &mut [Slot]) {
+    slots[grid.output] = recipes.find_crafting(&slots[grid.inputs.clone()], grid.width).map(|recipe| recipe.result.clone());
+}
+
+fn consume_ingredients(grid: &CraftingGrid, slots: &mut [Slot]) {
+    for slot in slots[grid.inputs.clone()].iter_mut() {
+        if let Some(item) = slot {
+            item.item_count -= 1;
+            if item.item_count <= 0 {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Applies a click to a window with a crafting grid. Taking the output consumes one of each
+/// ingredient, and shift-clicking it crafts as many times as the grid and inventory allow.
+pub fn crafting_click(recipes: &RecipeManager, grid: &CraftingGrid, layout: &dyn WindowLayout, slots: &mut [Slot], state: &mut WindowState, click: &Click, creative: bool) -> Result<()> {
+    update_output(recipes, grid, slots);
+    if click.slot < 0 || click.slot as usize != grid.output {
+        inventory::click(layout, slots, state, click, creative)?;
+        update_output(recipes, grid, slots);
+        return Ok(());
+    }
+
+    let crafts = if click.mode == InventoryOperationMode::ShiftClick { MAX_SHIFT_CRAFTS } else { 1 };
+    for _ in 0..crafts {
+        let output = match &slots[grid.output] {
+            Some(output) => output.clone(),
+            None => break,
+        };
+        // The whole result is always taken, so right clicks and single drops act like their
+        // left click and stack counterparts.
+        let button = match click.mode {
+            InventoryOperationMode::MouseClick => 0,
+            InventoryOperationMode::DropClick => 1,
+            _ => click.button,
+        };
+        let craft = Click { slot: click.slot, button, mode: click.mode.clone() };
+        let before = slots.to_vec();
+        inventory::click(layout, slots, state, &craft, creative)?;
+        match &slots[grid.output] {
+            None => {}
+            Some(remaining) if *remaining == output => break,
+            // Only part of the result fit, which crafting never allows.
+            Some(_) => {
+                slots.clone_from_slice(&before);
+                break;
+            }
+        }
+        consume_ingredients(grid, slots);
+        update_output(recipes, grid, slots);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::container::{ContainerKind, ContainerWindow};
+
+    fn stack(id: i32) -> Slot {
+        Some(ItemStack { item_id: VarInt::from(id), item_count: 1, nbt: None })
+    }
+
+    fn shapeless(ingredients: Vec<Ingredient>) -> Recipe {
+        Recipe {
+            id: "minecraft:test".to_string(),
+            group: String::new(),
+            kind: RecipeKind::Shapeless { ingredients },
+            result: ItemStack { item_id: VarInt::from(1), item_count: 1, nbt: None },
+        }
+    }
+
+    #[test]
+    fn shapeless_items_are_matched_to_ingredients_in_any_order() {
+        let recipe = shapeless(vec![vec![1, 2], vec![1]]);
+        assert!(recipe.matches_grid(&[stack(1), stack(2), None, None], 2));
+        assert!(recipe.matches_grid(&[stack(2), None, None, stack(1)], 2));
+        assert!(!recipe.matches_grid(&[stack(2), stack(2), None, None], 2));
+        assert!(!recipe.matches_grid(&[stack(1), None, None, None], 2));
+    }
+
+    fn double_click_beside_output(grid: &CraftingGrid, layout: &dyn WindowLayout, size: usize) {
+        let mut recipes = RecipeManager::new();
+        recipes.add(Recipe {
+            result: ItemStack { item_id: VarInt::from(1), item_count: 4, nbt: None },
+            ..shapeless(vec![vec![2]])
+        });
+        let mut slots = vec![None; size];
+        slots[grid.inputs.start] = stack(2);
+        let mut state = WindowState::default();
+        state.cursor = stack(1);
+        let click = Click { slot: (size - 1) as i16, button: 0, mode: InventoryOperationMode::DoubleClick };
+        crafting_click(&recipes, grid, layout, &mut slots, &mut state, &click, false).unwrap();
+        assert_eq!(slots[grid.inputs.start], stack(2));
+        assert_eq!(slots[grid.output].as_ref().map(|output| output.item_count), Some(4));
+        assert_eq!(state.cursor, stack(1));
+    }
+
+    #[test]
+    fn double_clicks_do_not_collect_crafting_results() {
+        double_click_beside_output(&PLAYER_CRAFTING, &inventory::PlayerWindow, crate::server::player::INVENTORY_SIZE);
+        let table = ContainerWindow { kind: ContainerKind::CraftingTable };
+        double_click_beside_output(&TABLE_CRAFTING, &table, table.size());
+    }
+
+    #[test]
+    fn tags_resolve_through_other_tags() {
+        let files: HashMap<String, Vec<String>> = [
+            ("minecraft:logs", vec!["#minecraft:oak_logs", "minecraft:not_an_item"]),
+            ("minecraft:oak_logs", vec!["minecraft:oak_log", "#minecraft:missing"]),
+        ].iter().map(|(name, values)| (name.to_string(), values.iter().map(|value| value.to_string()).collect())).collect();
+        let tags = resolve_item_tags(&files);
+        let oak_log = item_id("oak_log").unwrap();
+        assert_eq!(tags["minecraft:logs"], vec![oak_log]);
+        assert_eq!(tags["minecraft:oak_logs"], vec![oak_log]);
+    }
+}
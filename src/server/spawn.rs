@@ -0,0 +1,128 @@
+use crate::server::block;
+use crate::server::placement;
+use crate::server::world::{BlockChange, World};
+
+/// A bed or respawn anchor a player respawns at instead of the world spawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnPoint {
+    pub world: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+fn is_bed(state: u32) -> bool {
+    block::get_block(state).map(|block| block.name.ends_with("_bed")).unwrap_or(false)
+}
+
+/// Charges left in a respawn anchor, or `None` if the state is not one.
+pub fn anchor_charges(state: u32) -> Option<u32> {
+    let block = block::get_block(state)?;
+    if block.name == "minecraft:respawn_anchor" {
+        Some(state - block.min_state)
+    } else {
+        None
+    }
+}
+
+/// Whether a player can stand with their feet in the block: two passable blocks above solid
+/// ground that will not hurt them.
+fn is_standable(world: &mut World, x: i32, y: i32, z: i32) -> bool {
+    let safe = |state: u32| {
+        !block::is_solid(state) && match block::get_block(state) {
+            Some(block) => block.name != "minecraft:lava" && block.name != "minecraft:fire" && block.name != "minecraft:water",
+            None => false,
+        }
+    };
+    block::is_solid(world.get_block(x, y - 1, z)) && safe(world.get_block(x, y, z)) && safe(world.get_block(x, y + 1, z))
+}
+
+/// Finds the safe column closest to the given one within `radius`, returning the block a player
+/// stands in. Chunks are generated as needed.
+pub fn find_safe_spawn(world: &mut World, x: i32, z: i32, radius: i32) -> Option<(i32, i32, i32)> {
+    for ring in 0..=radius {
+        for dx in -ring..=ring {
+            for dz in -ring..=ring {
+                if dx.abs() != ring && dz.abs() != ring {
+                    continue;
+                }
+                let (column_x, column_z) = (x + dx, z + dz);
+                let y = world.highest_block(column_x, column_z);
+                if y > 0 && is_standable(world, column_x, y, column_z) {
+                    return Some((column_x, y, column_z));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Where players spawn in a world, searching around the configured spawn for safe ground and
+/// falling back to the spawn itself.
+pub fn world_spawn(world: &mut World, radius: i32) -> (i32, i32, i32) {
+    let spawn = world.spawn.clone();
+    find_safe_spawn(world, spawn.x, spawn.z, radius).unwrap_or((spawn.x, spawn.y as i32, spawn.z))
+}
+
+/// A free spot next to the block, or on top of it if it is surrounded.
+fn stand_up_position(world: &mut World, x: i32, y: i32, z: i32) -> Option<(i32, i32, i32)> {
+    for dy in [0, -1, 1].iter() {
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                if (dx != 0 || dz != 0) && is_standable(world, x + dx, y + dy, z + dz) {
+                    return Some((x + dx, y + dy, z + dz));
+                }
+            }
+        }
+    }
+    if is_standable(world, x, y + 1, z) {
+        Some((x, y + 1, z))
+    } else {
+        None
+    }
+}
+
+/// Where a player with the given spawn point respawns, along with the block changes respawning
+/// causes. `None` means the bed or anchor is gone, uncharged or obstructed.
+pub fn respawn_position(world: &mut World, point: &SpawnPoint) -> Option<((i32, i32, i32), Vec<BlockChange>)> {
+    let (x, y, z) = (point.x, point.y, point.z);
+    let state = world.get_block(x, y, z);
+    if is_bed(state) {
+        if !world.dimension_type.bed_works {
+            return None;
+        }
+        let around_foot = stand_up_position(world, x, y, z);
+        let around_head = placement::bed_other_half(state)
+            .and_then(|((dx, dy, dz), _)| stand_up_position(world, x + dx, y + dy, z + dz));
+        return around_foot.or(around_head).map(|position| (position, vec![]));
+    }
+
+    match anchor_charges(state) {
+        Some(charges) if charges > 0 && world.dimension_type.respawn_anchor_works => {
+            let position = stand_up_position(world, x, y, z)?;
+            let state = state - 1;
+            world.set_block(x, y, z, state);
+            Some((position, vec![BlockChange { x, y, z, state }]))
+        }
+        _ => None,
+    }
+}
+
+/// The spawn point set by using the block at the position, if it is a bed or charged anchor. Beds
+/// always point at their foot.
+pub fn spawn_point_at(world: &mut World, x: i32, y: i32, z: i32) -> Option<SpawnPoint> {
+    let state = world.get_block(x, y, z);
+    let (x, y, z) = if is_bed(state) {
+        let block = block::get_block(state)?;
+        let is_head = (state - block.min_state) % 2 == 0;
+        match placement::bed_other_half(state) {
+            Some(((dx, dy, dz), _)) if is_head => (x + dx, y + dy, z + dz),
+            _ => (x, y, z),
+        }
+    } else if anchor_charges(state).map(|charges| charges > 0).unwrap_or(false) {
+        (x, y, z)
+    } else {
+        return None;
+    };
+    Some(SpawnPoint { world: world.name.clone(), x, y, z })
+}
@@ -0,0 +1,67 @@
+use mcproto_rs::types::{Chat, CountedArray, VarInt};
+use mcproto_rs::uuid::UUID4;
+use mcproto_rs::v1_16_3::{GameMode, Packet753 as Packet, PlayPlayerInfoSpec, PlayPlayerListHeaderAndFooterSpec, PlayerAddActionSpec, PlayerAddProperty, PlayerInfoAction, PlayerInfoActionList};
+use crate::server::player::Player;
+
+/// Adds the given players to the tab list, including their skin properties.
+pub fn add_players(players: &[&Player]) -> Packet {
+    let actions = players
+        .iter()
+        .map(|player| PlayerInfoAction {
+            uuid: player.uuid,
+            action: PlayerAddActionSpec {
+                name: player.name.clone(),
+                properties: CountedArray::from(
+                    player
+                        .properties
+                        .iter()
+                        .map(|property| PlayerAddProperty {
+                            name: property.name.clone(),
+                            value: property.value.clone(),
+                            signature: property.signature.clone(),
+                        })
+                        .collect::<Vec<PlayerAddProperty>>(),
+                ),
+                game_mode: player.gamemode.clone(),
+                ping_ms: VarInt::from(player.ping),
+                display_name: player.display_name.clone(),
+            },
+        })
+        .collect::<Vec<PlayerInfoAction<PlayerAddActionSpec>>>();
+
+    player_info(PlayerInfoActionList::Add(CountedArray::from(actions)))
+}
+
+pub fn remove_players(uuids: Vec<UUID4>) -> Packet {
+    player_info(PlayerInfoActionList::Remove(CountedArray::from(uuids)))
+}
+
+pub fn update_latency(uuid: UUID4, ping: i32) -> Packet {
+    player_info(PlayerInfoActionList::UpdateLatency(CountedArray::from(vec![PlayerInfoAction {
+        uuid,
+        action: VarInt::from(ping),
+    }])))
+}
+
+pub fn update_gamemode(uuid: UUID4, gamemode: GameMode) -> Packet {
+    player_info(PlayerInfoActionList::UpdateGameMode(CountedArray::from(vec![PlayerInfoAction {
+        uuid,
+        action: gamemode,
+    }])))
+}
+
+/// A display name of `None` resets the entry to the player's name.
+pub fn update_display_name(uuid: UUID4, display_name: Option<Chat>) -> Packet {
+    player_info(PlayerInfoActionList::UpdateDisplayName(CountedArray::from(vec![PlayerInfoAction {
+        uuid,
+        action: display_name,
+    }])))
+}
+
+pub fn header_and_footer(header: Chat, footer: Chat) -> Packet {
+    Packet::PlayerPlayerListHeaderAndFooter(PlayPlayerListHeaderAndFooterSpec { header, footer })
+}
+
+fn player_info(actions: PlayerInfoActionList) -> Packet {
+    Packet::PlayPlayerInfo(PlayPlayerInfoSpec { actions })
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use mcproto_rs::types::{BaseComponent, Chat, ChatClickEvent, ChatHoverEvent, ColorCode, Formatter, TextComponent, TranslationComponent};
+
+/// English text of the translation keys the server sends, so messages can also be shown on the
+/// console.
+const TRANSLATIONS: &[(&str, &str)] = &[
+    ("chat.disabled.options", "Chat disabled in client options."),
+    ("chat.type.text", "<%s> %s"),
+    ("chat.type.announcement", "[%s] %s"),
+    ("commands.message.display.incoming", "%s whispers to you: %s"),
+    ("commands.message.display.outgoing", "You whisper to %s: %s"),
+    ("disconnect.spam", "Kicked for spamming"),
+    ("multiplayer.disconnect.duplicate_login", "You logged in from another location"),
+    ("multiplayer.disconnect.illegal_characters", "Illegal characters in chat"),
+    ("multiplayer.disconnect.kicked", "Kicked by an operator."),
+    ("multiplayer.disconnect.server_shutdown", "Server closed"),
+    ("multiplayer.player.joined", "%s joined the game"),
+    ("multiplayer.player.left", "%s left the game"),
+];
+
+/// Locale clients are assumed to use until they send their settings, and the one translations
+/// fall back to.
+pub const DEFAULT_LOCALE: &str = "en_us";
+
+/// Builds a JSON text component, with styles and events applying to it and everything appended.
+///
+/// ```ignore
+/// let name = text("Steve").color(ColorCode::Gold).suggest_command("/msg Steve ").build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextBuilder {
+    component: Chat,
+}
+
+/// A component showing literal text.
+pub fn text(text: &str) -> TextBuilder {
+    TextBuilder { component: Chat::from_text(text) }
+}
+
+/// A component the client translates into its own language, filling `%s` with the arguments.
+pub fn translate(key: &str, arguments: Vec<Chat>) -> TextBuilder {
+    TextBuilder {
+        component: Chat::Translation(TranslationComponent {
+            translate: key.to_string(),
+            with: arguments.into_iter().map(Chat::boxed).collect(),
+            base: BaseComponent::default(),
+        }),
+    }
+}
+
+/// A component from text with `§` formatting codes, or `&` codes as well if `ampersand` is set.
+pub fn legacy(text: &str, ampersand: bool) -> TextBuilder {
+    TextBuilder { component: Chat::from_traditional(text, ampersand) }
+}
+
+fn base_mut(chat: &mut Chat) -> &mut BaseComponent {
+    match chat {
+        Chat::Text(body) => &mut body.base,
+        Chat::Translation(body) => &mut body.base,
+        Chat::Keybind(body) => &mut body.base,
+        Chat::Score(body) => &mut body.base,
+    }
+}
+
+impl TextBuilder {
+    fn base(&mut self) -> &mut BaseComponent {
+        base_mut(&mut self.component)
+    }
+
+    pub fn color(mut self, color: ColorCode) -> Self {
+        self.base().color = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.base().bold = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.base().italic = true;
+        self
+    }
+
+    pub fn underlined(mut self) -> Self {
+        self.base().underlined = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Self {
+        self.base().strikethrough = true;
+        self
+    }
+
+    pub fn obfuscated(mut self) -> Self {
+        self.base().obfuscated = true;
+        self
+    }
+
+    /// Text inserted into the chat box when the component is shift-clicked.
+    pub fn insertion(mut self, text: &str) -> Self {
+        self.base().insertion = Some(text.to_string());
+        self
+    }
+
+    pub fn hover_text(mut self, hover: impl Into<Chat>) -> Self {
+        self.base().hover_event = Some(ChatHoverEvent::ShowText(hover.into().boxed()));
+        self
+    }
+
+    /// Runs a command, or sends a chat message, as the player clicking the component.
+    pub fn run_command(mut self, command: &str) -> Self {
+        self.base().click_event = Some(ChatClickEvent::RunCommand(command.to_string()));
+        self
+    }
+
+    /// Puts a command into the player's chat box when clicked.
+    pub fn suggest_command(mut self, command: &str) -> Self {
+        self.base().click_event = Some(ChatClickEvent::SuggestCommand(command.to_string()));
+        self
+    }
+
+    pub fn open_url(mut self, url: &str) -> Self {
+        self.base().click_event = Some(ChatClickEvent::OpenUrl(url.to_string()));
+        self
+    }
+
+    /// Adds a component after this one, inheriting this one's style.
+    pub fn append(mut self, child: impl Into<Chat>) -> Self {
+        self.base().extra.push(child.into().boxed());
+        self
+    }
+
+    pub fn build(self) -> Chat {
+        self.component
+    }
+}
+
+impl From<TextBuilder> for Chat {
+    fn from(builder: TextBuilder) -> Self {
+        builder.component
+    }
+}
+
+/// The text of a component without any formatting, for the console and logs. Translations are
+/// filled in where the server knows them.
+pub fn plain_text(chat: &Chat) -> String {
+    let mut result = match chat {
+        Chat::Text(body) => body.text.clone(),
+        Chat::Translation(body) => {
+            let template = TRANSLATIONS.iter().find(|(key, _)| *key == body.translate).map(|(_, text)| *text).unwrap_or(&body.translate);
+            let mut arguments = body.with.iter().map(|argument| plain_text(argument));
+            let mut filled = String::new();
+            let mut parts = template.split("%s");
+            filled.push_str(parts.next().unwrap_or(""));
+            for part in parts {
+                filled.push_str(&arguments.next().unwrap_or_default());
+                filled.push_str(part);
+            }
+            filled
+        }
+        Chat::Keybind(body) => body.keybind.clone(),
+        Chat::Score(body) => body.score.value.clone().unwrap_or_default(),
+    };
+    for child in chat.siblings() {
+        result.push_str(&plain_text(child));
+    }
+    strip_codes(&result)
+}
+
+/// Removes `§` formatting codes.
+pub fn strip_codes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// The colour and formats in effect at some point of legacy formatted text.
+#[derive(Clone, Default)]
+struct LegacyStyle {
+    color: Option<ColorCode>,
+    formats: Vec<Formatter>,
+}
+
+impl LegacyStyle {
+    fn apply(&mut self, formatter: Formatter) {
+        match formatter {
+            Formatter::Color(color) => {
+                self.color = Some(color);
+                self.formats.clear();
+            }
+            Formatter::Reset => *self = Self::default(),
+            formatter => self.formats.push(formatter),
+        }
+    }
+
+    /// An empty component carrying the style, for values that should look like the text around
+    /// them unless they have a style of their own.
+    fn wrap(&self, value: Chat) -> Chat {
+        let mut base = BaseComponent::default();
+        base.color = self.color;
+        for formatter in &self.formats {
+            match formatter {
+                Formatter::Bold => base.bold = true,
+                Formatter::Italic => base.italic = true,
+                Formatter::Underline => base.underlined = true,
+                Formatter::Strikethrough => base.strikethrough = true,
+                Formatter::Obfuscated => base.obfuscated = true,
+                _ => {}
+            }
+        }
+        base.extra.push(value.boxed());
+        Chat::Text(TextComponent { text: String::new(), base })
+    }
+
+    fn codes(&self) -> String {
+        let mut codes = String::new();
+        for formatter in self.color.map(Formatter::Color).iter().chain(self.formats.iter()) {
+            codes.push('§');
+            codes.push(formatter.code());
+        }
+        codes
+    }
+}
+
+/// Fills a chat template such as `<{name}> {message}`. The template may use `&` and `§` codes,
+/// which style the values inserted after them; the values themselves are never parsed for codes.
+/// Unknown placeholders are left as they are.
+pub fn render_template(template: &str, values: &[(&str, Chat)]) -> Chat {
+    let mut root = text("");
+    // The style at the end of what has been read, and at the start of the literal text not yet added.
+    let mut style = LegacyStyle::default();
+    let mut literal_style = LegacyStyle::default();
+    let mut literal = String::new();
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        let placeholder = match c {
+            '{' => rest.find('}').and_then(|end| values.iter().find(|(key, _)| *key == &rest[..end]).map(|(_, value)| (end, value))),
+            _ => None,
+        };
+        if let Some((end, value)) = placeholder {
+            if !literal.is_empty() {
+                root = root.append(Chat::from_traditional(&format!("{}{}", literal_style.codes(), literal), false));
+                literal.clear();
+            }
+            root = root.append(style.wrap(value.clone()));
+            literal_style = style.clone();
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if c == '&' || c == '§' {
+            if let Some(formatter) = rest.chars().next().and_then(|code| Formatter::from_code(&code.to_ascii_lowercase())) {
+                style.apply(formatter);
+                literal.push('§');
+                continue;
+            }
+        }
+        literal.push(c);
+    }
+    if !literal.is_empty() {
+        root = root.append(Chat::from_traditional(&format!("{}{}", literal_style.codes(), literal), false));
+    }
+    root.build()
+}
+
+/// Templates chat messages are rendered with, see `render_template`.
+#[derive(Clone, Debug)]
+pub struct ChatTemplates {
+    /// Player chat, with `{name}` and `{message}`.
+    pub chat: String,
+    /// `/say`, with `{name}` and `{message}`.
+    pub announcement: String,
+    /// Private messages as the receiver sees them, with `{name}` and `{message}`.
+    pub whisper_incoming: String,
+    /// Private messages as the sender sees them, with `{name}` being the receiver and `{message}`.
+    pub whisper_outgoing: String,
+}
+
+impl Default for ChatTemplates {
+    fn default() -> Self {
+        Self {
+            chat: "<{name}> {message}".to_string(),
+            announcement: "[{name}] {message}".to_string(),
+            whisper_incoming: "&7&o{name} whispers to you: {message}".to_string(),
+            whisper_outgoing: "&7&oYou whisper to {name}: {message}".to_string(),
+        }
+    }
+}
+
+/// Translations of keys clients do not know themselves, such as those of plugins, by locale.
+/// They are loaded from vanilla style `<locale>.json` files mapping keys to text with `%s` in
+/// place of the arguments.
+#[derive(Clone, Debug, Default)]
+pub struct Translations {
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    pub fn add(&mut self, locale: &str, key: &str, text: &str) {
+        self.locales.entry(locale.to_lowercase()).or_default().insert(key.to_string(), text.to_string());
+    }
+
+    /// Loads every `<locale>.json` file in a directory, returning how many translations were read.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let mut loaded = 0;
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let locale = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(locale) => locale.to_string(),
+                None => continue,
+            };
+            let json: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            let entries = json.as_object().ok_or(anyhow!("{} is not an object", path.display()))?;
+            for (key, text) in entries {
+                if let Some(text) = text.as_str() {
+                    self.add(&locale, key, text);
+                    loaded += 1;
+                }
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// The text of a key in a locale, or in `DEFAULT_LOCALE` if the locale does not have it.
+    pub fn get(&self, locale: &str, key: &str) -> Option<&str> {
+        [locale.to_lowercase().as_str(), DEFAULT_LOCALE].iter()
+            .find_map(|locale| self.locales.get(*locale).and_then(|keys| keys.get(key)))
+            .map(String::as_str)
+    }
+
+    /// Replaces the translation components whose keys are known here with text in the locale,
+    /// keeping their style. Other keys are left for the client to translate.
+    pub fn localize(&self, chat: &Chat, locale: &str) -> Chat {
+        let mut localized = match chat {
+            Chat::Translation(body) => match self.get(locale, &body.translate) {
+                Some(template) => {
+                    let mut arguments = body.with.iter();
+                    let mut parts = template.split("%s");
+                    let mut base = body.base.clone();
+                    base.extra = vec![];
+                    let mut filled = Chat::Text(TextComponent { text: parts.next().unwrap_or("").to_string(), base });
+                    for part in parts {
+                        if let Some(argument) = arguments.next() {
+                            base_mut(&mut filled).extra.push(argument.clone());
+                        }
+                        base_mut(&mut filled).extra.push(Chat::from_text(part).boxed());
+                    }
+                    // What was appended to the translation comes after the filled in text.
+                    base_mut(&mut filled).extra.extend(body.base.extra.iter().cloned());
+                    filled
+                }
+                None => chat.clone(),
+            },
+            _ => chat.clone(),
+        };
+        if let Chat::Translation(body) = &mut localized {
+            for argument in body.with.iter_mut() {
+                *argument = self.localize(argument, locale).boxed();
+            }
+        }
+        let base = base_mut(&mut localized);
+        for child in base.extra.iter_mut() {
+            *child = self.localize(child, locale).boxed();
+        }
+        localized
+    }
+}
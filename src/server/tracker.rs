@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use mcproto_rs::types::{Angle, CountedArray, EntityLocation, EntityRotation, Vec3, VarInt};
+use mcproto_rs::v1_16_3::{Packet753 as Packet, PlayDestroyEntitiesSpec, PlayEntityHeadLookSpec, PlayEntityPositionAndRotationSpec, PlayEntityPositionSpec, PlayEntityRotationSpec, PlayEntityTeleportSpec, PlaySpawnPlayerSpec};
+use crate::server::player::{Player, Position};
+
+pub const DEFAULT_TRACKING_RANGE: f64 = 48.0;
+
+/// Positions in relative move packets are sent in 1/4096ths of a block.
+const POSITION_SCALE: f64 = 4096.0;
+
+/// What a viewer was last told about an entity, in protocol units so that relative moves never
+/// drift from the real position.
+#[derive(Clone, Debug)]
+struct TrackedEntity {
+    x: i64,
+    y: i64,
+    z: i64,
+    yaw: u8,
+    pitch: u8,
+}
+
+impl TrackedEntity {
+    fn from_position(position: &Position) -> Self {
+        Self {
+            x: to_fixed(position.x),
+            y: to_fixed(position.y),
+            z: to_fixed(position.z),
+            yaw: to_angle(position.yaw).value,
+            pitch: to_angle(position.pitch).value,
+        }
+    }
+}
+
+/// The set of entities a single client can currently see.
+pub struct EntityTracker {
+    entities: HashMap<i32, TrackedEntity>,
+}
+
+impl EntityTracker {
+    pub fn new() -> Self {
+        Self {
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn is_tracking(&self, entity_id: i32) -> bool {
+        self.entities.contains_key(&entity_id)
+    }
+
+    pub fn tracked_ids(&self) -> Vec<i32> {
+        self.entities.keys().cloned().collect()
+    }
+
+    /// Starts tracking a player, returning the packets that make it appear for the viewer. The
+    /// player must already be in the viewer's tab list.
+    pub fn spawn_player(&mut self, player: &Player) -> Vec<Packet> {
+        let tracked = TrackedEntity::from_position(&player.position);
+
+        let spawn = Packet::PlaySpawnPlayer(PlaySpawnPlayerSpec {
+            entity_id: VarInt::from(player.entity_id),
+            uuid: player.uuid,
+            location: EntityLocation {
+                position: Vec3 {
+                    x: player.position.x,
+                    y: player.position.y,
+                    z: player.position.z,
+                },
+                rotation: EntityRotation {
+                    yaw: Angle { value: tracked.yaw },
+                    pitch: Angle { value: tracked.pitch },
+                },
+            },
+        });
+
+        let head_look = Packet::PlayEntityHeadLook(PlayEntityHeadLookSpec {
+            entity_id: VarInt::from(player.entity_id),
+            head_yaw: Angle { value: tracked.yaw },
+        });
+
+        self.entities.insert(player.entity_id, tracked);
+        vec![spawn, head_look, player.metadata()]
+    }
+
+    /// Returns the packets needed to bring the viewer up to date with a tracked player's position
+    /// and rotation. Moves too large for a relative packet are sent as a teleport.
+    pub fn move_player(&mut self, player: &Player) -> Vec<Packet> {
+        let tracked = match self.entities.get_mut(&player.entity_id) {
+            Some(tracked) => tracked,
+            None => return vec![],
+        };
+
+        let next = TrackedEntity::from_position(&player.position);
+        let entity_id = VarInt::from(player.entity_id);
+        let on_ground = player.position.on_ground;
+        let (dx, dy, dz) = (next.x - tracked.x, next.y - tracked.y, next.z - tracked.z);
+        let moved = dx != 0 || dy != 0 || dz != 0;
+        let rotated = next.yaw != tracked.yaw || next.pitch != tracked.pitch;
+        let relative = [dx, dy, dz].iter().all(|delta| *delta >= i16::MIN as i64 && *delta <= i16::MAX as i64);
+        let rotation = EntityRotation {
+            yaw: Angle { value: next.yaw },
+            pitch: Angle { value: next.pitch },
+        };
+
+        let mut packets = vec![];
+        if moved && !relative {
+            packets.push(Packet::PlayEntityTeleport(PlayEntityTeleportSpec {
+                entity_id,
+                location: EntityLocation {
+                    position: Vec3 {
+                        x: player.position.x,
+                        y: player.position.y,
+                        z: player.position.z,
+                    },
+                    rotation,
+                },
+                on_ground,
+            }));
+        } else if moved && rotated {
+            packets.push(Packet::PlayEntityPositionAndRotation(PlayEntityPositionAndRotationSpec {
+                entity_id,
+                delta: EntityLocation {
+                    position: Vec3 {
+                        x: dx as i16,
+                        y: dy as i16,
+                        z: dz as i16,
+                    },
+                    rotation,
+                },
+                on_ground,
+            }));
+        } else if moved {
+            packets.push(Packet::PlayEntityPosition(PlayEntityPositionSpec {
+                entity_id,
+                delta: Vec3 {
+                    x: dx as i16,
+                    y: dy as i16,
+                    z: dz as i16,
+                },
+                on_ground,
+            }));
+        } else if rotated {
+            packets.push(Packet::PlayEntityRotation(PlayEntityRotationSpec {
+                entity_id,
+                rotation,
+                on_ground,
+            }));
+        }
+
+        if next.yaw != tracked.yaw {
+            packets.push(Packet::PlayEntityHeadLook(PlayEntityHeadLookSpec {
+                entity_id,
+                head_yaw: Angle { value: next.yaw },
+            }));
+        }
+
+        *tracked = next;
+        packets
+    }
+
+    /// Forgets every entity without despawning them, for when the client already dropped them
+    /// itself such as after a respawn.
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    /// Stops tracking the given entities, returning the packet that removes the ones the viewer
+    /// could actually see.
+    pub fn destroy(&mut self, entity_ids: &[i32]) -> Option<Packet> {
+        let removed: Vec<VarInt> = entity_ids
+            .iter()
+            .filter(|id| self.entities.remove(id).is_some())
+            .map(|id| VarInt::from(*id))
+            .collect();
+
+        if removed.is_empty() {
+            None
+        } else {
+            Some(Packet::PlayDestroyEntities(PlayDestroyEntitiesSpec {
+                entity_ids: CountedArray::from(removed),
+            }))
+        }
+    }
+}
+
+pub fn in_tracking_range(viewer: &Position, target: &Position, range: f64) -> bool {
+    if viewer.world != target.world {
+        return false;
+    }
+    let dx = viewer.x - target.x;
+    let dz = viewer.z - target.z;
+    dx.abs() <= range && dz.abs() <= range
+}
+
+pub fn to_angle(degrees: f32) -> Angle {
+    Angle {
+        value: (degrees.rem_euclid(360.0) / 360.0 * 256.0) as u32 as u8,
+    }
+}
+
+fn to_fixed(coordinate: f64) -> i64 {
+    (coordinate * POSITION_SCALE).round() as i64
+}
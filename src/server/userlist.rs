@@ -0,0 +1,424 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use mcproto_rs::uuid::UUID4;
+
+/// Written in place of an expiry date by bans that never expire.
+pub const FOREVER: &str = "forever";
+
+/// An entry of one of the vanilla player lists, identified by name and, when known, UUID.
+pub trait ListEntry: Sized {
+    fn name(&self) -> &str;
+    fn uuid(&self) -> Option<UUID4>;
+    fn to_json(&self) -> Value;
+    fn from_json(json: &Value) -> Result<Self>;
+
+    /// Whether the entry is for the given player. In online mode UUIDs decide when both sides have
+    /// one, since names can change hands. Otherwise names are compared ignoring case, as offline
+    /// players get a new UUID every time they join.
+    fn matches(&self, name: &str, uuid: Option<UUID4>, online: bool) -> bool {
+        match (self.uuid(), uuid) {
+            (Some(entry), Some(uuid)) if online => entry == uuid,
+            _ => (uuid.is_some() && self.uuid() == uuid) || self.name().eq_ignore_ascii_case(name),
+        }
+    }
+
+    /// Whether the entry has run out and should be treated as if it was not there.
+    fn is_expired(&self) -> bool {
+        false
+    }
+}
+
+/// A player on the whitelist, stored in `whitelist.json`.
+#[derive(Clone, Debug)]
+pub struct UserEntry {
+    pub name: String,
+    pub uuid: Option<UUID4>,
+}
+
+/// An operator, stored in `ops.json`.
+#[derive(Clone, Debug)]
+pub struct OpEntry {
+    pub name: String,
+    pub uuid: Option<UUID4>,
+    /// Permission level from 1 to 4.
+    pub level: u8,
+    pub bypasses_player_limit: bool,
+}
+
+/// A banned player, stored in `banned-players.json`.
+#[derive(Clone, Debug)]
+pub struct BanEntry {
+    pub name: String,
+    pub uuid: Option<UUID4>,
+    pub created: String,
+    /// Who issued the ban.
+    pub source: String,
+    /// Expiry date, or `FOREVER`.
+    pub expires: String,
+    pub reason: String,
+}
+
+/// A banned IP address, stored in `banned-ips.json`.
+#[derive(Clone, Debug)]
+pub struct IpBanEntry {
+    pub ip: String,
+    pub created: String,
+    pub source: String,
+    pub expires: String,
+    pub reason: String,
+}
+
+impl BanEntry {
+    /// A ban issued now, lasting until `expires` or forever.
+    pub fn new(name: &str, uuid: Option<UUID4>, source: &str, reason: &str, expires: Option<SystemTime>) -> Self {
+        Self {
+            name: name.to_string(),
+            uuid,
+            created: format_date(SystemTime::now()),
+            source: source.to_string(),
+            expires: expires.map(format_date).unwrap_or(FOREVER.to_string()),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// The message players are turned away with.
+    pub fn kick_message(&self) -> String {
+        ban_message("You are banned from this server.", &self.reason, &self.expires)
+    }
+}
+
+impl IpBanEntry {
+    pub fn new(ip: &str, source: &str, reason: &str, expires: Option<SystemTime>) -> Self {
+        Self {
+            ip: ip.to_string(),
+            created: format_date(SystemTime::now()),
+            source: source.to_string(),
+            expires: expires.map(format_date).unwrap_or(FOREVER.to_string()),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn kick_message(&self) -> String {
+        ban_message("Your IP address is banned from this server.", &self.reason, &self.expires)
+    }
+}
+
+fn ban_message(message: &str, reason: &str, expires: &str) -> String {
+    let mut message = format!("{}\nReason: {}", message, reason);
+    if expires != FOREVER {
+        message.push_str(&format!("\nYour ban will be removed on {}", expires));
+    }
+    message
+}
+
+/// Whether a ban with the given expiry date has run out. Dates that cannot be read never expire.
+fn ban_expired(expires: &str) -> bool {
+    parse_date(expires).map(|expires| expires <= SystemTime::now()).unwrap_or(false)
+}
+
+fn field<'a>(json: &'a Value, name: &str) -> Result<&'a str> {
+    json[name].as_str().ok_or(anyhow!("Missing {} in list entry {}", name, json))
+}
+
+fn uuid_field(json: &Value) -> Option<UUID4> {
+    json["uuid"].as_str().and_then(UUID4::parse)
+}
+
+fn uuid_json(uuid: Option<UUID4>) -> Value {
+    uuid.map(|uuid| Value::String(uuid.to_string())).unwrap_or(Value::Null)
+}
+
+impl ListEntry for UserEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn uuid(&self) -> Option<UUID4> {
+        self.uuid
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "uuid": uuid_json(self.uuid), "name": self.name })
+    }
+
+    fn from_json(json: &Value) -> Result<Self> {
+        Ok(Self { name: field(json, "name")?.to_string(), uuid: uuid_field(json) })
+    }
+}
+
+impl ListEntry for OpEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn uuid(&self) -> Option<UUID4> {
+        self.uuid
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "uuid": uuid_json(self.uuid),
+            "name": self.name,
+            "level": self.level,
+            "bypassesPlayerLimit": self.bypasses_player_limit,
+        })
+    }
+
+    fn from_json(json: &Value) -> Result<Self> {
+        Ok(Self {
+            name: field(json, "name")?.to_string(),
+            uuid: uuid_field(json),
+            level: json["level"].as_u64().unwrap_or(4).min(4) as u8,
+            bypasses_player_limit: json["bypassesPlayerLimit"].as_bool().unwrap_or(false),
+        })
+    }
+}
+
+impl ListEntry for BanEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn uuid(&self) -> Option<UUID4> {
+        self.uuid
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "uuid": uuid_json(self.uuid),
+            "name": self.name,
+            "created": self.created,
+            "source": self.source,
+            "expires": self.expires,
+            "reason": self.reason,
+        })
+    }
+
+    fn from_json(json: &Value) -> Result<Self> {
+        Ok(Self {
+            name: field(json, "name")?.to_string(),
+            uuid: uuid_field(json),
+            created: json["created"].as_str().unwrap_or("").to_string(),
+            source: json["source"].as_str().unwrap_or("(Unknown)").to_string(),
+            expires: json["expires"].as_str().unwrap_or(FOREVER).to_string(),
+            reason: json["reason"].as_str().unwrap_or("Banned by an operator.").to_string(),
+        })
+    }
+
+    fn is_expired(&self) -> bool {
+        ban_expired(&self.expires)
+    }
+}
+
+impl ListEntry for IpBanEntry {
+    fn name(&self) -> &str {
+        &self.ip
+    }
+
+    fn uuid(&self) -> Option<UUID4> {
+        None
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "ip": self.ip,
+            "created": self.created,
+            "source": self.source,
+            "expires": self.expires,
+            "reason": self.reason,
+        })
+    }
+
+    fn from_json(json: &Value) -> Result<Self> {
+        Ok(Self {
+            ip: field(json, "ip")?.to_string(),
+            created: json["created"].as_str().unwrap_or("").to_string(),
+            source: json["source"].as_str().unwrap_or("(Unknown)").to_string(),
+            expires: json["expires"].as_str().unwrap_or(FOREVER).to_string(),
+            reason: json["reason"].as_str().unwrap_or("Banned by an operator.").to_string(),
+        })
+    }
+
+    fn is_expired(&self) -> bool {
+        ban_expired(&self.expires)
+    }
+}
+
+/// One of the JSON player lists vanilla servers keep next to the world.
+pub struct UserList<T: ListEntry> {
+    path: PathBuf,
+    entries: Vec<T>,
+    /// Whether the server authenticates players, making their UUIDs authoritative.
+    online: bool,
+}
+
+impl<T: ListEntry> UserList<T> {
+    pub fn new<P: AsRef<Path>>(path: P, online: bool) -> Self {
+        Self { path: path.as_ref().to_path_buf(), entries: vec![], online }
+    }
+
+    /// Replaces the entries with those on disk. A missing file is an empty list.
+    pub fn load(&mut self) -> Result<usize> {
+        if !self.path.exists() {
+            self.entries.clear();
+            return Ok(0);
+        }
+        let json: Value = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        let entries = json.as_array().ok_or(anyhow!("{} is not a list", self.path.display()))?;
+        self.entries = entries.iter().map(T::from_json).collect::<Result<Vec<T>>>()?;
+        self.remove_expired();
+        Ok(self.entries.len())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let json = Value::Array(self.entries.iter().map(T::to_json).collect());
+        fs::write(&self.path, serde_json::to_string_pretty(&json)?)?;
+        Ok(())
+    }
+
+    /// The entry for a player, ignoring expired ones.
+    pub fn get(&self, name: &str, uuid: Option<UUID4>) -> Option<&T> {
+        self.entries.iter().find(|entry| entry.matches(name, uuid, self.online) && !entry.is_expired())
+    }
+
+    pub fn contains(&self, name: &str, uuid: Option<UUID4>) -> bool {
+        self.get(name, uuid).is_some()
+    }
+
+    /// Adds an entry, replacing any for the same player.
+    pub fn add(&mut self, entry: T) {
+        let (name, uuid) = (entry.name().to_string(), entry.uuid());
+        let online = self.online;
+        self.entries.retain(|existing| !existing.matches(&name, uuid, online));
+        self.entries.push(entry);
+    }
+
+    pub fn remove(&mut self, name: &str, uuid: Option<UUID4>) -> Option<T> {
+        let index = self.entries.iter().position(|entry| entry.matches(name, uuid, self.online))?;
+        Some(self.entries.remove(index))
+    }
+
+    pub fn entries(&self) -> &[T] {
+        &self.entries
+    }
+
+    /// Drops expired entries, returning whether there were any.
+    pub fn remove_expired(&mut self) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| !entry.is_expired());
+        self.entries.len() != before
+    }
+}
+
+/// Formats a time the way vanilla writes dates in ban lists, e.g. `2021-03-04 12:34:56 +0000`.
+pub fn format_date(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0);
+    let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} +0000", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Reads a date written by `format_date`, honouring its UTC offset.
+pub fn parse_date(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split(' ');
+    let ymd = parts.next()?.split('-').map(|part| part.parse::<i64>().ok()).collect::<Option<Vec<i64>>>()?;
+    let hms = parts.next()?.split(':').map(|part| part.parse::<i64>().ok()).collect::<Option<Vec<i64>>>()?;
+    let offset = parts.next().unwrap_or("+0000");
+    if ymd.len() != 3 || hms.len() != 3 || offset.len() != 5 {
+        return None;
+    }
+    let offset_minutes = offset[1..3].parse::<i64>().ok()? * 60 + offset[3..].parse::<i64>().ok()?;
+    let offset_seconds = if offset.starts_with('-') { -offset_minutes * 60 } else { offset_minutes * 60 };
+    let seconds = days_from_civil(ymd[0], ymd[1], ymd[2]).checked_mul(86400)?
+        .checked_add(hms[0] * 3600 + hms[1] * 60 + hms[2] - offset_seconds)?;
+    if seconds < 0 {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+}
+
+/// Reads a ban duration such as `30m`, `12h` or `1d12h`. Units are `s`, `m`, `h`, `d` and `w`.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || anyhow!("Invalid duration '{}', expected something like 30m, 12h or 7d", text);
+    let mut seconds = 0u64;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return Err(invalid()),
+        };
+        let amount = number.parse::<u64>().ok().and_then(|number| number.checked_mul(unit)).ok_or_else(invalid)?;
+        seconds = seconds.checked_add(amount).ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() || seconds == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// When a ban of the given duration issued now ends. Durations reaching past what the clock can
+/// represent are invalid.
+pub fn parse_expiry(text: &str) -> Result<SystemTime> {
+    SystemTime::now().checked_add(parse_duration(text)?)
+        .ok_or_else(|| anyhow!("Invalid duration '{}', expected something like 30m, 12h or 7d", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_durations_are_invalid() {
+        assert_eq!(parse_duration("1d12h").unwrap(), Duration::from_secs(129600));
+        assert!(parse_duration("1000000000000000d").is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+        assert!(parse_expiry("18446744073709551615s").is_err());
+        let far = parse_expiry("9000000000000000000s").unwrap();
+        assert!(parse_date(&format_date(far)).map(|date| date > SystemTime::now()).unwrap_or(false));
+        assert!(parse_expiry("30m").is_ok());
+    }
+
+    #[test]
+    fn online_entries_match_by_uuid_when_both_are_known() {
+        let op = UUID4::parse("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+        let other = UUID4::parse("61699b2e-d327-4a01-9f1e-0ea8c3f06bc6").unwrap();
+        let entry = UserEntry { name: "Notch".to_string(), uuid: Some(op) };
+        assert!(entry.matches("Someone", Some(op), true));
+        assert!(!entry.matches("notch", Some(other), true));
+        assert!(entry.matches("notch", None, true));
+        assert!(entry.matches("notch", Some(other), false));
+        let unknown = UserEntry { name: "Notch".to_string(), uuid: None };
+        assert!(unknown.matches("notch", Some(other), true));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+use mcproto_rs::Serializer;
+use mcproto_rs::nbt::Tag;
+use mcproto_rs::types::{BytesSerializer, ChunkPosition, CountedArray, IntPosition, NamedNbtTag, VarInt};
+use mcproto_rs::v1_16_3::{ChunkData, GameChangeReason, LightingData, LightingUpdateSpec, Packet753 as Packet, PlayChangeGameStateSpec, PlayChunkDataWrapper, PlayTimeUpdateSpec, PlayUpdateLightSpec, LIGHT_DATA_LENGTH, LIGHT_DATA_SECTIONS};
+use crate::server::block;
+use crate::server::container::BlockEntity;
+use crate::server::dimension::DimensionType;
+
+pub const SECTION_WIDTH: usize = 16;
+pub const SECTION_VOLUME: usize = SECTION_WIDTH * SECTION_WIDTH * SECTION_WIDTH;
+pub const SECTIONS_PER_CHUNK: usize = 16;
+pub const WORLD_HEIGHT: i32 = (SECTION_WIDTH * SECTIONS_PER_CHUNK) as i32;
+
+/// Bits per entry of the global palette in 1.16.3.
+const GLOBAL_PALETTE_BITS: u8 = 15;
+const MIN_PALETTE_BITS: u8 = 4;
+const MAX_PALETTE_BITS: u8 = 8;
+const BIOMES_PER_CHUNK: usize = 1024;
+const HEIGHTMAP_BITS: u8 = 9;
+/// Ticks in a full day.
+pub const DAY_LENGTH: i64 = 24000;
+
+/// A 16x16x16 cube of block states.
+#[derive(Clone)]
+pub struct ChunkSection {
+    blocks: Vec<u32>,
+    block_count: u16,
+}
+
+impl ChunkSection {
+    pub fn new_empty() -> Self {
+        Self {
+            blocks: vec![block::AIR; SECTION_VOLUME],
+            block_count: 0,
+        }
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        (y * SECTION_WIDTH + z) * SECTION_WIDTH + x
+    }
+
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.blocks[Self::index(x, y, z)]
+    }
+
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: u32) {
+        let index = Self::index(x, y, z);
+        let previous = self.blocks[index];
+        if previous == block::AIR && state != block::AIR {
+            self.block_count += 1;
+        } else if previous != block::AIR && state == block::AIR {
+            self.block_count -= 1;
+        }
+        self.blocks[index] = state;
+    }
+
+    /// Number of non-air blocks in the section.
+    pub fn block_count(&self) -> u16 {
+        self.block_count
+    }
+
+    pub fn blocks(&self) -> &[u32] {
+        &self.blocks
+    }
+}
+
+/// A full column of sections at the given chunk coordinates.
+#[derive(Clone)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    sections: Vec<Option<ChunkSection>>,
+    /// Keyed by absolute block coordinates.
+    block_entities: HashMap<(i32, i32, i32), BlockEntity>,
+}
+
+impl Chunk {
+    pub fn new_empty(x: i32, z: i32) -> Self {
+        Self {
+            x,
+            z,
+            sections: vec![None; SECTIONS_PER_CHUNK],
+            block_entities: HashMap::new(),
+        }
+    }
+
+    /// Coordinates are relative to the chunk, y is absolute.
+    pub fn get_block(&self, x: usize, y: i32, z: usize) -> u32 {
+        if y < 0 || y >= WORLD_HEIGHT {
+            return block::AIR;
+        }
+        let y = y as usize;
+        match &self.sections[y / SECTION_WIDTH] {
+            Some(section) => section.get_block(x, y % SECTION_WIDTH, z),
+            None => block::AIR,
+        }
+    }
+
+    pub fn set_block(&mut self, x: usize, y: i32, z: usize, state: u32) {
+        if y < 0 || y >= WORLD_HEIGHT {
+            return;
+        }
+        let y = y as usize;
+        let section = self.sections[y / SECTION_WIDTH].get_or_insert_with(ChunkSection::new_empty);
+        section.set_block(x, y % SECTION_WIDTH, z, state);
+    }
+
+    pub fn sections(&self) -> &[Option<ChunkSection>] {
+        &self.sections
+    }
+
+    /// Height of the block above the highest non-air block in a column.
+    pub fn highest_block(&self, x: usize, z: usize) -> i32 {
+        (0..WORLD_HEIGHT)
+            .rev()
+            .find(|y| self.get_block(x, *y, z) != block::AIR)
+            .map(|y| y + 1)
+            .unwrap_or(0)
+    }
+
+    /// Encodes the chunk as the light update and chunk data packets sent to clients, in that order.
+    pub fn to_packets(&self, biome_id: i32, has_skylight: bool) -> Vec<Packet> {
+        let mut primary_bit_mask = 0;
+        let mut data = BytesSerializer::default();
+        for (index, section) in self.sections.iter().enumerate() {
+            if let Some(section) = section {
+                if section.block_count() > 0 {
+                    primary_bit_mask |= 1 << index;
+                    write_section(&mut data, section);
+                }
+            }
+        }
+
+        let mut heights = Vec::with_capacity(SECTION_WIDTH * SECTION_WIDTH);
+        for z in 0..SECTION_WIDTH {
+            for x in 0..SECTION_WIDTH {
+                heights.push(self.highest_block(x, z) as u32);
+            }
+        }
+
+        let chunk_data = ChunkData {
+            position: ChunkPosition { x: self.x, z: self.z },
+            primary_bit_mask: VarInt::from(primary_bit_mask),
+            heightmaps: NamedNbtTag {
+                root: Tag::Compound(vec![
+                    Tag::LongArray(pack(&heights, HEIGHTMAP_BITS)).with_name("MOTION_BLOCKING"),
+                ]).with_name(""),
+            },
+            biomes: Some(CountedArray::from(vec![VarInt::from(biome_id); BIOMES_PER_CHUNK])),
+            data: CountedArray::from(data.into_bytes()),
+            block_entities: self.block_entities.iter()
+                .map(|((x, y, z), entity)| NamedNbtTag { root: entity.to_nbt(*x, *y, *z) })
+                .collect(),
+        };
+
+        let skylight = if has_skylight {
+            Box::new([Some([0xFF; LIGHT_DATA_LENGTH]); LIGHT_DATA_SECTIONS])
+        } else {
+            Box::new([None; LIGHT_DATA_SECTIONS])
+        };
+        let light = PlayUpdateLightSpec {
+            chunk: ChunkPosition { x: VarInt::from(self.x), z: VarInt::from(self.z) },
+            trust_edges: true,
+            update: LightingUpdateSpec {
+                skylight_data: LightingData { data: skylight },
+                blocklight_data: LightingData { data: Box::new([None; LIGHT_DATA_SECTIONS]) },
+            },
+        };
+
+        vec![
+            Packet::PlayUpdateLight(light),
+            Packet::PlayChunkData(PlayChunkDataWrapper { data: chunk_data }),
+        ]
+    }
+}
+
+/// Writes a section using an indirect palette when it is small enough, otherwise the global one.
+fn write_section(to: &mut BytesSerializer, section: &ChunkSection) {
+    let mut palette: Vec<u32> = vec![];
+    for state in section.blocks() {
+        if !palette.contains(state) {
+            palette.push(*state);
+            if palette.len() > 1 << MAX_PALETTE_BITS {
+                break;
+            }
+        }
+    }
+
+    let _ = to.serialize_other(&(section.block_count() as i16));
+    if palette.len() <= 1 << MAX_PALETTE_BITS {
+        let bits = (32 - (palette.len() as u32 - 1).leading_zeros()).max(MIN_PALETTE_BITS as u32) as u8;
+        let _ = to.serialize_byte(bits);
+        let _ = to.serialize_other(&VarInt::from(palette.len() as i32));
+        for state in &palette {
+            let _ = to.serialize_other(&VarInt::from(*state as i32));
+        }
+        let values: Vec<u32> = section
+            .blocks()
+            .iter()
+            .map(|state| palette.iter().position(|entry| entry == state).unwrap() as u32)
+            .collect();
+        write_longs(to, &pack(&values, bits));
+    } else {
+        let _ = to.serialize_byte(GLOBAL_PALETTE_BITS);
+        write_longs(to, &pack(section.blocks(), GLOBAL_PALETTE_BITS));
+    }
+}
+
+fn write_longs(to: &mut BytesSerializer, longs: &[i64]) {
+    let _ = to.serialize_other(&VarInt::from(longs.len() as i32));
+    for long in longs {
+        let _ = to.serialize_other(long);
+    }
+}
+
+/// Packs values into longs without letting an entry span two longs, as 1.16 expects.
+fn pack(values: &[u32], bits: u8) -> Vec<i64> {
+    let per_long = 64 / bits as usize;
+    let mut longs = vec![0u64; (values.len() + per_long - 1) / per_long];
+    for (index, value) in values.iter().enumerate() {
+        let offset = (index % per_long) * bits as usize;
+        longs[index / per_long] |= (*value as u64) << offset;
+    }
+    longs.into_iter().map(|long| long as i64).collect()
+}
+
+/// A block state change that clients with the chunk loaded need to be told about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockChange {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub state: u32,
+}
+
+/// Source of chunks that have not been generated or loaded yet.
+pub trait ChunkProvider: Send + Sync {
+    fn provide(&self, x: i32, z: i32) -> Chunk;
+}
+
+/// Generates a superflat world from a list of layers, starting at y = 0.
+pub struct FlatChunkProvider {
+    layers: Vec<u32>,
+}
+
+impl FlatChunkProvider {
+    pub fn new(layers: Vec<u32>) -> Self {
+        Self { layers }
+    }
+}
+
+impl Default for FlatChunkProvider {
+    fn default() -> Self {
+        Self::new(vec![block::BEDROCK, block::DIRT, block::DIRT, block::GRASS_BLOCK])
+    }
+}
+
+impl ChunkProvider for FlatChunkProvider {
+    fn provide(&self, x: i32, z: i32) -> Chunk {
+        let mut chunk = Chunk::new_empty(x, z);
+        for (y, state) in self.layers.iter().enumerate() {
+            for block_x in 0..SECTION_WIDTH {
+                for block_z in 0..SECTION_WIDTH {
+                    chunk.set_block(block_x, y as i32, block_z, *state);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+pub struct World {
+    pub name: String,
+    pub dimension_type: DimensionType,
+    /// Biome every chunk of the world is filled with.
+    pub biome: String,
+    pub spawn: IntPosition,
+    /// World nether portals lead to.
+    pub nether_link: Option<String>,
+    /// World end portals lead to.
+    pub end_link: Option<String>,
+    /// Ticks since the world was created.
+    pub age: i64,
+    /// Ticks since the first sunrise, a day being `DAY_LENGTH` ticks.
+    pub time: i64,
+    pub weather: Weather,
+    /// Ticks left until the weather clears, `None` if it lasts until changed.
+    pub weather_ticks: Option<i32>,
+    /// Whether clients render the world as superflat, with the horizon at y 0 and no void fog.
+    pub is_flat: bool,
+    chunks: HashMap<(i32, i32), Chunk>,
+    provider: Box<dyn ChunkProvider>,
+}
+
+impl World {
+    pub fn new(name: &str, dimension_type: DimensionType, provider: Box<dyn ChunkProvider>) -> Self {
+        Self {
+            name: name.to_string(),
+            dimension_type,
+            biome: "minecraft:plains".to_string(),
+            spawn: IntPosition { x: 0, y: 64, z: 0 },
+            nether_link: None,
+            end_link: None,
+            age: 0,
+            time: 0,
+            weather: Weather::Clear,
+            weather_ticks: None,
+            is_flat: false,
+            chunks: HashMap::new(),
+            provider,
+        }
+    }
+
+    /// Advances time and weather by a tick, returning whether the weather changed.
+    pub fn tick(&mut self, daylight_cycle: bool) -> bool {
+        self.age += 1;
+        if daylight_cycle && self.dimension_type.fixed_time.is_none() {
+            self.time += 1;
+        }
+        match self.weather_ticks {
+            Some(ticks) if ticks <= 1 => {
+                self.set_weather(Weather::Clear, None);
+                true
+            }
+            Some(ticks) => {
+                self.weather_ticks = Some(ticks - 1);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_weather(&mut self, weather: Weather, duration: Option<i32>) {
+        self.weather = weather;
+        self.weather_ticks = duration;
+    }
+
+    /// The time update players in this world are sent. Clients keep the sun moving on their own
+    /// unless the time is negative.
+    pub fn time_packet(&self, daylight_cycle: bool) -> Packet {
+        let time_of_day = if daylight_cycle { self.time } else { -self.time.max(1) };
+        Packet::PlayTimeUpdate(PlayTimeUpdateSpec { world_age: self.age, time_of_day })
+    }
+
+    /// The game state changes that bring a client in line with the world's weather.
+    pub fn weather_packets(&self) -> Vec<Packet> {
+        let (raining, rain, thunder) = match self.weather {
+            Weather::Clear => (false, 0.0, 0.0),
+            Weather::Rain => (true, 1.0, 0.0),
+            Weather::Thunder => (true, 1.0, 1.0),
+        };
+        let reasons = vec![
+            if raining { GameChangeReason::BeginRaining } else { GameChangeReason::EndRaining },
+            GameChangeReason::RainLevelChange(rain),
+            GameChangeReason::ThunderLevelChange(thunder),
+        ];
+        reasons.into_iter().map(|reason| Packet::PlayChangeGameState(PlayChangeGameStateSpec { reason })).collect()
+    }
+
+    /// Returns the chunk at the given chunk coordinates, generating it if necessary.
+    pub fn get_chunk(&mut self, x: i32, z: i32) -> &mut Chunk {
+        let provider = &self.provider;
+        self.chunks.entry((x, z)).or_insert_with(|| provider.provide(x, z))
+    }
+
+    pub fn get_block(&mut self, x: i32, y: i32, z: i32) -> u32 {
+        self.get_chunk(x >> 4, z >> 4)
+            .get_block((x & 15) as usize, y, (z & 15) as usize)
+    }
+
+    /// Sets a block. If it replaces a different block, that block's entity is removed and returned
+    /// so its contents are not silently lost.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, state: u32) -> Option<BlockEntity> {
+        let chunk = self.get_chunk(x >> 4, z >> 4);
+        let previous = chunk.get_block((x & 15) as usize, y, (z & 15) as usize);
+        let removed = if block::get_block(previous).map(|block| block.name) != block::get_block(state).map(|block| block.name) {
+            chunk.block_entities.remove(&(x, y, z))
+        } else {
+            None
+        };
+        chunk.set_block((x & 15) as usize, y, (z & 15) as usize, state);
+        removed
+    }
+
+    pub fn get_block_entity(&mut self, x: i32, y: i32, z: i32) -> Option<&BlockEntity> {
+        self.get_chunk(x >> 4, z >> 4).block_entities.get(&(x, y, z))
+    }
+
+    pub fn get_block_entity_mut(&mut self, x: i32, y: i32, z: i32) -> Option<&mut BlockEntity> {
+        self.get_chunk(x >> 4, z >> 4).block_entities.get_mut(&(x, y, z))
+    }
+
+    /// Every block entity in the world's generated chunks, keyed by position.
+    pub fn block_entities_mut(&mut self) -> impl Iterator<Item = (&(i32, i32, i32), &mut BlockEntity)> {
+        self.chunks.values_mut().flat_map(|chunk| chunk.block_entities.iter_mut())
+    }
+
+    pub fn set_block_entity(&mut self, x: i32, y: i32, z: i32, entity: BlockEntity) {
+        self.get_chunk(x >> 4, z >> 4).block_entities.insert((x, y, z), entity);
+    }
+
+    /// Height of the block above the highest non-air block in a column.
+    pub fn highest_block(&mut self, x: i32, z: i32) -> i32 {
+        self.get_chunk(x >> 4, z >> 4).highest_block((x & 15) as usize, (z & 15) as usize)
+    }
+
+    /// Finds the block matching `filter` closest to the given column, only looking through chunks
+    /// that have already been generated.
+    pub fn find_block<F: Fn(u32) -> bool>(&self, x: i32, z: i32, radius: i32, filter: F) -> Option<(i32, i32, i32)> {
+        let mut closest: Option<((i32, i32, i32), i64)> = None;
+        for ((chunk_x, chunk_z), chunk) in &self.chunks {
+            let base_x = chunk_x * SECTION_WIDTH as i32;
+            let base_z = chunk_z * SECTION_WIDTH as i32;
+            if base_x + 15 < x - radius || base_x > x + radius || base_z + 15 < z - radius || base_z > z + radius {
+                continue;
+            }
+
+            for (index, section) in chunk.sections().iter().enumerate() {
+                let section = match section {
+                    Some(section) if section.block_count() > 0 => section,
+                    _ => continue,
+                };
+                for (offset, state) in section.blocks().iter().enumerate() {
+                    if !filter(*state) {
+                        continue;
+                    }
+                    let block_x = base_x + (offset % SECTION_WIDTH) as i32;
+                    let block_z = base_z + ((offset / SECTION_WIDTH) % SECTION_WIDTH) as i32;
+                    let block_y = (index * SECTION_WIDTH + offset / (SECTION_WIDTH * SECTION_WIDTH)) as i32;
+                    if (block_x - x).abs() > radius || (block_z - z).abs() > radius {
+                        continue;
+                    }
+                    let distance = ((block_x - x) as i64).pow(2) + ((block_z - z) as i64).pow(2);
+                    if closest.map(|(_, closest)| distance < closest).unwrap_or(true) {
+                        closest = Some(((block_x, block_y, block_z), distance));
+                    }
+                }
+            }
+        }
+        closest.map(|(position, _)| position)
+    }
+}
+
+/// Every world loaded by the server, keyed by name.
+pub struct WorldManager {
+    worlds: HashMap<String, World>,
+    default_world: String,
+}
+
+impl WorldManager {
+    pub fn new(default_world: World) -> Self {
+        let name = default_world.name.clone();
+        let mut worlds = HashMap::new();
+        worlds.insert(name.clone(), default_world);
+        Self {
+            worlds,
+            default_world: name,
+        }
+    }
+
+    /// A flat overworld named `world` along with its nether and end.
+    pub fn vanilla() -> Self {
+        let mut overworld = World::new("world", DimensionType::overworld(), Box::new(FlatChunkProvider::default()));
+        overworld.spawn = IntPosition { x: 0, y: 4, z: 0 };
+
+        let mut nether = World::new("world_nether", DimensionType::nether(), Box::new(FlatChunkProvider::new(vec![block::BEDROCK, block::STONE, block::STONE, block::STONE])));
+        nether.biome = "minecraft:nether_wastes".to_string();
+        nether.spawn = IntPosition { x: 0, y: 4, z: 0 };
+
+        let mut end = World::new("world_the_end", DimensionType::end(), Box::new(FlatChunkProvider::new(vec![block::STONE; 4])));
+        end.biome = "minecraft:the_end".to_string();
+        end.spawn = IntPosition { x: 0, y: 4, z: 0 };
+
+        overworld.nether_link = Some(nether.name.clone());
+        overworld.end_link = Some(end.name.clone());
+        nether.nether_link = Some(overworld.name.clone());
+        end.end_link = Some(overworld.name.clone());
+
+        let mut manager = Self::new(overworld);
+        manager.add_world(nether);
+        manager.add_world(end);
+        manager
+    }
+
+    /// Adds a world, replacing any loaded world with the same name.
+    pub fn add_world(&mut self, world: World) {
+        self.worlds.insert(world.name.clone(), world);
+    }
+
+    /// Unloads a world. The default world cannot be removed.
+    pub fn remove_world(&mut self, name: &str) -> Option<World> {
+        if name == self.default_world {
+            return None;
+        }
+        self.worlds.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&World> {
+        self.worlds.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut World> {
+        self.worlds.get_mut(name)
+    }
+
+    pub fn default_world_name(&self) -> &str {
+        &self.default_world
+    }
+
+    pub fn default_world_mut(&mut self) -> &mut World {
+        self.worlds.get_mut(&self.default_world).expect("Default world is always loaded.")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &World> {
+        self.worlds.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut World> {
+        self.worlds.values_mut()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.worlds.keys().cloned().collect()
+    }
+}